@@ -0,0 +1,87 @@
+//! Reference keystore server for nodes started with `--keystore-uri`, so that their Aura and
+//! GRANDPA keys are kept out of the node process. See `node_template::remote_keystore` for the
+//! protocol.
+
+use clap::Parser;
+use node_template::keystore_server::{serve, Connection};
+use sc_keystore::LocalKeystore;
+use sp_core::crypto::SecretString;
+use std::{
+	fs::{self, Permissions},
+	net::{SocketAddr, TcpListener},
+	os::unix::{fs::PermissionsExt, net::UnixListener},
+	path::PathBuf,
+	sync::Arc,
+};
+
+/// Serve a keystore to nodes started with `--keystore-uri`.
+#[derive(Debug, clap::Parser)]
+struct Args {
+	/// Directory of the keystore. Without it, keys are kept in memory and lost on exit.
+	#[clap(long, value_name = "PATH")]
+	keystore_path: Option<PathBuf>,
+
+	/// File that contains the password of the keystore.
+	#[clap(long, value_name = "PATH")]
+	password_filename: Option<PathBuf>,
+
+	/// Loopback address to serve HTTP on, for `--keystore-uri http://<address>`.
+	#[clap(long, value_name = "ADDRESS", default_value = "127.0.0.1:9955")]
+	listen: SocketAddr,
+
+	/// Unix socket to serve on instead, for `--keystore-uri unix://<path>`.
+	#[clap(long, value_name = "PATH")]
+	unix_socket: Option<PathBuf>,
+}
+
+fn main() -> Result<(), String> {
+	let args = Args::parse();
+	let password = match &args.password_filename {
+		Some(path) => Some(SecretString::new(
+			fs::read_to_string(path).map_err(|e| format!("Unable to read password: {}", e))?,
+		)),
+		None => None,
+	};
+	let keystore = match &args.keystore_path {
+		Some(path) => LocalKeystore::open(path, password)
+			.map_err(|e| format!("Unable to open keystore: {}", e))?,
+		None => LocalKeystore::in_memory(),
+	};
+	let keystore = Arc::new(keystore);
+
+	match &args.unix_socket {
+		Some(path) => {
+			let listener = UnixListener::bind(path).map_err(|e| e.to_string())?;
+			// Only the user the server runs as may connect and use its keys.
+			fs::set_permissions(path, Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+			eprintln!("Serving keystore on unix://{}", path.display());
+			for stream in listener.incoming() {
+				spawn(keystore.clone(), stream.map_err(|e| e.to_string())?);
+			}
+		},
+		None => {
+			// The protocol has no authentication, so keys must not be reachable over the network.
+			if !args.listen.ip().is_loopback() {
+				return Err(format!(
+					"{} is not a loopback address, serve on localhost or a Unix socket",
+					args.listen
+				))
+			}
+			let listener = TcpListener::bind(args.listen).map_err(|e| e.to_string())?;
+			eprintln!("Serving keystore on http://{}", args.listen);
+			for stream in listener.incoming() {
+				spawn(keystore.clone(), stream.map_err(|e| e.to_string())?);
+			}
+		},
+	}
+	Ok(())
+}
+
+// Answer the request on `stream` in a thread of its own.
+fn spawn(keystore: Arc<LocalKeystore>, stream: impl Connection + Send + 'static) {
+	std::thread::spawn(move || {
+		if let Err(e) = serve(&keystore, stream) {
+			eprintln!("Unable to answer request: {}", e);
+		}
+	});
+}
@@ -1,7 +1,10 @@
 use node_template_runtime::{
-	AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-	SystemConfig, WASM_BINARY,
+	AccountId, AssetsConfig, AuraConfig, Balance, BalancesConfig, BlockNumber, DevIdentitiesConfig,
+	EngineeringGuild, GenesisConfig, GovernanceAssetId, GrandpaConfig, QuadraticVoting,
+	QuadraticVotingConfig, Signature, SudoConfig, SystemConfig, DOLLARS, EXISTENTIAL_DEPOSIT,
+	HOURS, WASM_BINARY,
 };
+use pallet_quadratic_voting::Redistribution;
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{sr25519, Pair, Public};
@@ -36,6 +39,31 @@ pub fn authority_keys_from_seed(s: &str) -> (AuraId, GrandpaId) {
 	(get_from_seed::<AuraId>(s), get_from_seed::<GrandpaId>(s))
 }
 
+/// Well-known development accounts that get an identity at genesis.
+const DEV_ACCOUNTS: [&str; 6] = ["Alice", "Bob", "Charlie", "Dave", "Eve", "Ferdie"];
+
+/// Identities of the well-known development accounts, named after their seed.
+fn dev_identities() -> Vec<(AccountId, Vec<u8>)> {
+	DEV_ACCOUNTS
+		.iter()
+		.map(|seed| (get_account_id_from_seed::<sr25519::Public>(seed), seed.as_bytes().to_vec()))
+		.collect()
+}
+
+/// Minimum balance of the governance token.
+const GOVERNANCE_MIN_BALANCE: Balance = 1;
+
+/// Number of blocks the demo proposals are open for.
+const DEMO_VOTING_PERIOD: BlockNumber = 24 * HOURS;
+
+/// Proposals of `proposer` that demo chains start with.
+fn demo_proposals(proposer: AccountId) -> Vec<(AccountId, Vec<u8>, Option<Redistribution>)> {
+	vec![
+		(proposer.clone(), b"Upgrade the runtime".to_vec(), None),
+		(proposer, b"Fund the community treasury".to_vec(), Some(Redistribution::Voters)),
+	]
+}
+
 pub fn development_config() -> Result<ChainSpec, String> {
 	let wasm_binary = WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?;
 
@@ -56,9 +84,19 @@ pub fn development_config() -> Result<ChainSpec, String> {
 				vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
+					get_account_id_from_seed::<sr25519::Public>("Charlie"),
+					get_account_id_from_seed::<sr25519::Public>("Dave"),
+					get_account_id_from_seed::<sr25519::Public>("Eve"),
+					get_account_id_from_seed::<sr25519::Public>("Ferdie"),
 					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
 				],
+				// Identity registrar
+				Some(get_account_id_from_seed::<sr25519::Public>("Alice")),
+				// Accounts with a judged identity
+				dev_identities(),
+				// Proposals open for voting at genesis
+				demo_proposals(get_account_id_from_seed::<sr25519::Public>("Alice")),
 				true,
 			)
 		},
@@ -107,6 +145,12 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
 					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
 				],
+				// Identity registrar
+				Some(get_account_id_from_seed::<sr25519::Public>("Alice")),
+				// Accounts with a judged identity
+				dev_identities(),
+				// Proposals open for voting at genesis
+				demo_proposals(get_account_id_from_seed::<sr25519::Public>("Alice")),
 				true,
 			)
 		},
@@ -130,6 +174,9 @@ fn testnet_genesis(
 	initial_authorities: Vec<(AuraId, GrandpaId)>,
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
+	registrar: Option<AccountId>,
+	identities: Vec<(AccountId, Vec<u8>)>,
+	proposals: Vec<(AccountId, Vec<u8>, Option<Redistribution>)>,
 	_enable_println: bool,
 ) -> GenesisConfig {
 	GenesisConfig {
@@ -138,8 +185,14 @@ fn testnet_genesis(
 			code: wasm_binary.to_vec(),
 		},
 		balances: BalancesConfig {
-			// Configure endowed accounts with initial balance of 1 << 60.
-			balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
+			// Configure endowed accounts with initial balance of 1 << 60. The account holding the
+			// pots gets the existential deposit, so paying a pot out in full can't reap it.
+			balances: endowed_accounts
+				.iter()
+				.cloned()
+				.map(|k| (k, 1 << 60))
+				.chain([(QuadraticVoting::account_id(), EXISTENTIAL_DEPOSIT)])
+				.collect(),
 		},
 		aura: AuraConfig {
 			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
@@ -149,8 +202,37 @@ fn testnet_genesis(
 		},
 		sudo: SudoConfig {
 			// Assign network admin rights.
-			key: Some(root_key),
+			key: Some(root_key.clone()),
 		},
 		transaction_payment: Default::default(),
+		quadratic_voting: QuadraticVotingConfig {
+			proposals: proposals
+				.into_iter()
+				.map(|(proposer, hash, target)| {
+					let hash = hash.try_into().expect("proposal hashes are at most 32 bytes; qed");
+					(proposer, hash, target)
+				})
+				.collect(),
+			voting_period: Some(DEMO_VOTING_PERIOD),
+			phantom: Default::default(),
+		},
+		assets: AssetsConfig {
+			// Create the governance token the engineering guild votes with, owned by root, and
+			// hand some out to every endowed account. The account holding the guild's pots gets
+			// the minimum balance, like the one of the main instance.
+			assets: vec![(GovernanceAssetId::get(), root_key, true, GOVERNANCE_MIN_BALANCE)],
+			metadata: vec![(GovernanceAssetId::get(), b"Governance".to_vec(), b"GOV".to_vec(), 12)],
+			accounts: endowed_accounts
+				.iter()
+				.map(|k| (GovernanceAssetId::get(), k.clone(), 1_000 * DOLLARS))
+				.chain([(
+					GovernanceAssetId::get(),
+					EngineeringGuild::account_id(),
+					GOVERNANCE_MIN_BALANCE,
+				)])
+				.collect(),
+		},
+		dev_identities: DevIdentitiesConfig { registrar, identities },
+		engineering_guild: Default::default(),
 	}
 }
@@ -1,3 +1,4 @@
+use crate::service::Sealing;
 use sc_cli::RunCmd;
 
 #[derive(Debug, clap::Parser)]
@@ -7,6 +8,12 @@ pub struct Cli {
 
 	#[clap(flatten)]
 	pub run: RunCmd,
+
+	/// Seal blocks for development instead of authoring them with Aura: `instant` as soon as a
+	/// transaction arrives, `manual` when `engine_createBlock` is called over RPC, or
+	/// `interval=<ms>` on a timer.
+	#[clap(long, value_name = "MODE")]
+	pub sealing: Option<Sealing>,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -50,4 +57,8 @@ pub enum Subcommand {
 
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
+
+	/// Inspect proposals, votes and reserves of the quadratic voting pallet.
+	#[clap(subcommand)]
+	Qv(crate::qv_cmd::QvCmd),
 }
@@ -2,6 +2,7 @@ use crate::{
 	chain_spec,
 	cli::{Cli, Subcommand},
 	command_helper::{inherent_benchmark_data, BenchmarkExtrinsicBuilder},
+	qv_cmd::QvCmd,
 	service,
 };
 use frame_benchmarking_cli::{BenchmarkCmd, SUBSTRATE_REFERENCE_HARDWARE};
@@ -167,10 +168,22 @@ pub fn run() -> sc_cli::Result<()> {
 			let runner = cli.create_runner(cmd)?;
 			runner.sync_run(|config| cmd.run::<Block>(&config))
 		},
+		Some(Subcommand::Qv(cmd)) => {
+			let runner = cli.create_runner(cmd)?;
+			runner.sync_run(|config| match cmd {
+				// Planning does not read the chain, so the database is not opened.
+				QvCmd::Plan(cmd) => cmd.run(),
+				_ => {
+					let PartialComponents { client, .. } = service::new_partial(&config)?;
+					cmd.run(&client, &config)
+				},
+			})
+		},
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let sealing = cli.sealing;
 			runner.run_node_until_exit(|config| async move {
-				service::new_full(config).map_err(sc_cli::Error::Service)
+				service::new_full(config, sealing).map_err(sc_cli::Error::Service)
 			})
 		},
 	}
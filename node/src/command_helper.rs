@@ -90,6 +90,11 @@ pub fn create_benchmark_extrinsic(
 		frame_system::CheckNonce::<runtime::Runtime>::from(nonce),
 		frame_system::CheckWeight::<runtime::Runtime>::new(),
 		pallet_transaction_payment::ChargeTransactionPayment::<runtime::Runtime>::from(0),
+		pallet_quadratic_voting::currency::CheckpointCalls::<
+			runtime::Runtime,
+			runtime::EngineeringGuild,
+			runtime::GovernanceTransfers,
+		>::new(),
 	);
 
 	let raw_payload = runtime::SignedPayload::from_raw(
@@ -104,6 +109,7 @@ pub fn create_benchmark_extrinsic(
 			(),
 			(),
 			(),
+			(),
 		),
 	);
 	let signature = raw_payload.using_encoded(|e| sender.sign(e));
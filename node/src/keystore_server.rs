@@ -0,0 +1,130 @@
+//! Reference server of the remote keystore protocol of [`crate::remote_keystore`], which answers
+//! requests from a local keystore.
+
+use crate::remote_keystore::{read_message, type_id, type_name, Request};
+use sc_keystore::LocalKeystore;
+use sp_core::{
+	crypto::{ByteArray, CryptoTypeId, CryptoTypePublicPair, KeyTypeId},
+	ecdsa, ed25519, sr25519, Bytes,
+};
+use sp_keystore::SyncCryptoStore;
+use std::{
+	io::{self, Read, Write},
+	net::TcpStream,
+	os::unix::net::UnixStream,
+	time::Duration,
+};
+
+/// How long to wait for a client to send its request, so that idle connections don't hold on to a
+/// thread of the server.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connection requests are read from, whose reads can time out.
+pub trait Connection: Read + Write {
+	/// Make reads fail once they wait longer than `timeout`.
+	fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+	fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+		TcpStream::set_read_timeout(self, timeout)
+	}
+}
+
+impl Connection for UnixStream {
+	fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+		UnixStream::set_read_timeout(self, timeout)
+	}
+}
+
+/// Answer one request that is read from `stream`, giving up after [`READ_TIMEOUT`].
+pub fn serve(keystore: &LocalKeystore, mut stream: impl Connection) -> io::Result<()> {
+	stream.set_read_timeout(Some(READ_TIMEOUT))?;
+	let (start, body) = read_message(&mut stream)?;
+	if !start.starts_with("POST ") {
+		return respond(stream, "405 Method Not Allowed", b"")
+	}
+	match serde_json::from_slice::<Request>(&body) {
+		Ok(request) => {
+			let response = serde_json::to_vec(&handle(keystore, request))?;
+			respond(stream, "200 OK", &response)
+		},
+		Err(e) => respond(stream, "400 Bad Request", e.to_string().as_bytes()),
+	}
+}
+
+fn respond(mut stream: impl Write, status: &str, body: &[u8]) -> io::Result<()> {
+	write!(
+		stream,
+		"HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+		 Connection: close\r\n\r\n",
+		status,
+		body.len()
+	)?;
+	stream.write_all(body)?;
+	stream.flush()
+}
+
+/// Result of `request` on `keystore`.
+pub fn handle(keystore: &LocalKeystore, request: Request) -> Result<serde_json::Value, String> {
+	let key_type = |name: &str| type_id(name).map(KeyTypeId);
+	let result = match request {
+		Request::Keys { key_type: id } => {
+			let keys =
+				SyncCryptoStore::keys(keystore, key_type(&id)?).map_err(|e| e.to_string())?;
+			let keys: Vec<(String, Bytes)> = keys
+				.into_iter()
+				.map(|CryptoTypePublicPair(crypto_type, public)| {
+					(type_name(crypto_type.0), public.into())
+				})
+				.collect();
+			serde_json::to_value(keys)
+		},
+		Request::HasKeys { keys } => {
+			let keys = keys
+				.into_iter()
+				.map(|(public, id)| Ok((public.0, key_type(&id)?)))
+				.collect::<Result<Vec<_>, String>>()?;
+			serde_json::to_value(SyncCryptoStore::has_keys(keystore, &keys))
+		},
+		Request::Generate { key_type: id, crypto_type, seed } => {
+			let (id, seed) = (key_type(&id)?, seed.as_deref());
+			let public = match CryptoTypeId(type_id(&crypto_type)?) {
+				sr25519::CRYPTO_ID => SyncCryptoStore::sr25519_generate_new(keystore, id, seed)
+					.map(|public| public.to_raw_vec()),
+				ed25519::CRYPTO_ID => SyncCryptoStore::ed25519_generate_new(keystore, id, seed)
+					.map(|public| public.to_raw_vec()),
+				ecdsa::CRYPTO_ID => SyncCryptoStore::ecdsa_generate_new(keystore, id, seed)
+					.map(|public| public.to_raw_vec()),
+				_ => return Err(format!("Unknown crypto type {}", crypto_type)),
+			}
+			.map_err(|e| e.to_string())?;
+			serde_json::to_value(Bytes(public))
+		},
+		Request::InsertUnknown { key_type: id, suri, public } => {
+			SyncCryptoStore::insert_unknown(keystore, key_type(&id)?, &suri, &public)
+				.map_err(|()| "Unable to insert key".to_string())?;
+			serde_json::to_value(())
+		},
+		Request::Sign { key_type: id, crypto_type, public, message } => {
+			let key = CryptoTypePublicPair(CryptoTypeId(type_id(&crypto_type)?), public.0);
+			let signature = SyncCryptoStore::sign_with(keystore, key_type(&id)?, &key, &message)
+				.map_err(|e| e.to_string())?;
+			serde_json::to_value(signature.map(Bytes))
+		},
+		Request::SignPrehashed { key_type: id, public, message } => {
+			let public = ecdsa::Public::try_from(&public[..])
+				.map_err(|()| "Invalid ECDSA public key".to_string())?;
+			let message: [u8; 32] = message
+				.0
+				.as_slice()
+				.try_into()
+				.map_err(|_| "Message is not a 32 byte hash".to_string())?;
+			let signature =
+				SyncCryptoStore::ecdsa_sign_prehashed(keystore, key_type(&id)?, &public, &message)
+					.map_err(|e| e.to_string())?;
+			serde_json::to_value(signature.map(|signature| Bytes(signature.0.to_vec())))
+		},
+	};
+	result.map_err(|e| e.to_string())
+}
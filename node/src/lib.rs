@@ -1,3 +1,5 @@
 pub mod chain_spec;
+pub mod keystore_server;
+pub mod remote_keystore;
 pub mod rpc;
 pub mod service;
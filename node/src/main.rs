@@ -7,6 +7,10 @@ mod service;
 mod cli;
 mod command;
 mod command_helper;
+mod qv_cmd;
+mod qv_plan;
+mod qv_report;
+mod remote_keystore;
 mod rpc;
 
 fn main() -> sc_cli::Result<()> {
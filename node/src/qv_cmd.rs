@@ -0,0 +1,620 @@
+//! The `qv` subcommand, which prints the state of the quadratic voting pallet straight from the
+//! local database, without a running node.
+//!
+//! Storage is read and decoded directly, so it works on any block the database still has state
+//! for. JSON output uses the same shapes as the `qv_*` RPC methods. `qv report` and `qv plan` are
+//! implemented in [`crate::qv_report`] and [`crate::qv_plan`].
+
+use crate::{
+	qv_plan::PlanCmd,
+	qv_report::ReportParams,
+	rpc::quadratic_voting::{RpcProposal, RpcVote},
+	service::FullClient,
+};
+use node_template_runtime::{opaque::Block, AccountId, Balance, BlockNumber};
+use pallet_quadratic_voting::{
+	Pot, Proposal, ProposalIndex, ProposalInfo, ProposalStatus, VoteInfo, VoteRecord, VoterStats,
+};
+use sc_cli::{
+	BlockNumberOrHash, CliConfiguration, DatabaseParams, KeystoreParams, PruningParams,
+	SharedParams,
+};
+use sc_client_api::StorageProvider;
+use sc_service::Configuration;
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	blake2_128,
+	hexdisplay::HexDisplay,
+	storage::{StorageData, StorageKey},
+	twox_128, Decode, Encode,
+};
+use sp_runtime::generic::BlockId;
+
+/// Inspect proposals, votes and reserves of the quadratic voting pallet.
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum QvCmd {
+	/// List all proposals with their tallies and status.
+	List(QvParams),
+
+	/// Show a proposal together with its voters and their reserves.
+	Show {
+		/// Index of the proposal.
+		index: ProposalIndex,
+
+		#[allow(missing_docs)]
+		#[clap(flatten)]
+		params: QvParams,
+	},
+
+	/// Write a signed report of every proposal that was finalized in a range of blocks.
+	Report(ReportParams),
+
+	/// Plan how to spread a budget over votes on several proposals, without a chain.
+	Plan(PlanCmd),
+}
+
+/// Parameters shared by the `qv` subcommands.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct QvParams {
+	/// Block hash or number to read the state at. Defaults to the best block.
+	#[clap(long, value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Instance of the pallet to read.
+	#[clap(long, arg_enum, default_value = "quadratic-voting")]
+	pub instance: Instance,
+
+	/// Print JSON instead of a table.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+}
+
+/// Instances of the quadratic voting pallet in the runtime.
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+pub enum Instance {
+	/// The main voting body.
+	QuadraticVoting,
+	/// The engineering guild.
+	EngineeringGuild,
+}
+
+impl Instance {
+	/// Name of the instance in `construct_runtime!`, which prefixes its storage.
+	pub(crate) fn pallet_name(&self) -> &'static str {
+		match self {
+			Instance::QuadraticVoting => "QuadraticVoting",
+			Instance::EngineeringGuild => "EngineeringGuild",
+		}
+	}
+}
+
+/// A voter on a proposal as it is printed as JSON.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Voter {
+	voter: AccountId,
+	#[serde(flatten)]
+	vote: RpcVote<BlockNumber>,
+	/// Amount that is still reserved for all votes of the voter.
+	outstanding_reserves: String,
+}
+
+/// A proposal with its voters as it is printed as JSON.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProposalDetails {
+	#[serde(flatten)]
+	proposal: RpcProposal<AccountId, BlockNumber>,
+	voters: Vec<Voter>,
+}
+
+impl QvCmd {
+	/// Print the requested state of the pallet, or write reports.
+	pub fn run(&self, client: &FullClient, config: &Configuration) -> sc_cli::Result<()> {
+		match self {
+			QvCmd::List(params) => list(&state(client, params)?, params.json),
+			QvCmd::Show { index, params } => show(&state(client, params)?, *index, params.json),
+			QvCmd::Report(params) => params.run(client, &config.keystore),
+			QvCmd::Plan(cmd) => cmd.run(),
+		}
+	}
+}
+
+fn state<'a>(client: &'a FullClient, params: &QvParams) -> sc_cli::Result<State<'a>> {
+	let at = match &params.at {
+		Some(at) => at.parse::<Block>()?,
+		None => BlockId::Hash(client.info().best_hash),
+	};
+	let number = client.block_number_from_id(&at)?.ok_or("Block not found")?;
+	Ok(State { client, at, number, pallet: params.instance.pallet_name() })
+}
+
+fn list(state: &State, json: bool) -> sc_cli::Result<()> {
+	let proposals = state.proposals()?;
+	if json {
+		let proposals: Vec<RpcProposal<_, _>> = proposals.into_iter().map(Into::into).collect();
+		print_json(&proposals)
+	} else {
+		println!(
+			"{:>5}  {:<8}  {:>10}  {:>10}  {:>8}  {:<48}  HASH",
+			"INDEX", "STATUS", "AYE", "NAY", "END", "PROPOSER"
+		);
+		for info in proposals {
+			println!(
+				"{:>5}  {:<8}  {:>10}  {:>10}  {:>8}  {:<48}  {}",
+				info.index,
+				format!("{:?}", info.status),
+				info.proposal.aye,
+				info.proposal.nay,
+				info.proposal.end,
+				info.proposal.proposer.to_string(),
+				display_hash(&info.proposal.hash),
+			);
+		}
+		Ok(())
+	}
+}
+
+fn show(state: &State, index: ProposalIndex, json: bool) -> sc_cli::Result<()> {
+	let details = details(state, index)?;
+	if json {
+		print_json(&details)
+	} else {
+		let (p, voters) = (&details.proposal, details.voters);
+		println!("Proposal {} at block {}", p.index, state.number);
+		println!("  status:   {:?}", p.status);
+		println!("  proposer: {}", p.proposer);
+		println!("  hash:     {}", display_hash(&p.hash.0));
+		println!("  aye:      {}", p.aye);
+		println!("  nay:      {}", p.nay);
+		println!("  end:      {}", p.end);
+		println!();
+		println!(
+			"{:<48}  {:<4}  {:>10}  {:>12}  {:>8}  {:>4}  {:>8}  {:>12}",
+			"VOTER", "SIDE", "VOTES", "COST", "AT", "POT", "RELEASED", "RESERVED"
+		);
+		for voter in voters {
+			let side = voter.vote.side.map_or("?".into(), |side| format!("{:?}", side));
+			println!(
+				"{:<48}  {:<4}  {:>10}  {:>12}  {:>8}  {:>4}  {:>8}  {:>12}",
+				voter.voter.to_string(),
+				side,
+				voter.vote.votes,
+				voter.vote.cost,
+				voter.vote.at_block,
+				if voter.vote.paid_into_pot { "yes" } else { "no" },
+				if voter.vote.released { "yes" } else { "no" },
+				voter.outstanding_reserves,
+			);
+		}
+		Ok(())
+	}
+}
+
+// The proposal at `index` with its voters and their outstanding reserves.
+fn details(state: &State, index: ProposalIndex) -> sc_cli::Result<ProposalDetails> {
+	let info = state
+		.proposal(index)?
+		.ok_or_else(|| format!("Proposal {} does not exist at this block", index))?;
+	let votes = state.votes(index)?;
+	let mut voters = Vec::with_capacity(votes.len());
+	for (voter, vote) in votes {
+		let stats: VoterStats<Balance> =
+			state.get(state.map_key("VoterStatistics", &voter))?.unwrap_or_default();
+		voters.push(Voter {
+			voter,
+			vote: vote.into(),
+			outstanding_reserves: stats.outstanding_reserves.to_string(),
+		});
+	}
+	Ok(ProposalDetails { proposal: info.into(), voters })
+}
+
+impl CliConfiguration for QvCmd {
+	fn shared_params(&self) -> &SharedParams {
+		match self {
+			QvCmd::List(params) | QvCmd::Show { params, .. } => &params.shared_params,
+			QvCmd::Report(params) => &params.shared_params,
+			QvCmd::Plan(cmd) => cmd.shared_params(),
+		}
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		match self {
+			QvCmd::List(params) | QvCmd::Show { params, .. } => Some(&params.database_params),
+			QvCmd::Report(params) => Some(&params.database_params),
+			QvCmd::Plan(_) => None,
+		}
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		match self {
+			QvCmd::List(params) | QvCmd::Show { params, .. } => Some(&params.pruning_params),
+			QvCmd::Report(params) => Some(&params.pruning_params),
+			QvCmd::Plan(_) => None,
+		}
+	}
+
+	fn keystore_params(&self) -> Option<&KeystoreParams> {
+		match self {
+			QvCmd::List(_) | QvCmd::Show { .. } | QvCmd::Plan(_) => None,
+			QvCmd::Report(params) => Some(&params.keystore_params),
+		}
+	}
+}
+
+/// Reads the state of blocks in the local database.
+pub(crate) trait ReadStorage {
+	/// Value of `key` in the state of block `at`.
+	fn storage(
+		&self,
+		at: &BlockId<Block>,
+		key: &StorageKey,
+	) -> sp_blockchain::Result<Option<StorageData>>;
+
+	/// Keys and values that start with `prefix` in the state of block `at`.
+	fn storage_pairs(
+		&self,
+		at: &BlockId<Block>,
+		prefix: &StorageKey,
+	) -> sp_blockchain::Result<Vec<(StorageKey, StorageData)>>;
+}
+
+impl ReadStorage for FullClient {
+	fn storage(
+		&self,
+		at: &BlockId<Block>,
+		key: &StorageKey,
+	) -> sp_blockchain::Result<Option<StorageData>> {
+		StorageProvider::storage(self, at, key)
+	}
+
+	fn storage_pairs(
+		&self,
+		at: &BlockId<Block>,
+		prefix: &StorageKey,
+	) -> sp_blockchain::Result<Vec<(StorageKey, StorageData)>> {
+		StorageProvider::storage_pairs(self, at, prefix)
+	}
+}
+
+/// Storage of one pallet instance at one block.
+pub(crate) struct State<'a> {
+	pub(crate) client: &'a dyn ReadStorage,
+	pub(crate) at: BlockId<Block>,
+	pub(crate) number: BlockNumber,
+	pub(crate) pallet: &'static str,
+}
+
+impl State<'_> {
+	fn prefix(&self, item: &str) -> Vec<u8> {
+		[twox_128(self.pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+	}
+
+	// Key of `key` in a map of the pallet that is hashed with `Blake2_128Concat`.
+	fn map_key(&self, item: &str, key: &impl Encode) -> Vec<u8> {
+		[self.prefix(item), blake2_128_concat(key)].concat()
+	}
+
+	fn get<T: Decode>(&self, key: Vec<u8>) -> sc_cli::Result<Option<T>> {
+		self.client
+			.storage(&self.at, &StorageKey(key))?
+			.map(|data| decode(&data.0))
+			.transpose()
+	}
+
+	// Values under `prefix`, with the part of their key that follows the prefix.
+	fn pairs<T: Decode>(&self, prefix: Vec<u8>) -> sc_cli::Result<Vec<(Vec<u8>, T)>> {
+		self.client
+			.storage_pairs(&self.at, &StorageKey(prefix.clone()))?
+			.into_iter()
+			.map(|(key, data)| Ok((key.0[prefix.len()..].to_vec(), decode(&data.0)?)))
+			.collect()
+	}
+
+	fn info(
+		&self,
+		index: ProposalIndex,
+		proposal: Proposal<AccountId, BlockNumber>,
+	) -> ProposalInfo<AccountId, BlockNumber> {
+		// Same as `Pallet::proposal_status`, as of the block that is read.
+		let status = if self.number <= proposal.end {
+			ProposalStatus::Voting
+		} else if proposal.aye > proposal.nay {
+			ProposalStatus::Passed
+		} else {
+			ProposalStatus::Rejected
+		};
+		ProposalInfo { index, proposal, status }
+	}
+
+	pub(crate) fn proposal(
+		&self,
+		index: ProposalIndex,
+	) -> sc_cli::Result<Option<ProposalInfo<AccountId, BlockNumber>>> {
+		let proposal = self.get(self.map_key("Proposals", &index))?;
+		Ok(proposal.map(|proposal| self.info(index, proposal)))
+	}
+
+	/// All proposals, in ascending order of their index.
+	fn proposals(&self) -> sc_cli::Result<Vec<ProposalInfo<AccountId, BlockNumber>>> {
+		let mut proposals = self
+			.pairs::<Proposal<AccountId, BlockNumber>>(self.prefix("Proposals"))?
+			.into_iter()
+			.map(|(key, proposal)| Ok(self.info(decode(&key[16..])?, proposal)))
+			.collect::<sc_cli::Result<Vec<_>>>()?;
+		proposals.sort_by_key(|info| info.index);
+		Ok(proposals)
+	}
+
+	/// Votes on the proposal at `index`, including released ones, by voter.
+	fn votes(
+		&self,
+		index: ProposalIndex,
+	) -> sc_cli::Result<Vec<(AccountId, VoteInfo<Balance, BlockNumber>)>> {
+		let paid_into_pot = self.get::<Pot<Balance>>(self.map_key("Pots", &index))?.is_some();
+		self.pairs::<VoteRecord<Balance, BlockNumber>>(self.map_key("Votes", &index))?
+			.into_iter()
+			.map(|(key, vote)| Ok((decode(&key[16..])?, VoteInfo { vote, paid_into_pot })))
+			.collect()
+	}
+}
+
+fn blake2_128_concat(key: &impl Encode) -> Vec<u8> {
+	let key = key.encode();
+	[blake2_128(&key).to_vec(), key].concat()
+}
+
+fn decode<T: Decode>(mut data: &[u8]) -> sc_cli::Result<T> {
+	T::decode(&mut data).map_err(|e| format!("Unable to decode storage: {}", e).into())
+}
+
+// Proposals are identified by a short text or a hash.
+fn display_hash(hash: &[u8]) -> String {
+	match std::str::from_utf8(hash) {
+		Ok(text) if !text.chars().any(char::is_control) => text.into(),
+		_ => format!("0x{}", HexDisplay::from(&hash)),
+	}
+}
+
+fn print_json<T: Serialize>(value: &T) -> sc_cli::Result<()> {
+	let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+	println!("{}", json);
+	Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use node_template_runtime::{Hash, Header};
+	use pallet_quadratic_voting::{Redistribution, VoteSide};
+	use sc_client_api::{
+		backend::{Backend, BlockImportOperation, NewBlockState},
+		in_mem, StateBackend,
+	};
+	use sp_blockchain::{BlockStatus, Info};
+	use sp_core::storage::Storage;
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::{traits::Header as HeaderT, StateVersion};
+	use std::collections::BTreeMap;
+
+	/// Chain in memory whose blocks hold exactly the storage they are built with.
+	pub(crate) struct TestChain {
+		backend: in_mem::Backend<Block>,
+		pub(crate) best: Header,
+	}
+
+	impl TestChain {
+		pub(crate) fn new() -> Self {
+			let backend = in_mem::Backend::new();
+			let best = import(&backend, Default::default(), 0, BTreeMap::new());
+			TestChain { backend, best }
+		}
+
+		/// Import a block with `storage` on top of the best one.
+		pub(crate) fn push(&mut self, storage: BTreeMap<Vec<u8>, Vec<u8>>) -> Header {
+			let number = self.best.number() + 1;
+			self.best = import(&self.backend, self.best.hash(), number, storage);
+			self.best.clone()
+		}
+
+		pub(crate) fn state(&self, pallet: &'static str) -> State {
+			let (number, at) = (self.best.number, BlockId::Hash(self.best.hash()));
+			State { client: self, at, number, pallet }
+		}
+	}
+
+	impl ReadStorage for TestChain {
+		fn storage(
+			&self,
+			at: &BlockId<Block>,
+			key: &StorageKey,
+		) -> sp_blockchain::Result<Option<StorageData>> {
+			let state = self.backend.state_at(*at)?;
+			Ok(state.storage(&key.0).map_err(sp_blockchain::Error::Backend)?.map(StorageData))
+		}
+
+		fn storage_pairs(
+			&self,
+			at: &BlockId<Block>,
+			prefix: &StorageKey,
+		) -> sp_blockchain::Result<Vec<(StorageKey, StorageData)>> {
+			let state = self.backend.state_at(*at)?;
+			Ok(state
+				.pairs()
+				.into_iter()
+				.filter(|(key, _)| key.starts_with(&prefix.0))
+				.map(|(key, data)| (StorageKey(key), StorageData(data)))
+				.collect())
+		}
+	}
+
+	impl HeaderBackend<Block> for TestChain {
+		fn header(&self, id: BlockId<Block>) -> sp_blockchain::Result<Option<Header>> {
+			self.backend.blockchain().header(id)
+		}
+
+		fn info(&self) -> Info<Block> {
+			self.backend.blockchain().info()
+		}
+
+		fn status(&self, id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+			self.backend.blockchain().status(id)
+		}
+
+		fn number(&self, hash: Hash) -> sp_blockchain::Result<Option<BlockNumber>> {
+			self.backend.blockchain().number(hash)
+		}
+
+		fn hash(&self, number: BlockNumber) -> sp_blockchain::Result<Option<Hash>> {
+			self.backend.blockchain().hash(number)
+		}
+	}
+
+	// Import a block with a state that holds exactly `storage` as the new best block.
+	fn import(
+		backend: &in_mem::Backend<Block>,
+		parent: Hash,
+		number: BlockNumber,
+		storage: BTreeMap<Vec<u8>, Vec<u8>>,
+	) -> Header {
+		let mut op = backend.begin_operation().unwrap();
+		let storage = Storage { top: storage, children_default: Default::default() };
+		let state_root = op.reset_storage(storage, StateVersion::V1).unwrap();
+		let header =
+			Header::new(number, Default::default(), state_root, parent, Default::default());
+		op.set_block_data(header.clone(), None, None, None, NewBlockState::Best)
+			.unwrap();
+		backend.commit_operation(op).unwrap();
+		header
+	}
+
+	/// Key of `keys` in the storage item `item` of `pallet`, hashed with `Blake2_128Concat`.
+	pub(crate) fn storage_key(pallet: &str, item: &str, keys: &[&dyn Encode]) -> Vec<u8> {
+		let prefix = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+		keys.iter().fold(prefix, |key, part| [key, blake2_128_concat(part)].concat())
+	}
+
+	pub(crate) fn proposal(
+		aye: u128,
+		nay: u128,
+		end: BlockNumber,
+	) -> Proposal<AccountId, BlockNumber> {
+		Proposal {
+			aye,
+			nay,
+			hash: b"proposal".to_vec().try_into().unwrap(),
+			proposer: Sr25519Keyring::Alice.to_account_id(),
+			end,
+		}
+	}
+
+	fn vote(side: VoteSide, votes: u128, released: bool) -> VoteRecord<Balance, BlockNumber> {
+		let cost = votes * votes;
+		VoteRecord { side: Some(side), votes, cost, at_block: 1, released }
+	}
+
+	#[test]
+	fn proposals_are_read_in_order_with_their_status() {
+		let mut chain = TestChain::new();
+		chain.push(BTreeMap::new());
+		let storage = [
+			(storage_key("QuadraticVoting", "Proposals", &[&10u32]), proposal(3, 1, 1).encode()),
+			(storage_key("QuadraticVoting", "Proposals", &[&2u32]), proposal(1, 3, 1).encode()),
+			(storage_key("QuadraticVoting", "Proposals", &[&0u32]), proposal(0, 0, 5).encode()),
+			// Proposals of the guild are kept apart.
+			(storage_key("EngineeringGuild", "Proposals", &[&1u32]), proposal(0, 0, 5).encode()),
+		]
+		.into_iter()
+		.collect();
+		chain.push(storage);
+
+		let proposals: Vec<_> = chain
+			.state("QuadraticVoting")
+			.proposals()
+			.unwrap()
+			.into_iter()
+			.map(|info| (info.index, info.status))
+			.collect();
+		assert_eq!(
+			proposals,
+			vec![
+				(0, ProposalStatus::Voting),
+				(2, ProposalStatus::Rejected),
+				(10, ProposalStatus::Passed),
+			]
+		);
+		let guild = chain.state("EngineeringGuild").proposals().unwrap();
+		assert_eq!(guild.iter().map(|info| info.index).collect::<Vec<_>>(), vec![1]);
+	}
+
+	#[test]
+	fn details_are_rendered_like_the_rpc() {
+		let (bob, charlie) =
+			(Sr25519Keyring::Bob.to_account_id(), Sr25519Keyring::Charlie.to_account_id());
+		let stats = VoterStats { votes_cast: 3, credits_spent: 9, outstanding_reserves: 9 };
+		let pot = Pot { target: Redistribution::Voters, remaining: 4, recipients: 1 };
+		let storage = [
+			(storage_key("QuadraticVoting", "Proposals", &[&0u32]), proposal(3, 0, 5).encode()),
+			(storage_key("QuadraticVoting", "Proposals", &[&1u32]), proposal(0, 2, 5).encode()),
+			(storage_key("QuadraticVoting", "Pots", &[&1u32]), pot.encode()),
+			(
+				storage_key("QuadraticVoting", "Votes", &[&0u32, &bob]),
+				vote(VoteSide::Aye, 3, false).encode(),
+			),
+			(
+				storage_key("QuadraticVoting", "Votes", &[&1u32, &charlie]),
+				vote(VoteSide::Nay, 2, false).encode(),
+			),
+			(storage_key("QuadraticVoting", "VoterStatistics", &[&bob]), stats.encode()),
+		]
+		.into_iter()
+		.collect();
+		let mut chain = TestChain::new();
+		chain.push(storage);
+
+		let shown = details(&chain.state("QuadraticVoting"), 0).unwrap();
+		assert_eq!(
+			serde_json::to_value(&shown).unwrap(),
+			serde_json::json!({
+				"index": 0,
+				"aye": "3",
+				"nay": "0",
+				"hash": "0x70726f706f73616c",
+				"proposer": Sr25519Keyring::Alice.to_account_id(),
+				"end": 5,
+				"status": "Voting",
+				"voters": [{
+					"voter": bob,
+					"side": "Aye",
+					"votes": "3",
+					"cost": "9",
+					"atBlock": 1,
+					"released": false,
+					"paidIntoPot": false,
+					"outstandingReserves": "9",
+				}],
+			})
+		);
+		// Votes on proposals with a pot were paid into it, and voters without statistics have
+		// nothing reserved.
+		let shown = details(&chain.state("QuadraticVoting"), 1).unwrap();
+		let voter = serde_json::to_value(&shown.voters[0]).unwrap();
+		assert_eq!(voter["paidIntoPot"], true);
+		assert_eq!(voter["outstandingReserves"], "0");
+		assert!(details(&chain.state("QuadraticVoting"), 2).is_err());
+	}
+}
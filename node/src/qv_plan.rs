@@ -0,0 +1,339 @@
+//! The `qv plan` subcommand, which works out how to spread a budget over votes on several
+//! proposals. It needs neither a running chain nor a database.
+//!
+//! The input is a JSON file with a budget and preferences, for example:
+//!
+//! ```json
+//! {
+//!   "budget": 2000,
+//!   "preferences": [
+//!     { "proposal": 3, "side": "Aye", "weight": 3 },
+//!     { "proposal": 5, "side": "Nay" },
+//!     { "proposal": 8, "side": "Aye", "votes": 10 }
+//!   ]
+//! }
+//! ```
+//!
+//! Preferences with `votes` are priced as they are. What is left of the budget is spread over the
+//! others so that the sum of their votes times their `weight` (1 by default) is as large as
+//! possible. Amounts can be given as numbers or as strings, and are in the smallest unit of the
+//! currency. Costs are those of the pallet's `quote_cost`, and the reserve a proposal needs is
+//! the cost of the votes on it.
+
+use crate::qv_cmd::Instance;
+use node_template_runtime::{Balance, Instance1, QuadraticVoting, Runtime};
+use pallet_quadratic_voting::{ProposalIndex, VoteSide};
+use sc_cli::{CliConfiguration, SharedParams};
+use serde::{Deserialize, Serialize};
+use sp_core::Get;
+use std::{cmp::Reverse, collections::BTreeSet, io::Read, path::PathBuf};
+
+/// Parameters of the `qv plan` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct PlanCmd {
+	/// JSON file with the budget and the preferences, or `-` to read it from standard input.
+	#[clap(value_name = "FILE")]
+	pub input: PathBuf,
+
+	/// Instance of the pallet whose limits apply.
+	#[clap(long, arg_enum, default_value = "quadratic-voting")]
+	pub instance: Instance,
+
+	/// Print JSON instead of a table.
+	#[clap(long)]
+	pub json: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// An amount that is given as a JSON number or, if it does not fit one, as a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Amount {
+	Number(u64),
+	Text(String),
+}
+
+impl Amount {
+	fn value(&self) -> Result<u128, String> {
+		match self {
+			Amount::Number(n) => Ok(*n as u128),
+			Amount::Text(text) => text.parse().map_err(|_| format!("Invalid amount: {}", text)),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Ballot {
+	budget: Amount,
+	preferences: Vec<Preference>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Preference {
+	proposal: ProposalIndex,
+	side: VoteSide,
+	#[serde(default)]
+	weight: Option<u32>,
+	#[serde(default)]
+	votes: Option<Amount>,
+}
+
+/// Votes that are planned on one proposal.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlannedVote {
+	proposal: ProposalIndex,
+	side: VoteSide,
+	/// Weight of the preference, or `None` if its votes were fixed.
+	weight: Option<u32>,
+	votes: String,
+	/// Amount the proposal needs reserved for the votes, which is their cost.
+	reserve: String,
+	/// Cost of one more vote on the proposal.
+	next_vote_cost: Option<String>,
+}
+
+/// A plan for a budget.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Plan {
+	budget: String,
+	reserved: String,
+	left: String,
+	votes: Vec<PlannedVote>,
+	/// Proposals that are left out because no more can be voted on at once.
+	dropped: Vec<ProposalIndex>,
+}
+
+fn price(votes: u128) -> Option<Balance> {
+	QuadraticVoting::quote_cost(votes)
+}
+
+// Cost of the vote after `votes` votes.
+fn next_vote_cost(votes: u128) -> Option<Balance> {
+	Some(price(votes.checked_add(1)?)? - price(votes)?)
+}
+
+/// Votes for `weights` that maximise the sum of votes times weight and cost at most `budget`.
+fn spread(weights: &[u32], budget: Balance) -> Vec<u128> {
+	// Without rounding, the optimum gives every proposal votes in proportion to its weight.
+	let squares: f64 = weights.iter().map(|w| (*w as f64).powi(2)).sum();
+	let scale = if squares > 0.0 { (budget as f64 / squares).sqrt() } else { 0.0 };
+	let mut votes: Vec<u128> = weights.iter().map(|w| (*w as f64 * scale) as u128).collect();
+	let cost = |votes: &[u128]| {
+		votes
+			.iter()
+			.fold(0, |sum: Balance, v| sum.saturating_add(price(*v).unwrap_or(Balance::MAX)))
+	};
+
+	// Take back votes the floating point arithmetic may have given too many.
+	while cost(&votes) > budget {
+		let most = (0..votes.len()).max_by_key(|i| votes[*i]).expect("cost is positive; qed");
+		votes[most] -= 1;
+	}
+
+	// Spend what is left one vote at a time, on the proposal where a vote gives most per cost.
+	let mut left = budget - cost(&votes);
+	loop {
+		let affordable = (0..votes.len()).filter_map(|i| {
+			let next = next_vote_cost(votes[i])?;
+			if weights[i] > 0 && next <= left {
+				Some((i, next))
+			} else {
+				None
+			}
+		});
+		// `a` is better than `b` if weight(a) / next(a) > weight(b) / next(b).
+		let best = affordable.max_by(|(a, next_a), (b, next_b)| {
+			let a = weights[*a] as u128 * next_b;
+			let b = weights[*b] as u128 * next_a;
+			a.cmp(&b)
+		});
+		match best {
+			Some((i, next)) => {
+				votes[i] += 1;
+				left -= next;
+			},
+			None => return votes,
+		}
+	}
+}
+
+/// Plan votes of `ballot` on at most `max_voted` proposals.
+fn plan(ballot: &Ballot, max_voted: usize) -> Result<Plan, String> {
+	let budget = ballot.budget.value()?;
+	let mut seen = BTreeSet::new();
+	if let Some(p) = ballot.preferences.iter().find(|p| !seen.insert(p.proposal)) {
+		return Err(format!("Proposal {} can only be voted on once", p.proposal))
+	}
+
+	// Fixed votes are kept, and of the others those with the highest weight.
+	let mut preferences: Vec<&Preference> = ballot.preferences.iter().collect();
+	preferences.sort_by_key(|p| (p.votes.is_none(), Reverse(p.weight.unwrap_or(1))));
+	let dropped = preferences.split_off(max_voted.min(preferences.len()));
+	if dropped.iter().any(|p| p.votes.is_some()) {
+		return Err(format!("Votes can be fixed on at most {} proposals", max_voted))
+	}
+
+	let mut votes = Vec::with_capacity(preferences.len());
+	let mut weights = Vec::new();
+	for p in &preferences {
+		match &p.votes {
+			Some(fixed) => votes.push(fixed.value()?),
+			None => weights.push(p.weight.unwrap_or(1)),
+		}
+	}
+	let fixed_cost = votes
+		.iter()
+		.try_fold(0 as Balance, |sum, v| sum.checked_add(price(*v)?))
+		.ok_or("Fixed votes cost more than any budget")?;
+	let left = budget
+		.checked_sub(fixed_cost)
+		.ok_or_else(|| format!("Fixed votes cost {}, more than the budget", fixed_cost))?;
+	votes.extend(spread(&weights, left));
+	let reserved: Balance =
+		votes.iter().map(|v| price(*v).expect("votes are within the budget; qed")).sum();
+
+	let planned: Vec<PlannedVote> = preferences
+		.iter()
+		.zip(votes)
+		.map(|(p, votes)| PlannedVote {
+			proposal: p.proposal,
+			side: p.side,
+			weight: if p.votes.is_none() { Some(p.weight.unwrap_or(1)) } else { None },
+			votes: votes.to_string(),
+			reserve: price(votes).expect("votes are within the budget; qed").to_string(),
+			next_vote_cost: next_vote_cost(votes).map(|cost| cost.to_string()),
+		})
+		.collect();
+	Ok(Plan {
+		budget: budget.to_string(),
+		reserved: reserved.to_string(),
+		left: (budget - reserved).to_string(),
+		votes: planned,
+		dropped: dropped.iter().map(|p| p.proposal).collect(),
+	})
+}
+
+impl PlanCmd {
+	/// Read the ballot and print the plan for it.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let mut input = String::new();
+		if self.input.as_os_str() == "-" {
+			std::io::stdin().read_to_string(&mut input)?;
+		} else {
+			input = std::fs::read_to_string(&self.input)?;
+		}
+		let ballot: Ballot =
+			serde_json::from_str(&input).map_err(|e| format!("Invalid ballot: {}", e))?;
+		let max_voted = match self.instance {
+			Instance::QuadraticVoting =>
+				<Runtime as pallet_quadratic_voting::Config>::MaxVotedProposals::get(),
+			Instance::EngineeringGuild =>
+				<Runtime as pallet_quadratic_voting::Config<Instance1>>::MaxVotedProposals::get(),
+		};
+		let plan = plan(&ballot, max_voted as usize)?;
+
+		if self.json {
+			let json = serde_json::to_string_pretty(&plan).map_err(|e| e.to_string())?;
+			println!("{}", json);
+			return Ok(())
+		}
+		println!("Budget:   {}", plan.budget);
+		println!("Reserved: {}", plan.reserved);
+		println!("Left:     {}", plan.left);
+		println!();
+		println!(
+			"{:>8}  {:<4}  {:>6}  {:>12}  {:>16}  {:>12}",
+			"PROPOSAL", "SIDE", "WEIGHT", "VOTES", "RESERVE", "NEXT VOTE"
+		);
+		for vote in &plan.votes {
+			println!(
+				"{:>8}  {:<4}  {:>6}  {:>12}  {:>16}  {:>12}",
+				vote.proposal,
+				format!("{:?}", vote.side),
+				vote.weight.map_or("fixed".into(), |weight| weight.to_string()),
+				vote.votes,
+				vote.reserve,
+				vote.next_vote_cost.as_deref().unwrap_or("-"),
+			);
+		}
+		if !plan.dropped.is_empty() {
+			println!();
+			println!(
+				"Left out, as at most {} proposals can be voted on at once: {:?}",
+				max_voted, plan.dropped
+			);
+		}
+		Ok(())
+	}
+}
+
+impl CliConfiguration for PlanCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn ballot(json: &str) -> Ballot {
+		serde_json::from_str(json).unwrap()
+	}
+
+	#[test]
+	fn votes_are_spread_by_weight_within_the_budget() {
+		let ballot = ballot(
+			r#"{ "budget": 1000, "preferences": [
+				{ "proposal": 1, "side": "Aye", "weight": 3 },
+				{ "proposal": 2, "side": "Nay", "weight": 1 },
+				{ "proposal": 3, "side": "Aye", "votes": "10" }
+			] }"#,
+		);
+		let plan = plan(&ballot, 8).unwrap();
+		let votes: Vec<_> = plan
+			.votes
+			.iter()
+			.map(|v| (v.proposal, v.votes.as_str(), v.reserve.as_str()))
+			.collect();
+		// 100 is fixed, and 28 and 10 votes (784 + 100) are the best use of the other 900.
+		assert_eq!(votes, vec![(3, "10", "100"), (1, "28", "784"), (2, "10", "100")]);
+		assert_eq!((plan.reserved.as_str(), plan.left.as_str()), ("984", "16"));
+		// No more votes are affordable.
+		let next: Vec<_> = plan.votes.iter().map(|v| v.next_vote_cost.as_deref()).collect();
+		assert_eq!(next, vec![Some("21"), Some("57"), Some("21")]);
+	}
+
+	#[test]
+	fn invalid_ballots_are_rejected() {
+		let duplicate = ballot(
+			r#"{ "budget": 10, "preferences": [
+				{ "proposal": 1, "side": "Aye" },
+				{ "proposal": 1, "side": "Nay" }
+			] }"#,
+		);
+		assert!(plan(&duplicate, 8).is_err());
+
+		let expensive = ballot(
+			r#"{ "budget": 10, "preferences": [ { "proposal": 1, "side": "Aye", "votes": 4 } ] }"#,
+		);
+		assert!(plan(&expensive, 8).is_err());
+
+		let many = ballot(
+			r#"{ "budget": 10, "preferences": [
+				{ "proposal": 1, "side": "Aye", "weight": 1 },
+				{ "proposal": 2, "side": "Aye", "weight": 2 }
+			] }"#,
+		);
+		let plan = plan(&many, 1).unwrap();
+		assert_eq!(plan.votes[0].proposal, 2);
+		assert_eq!(plan.dropped, vec![1]);
+	}
+}
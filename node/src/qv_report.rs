@@ -0,0 +1,521 @@
+//! The `qv report` subcommand, which writes an archive of governance decisions.
+//!
+//! Blocks are walked like `export-blocks` does, and the `Proposed`, `VotedAye`, `VotedNay` and
+//! `Finalized` events of the pallet are read from the state of each block. That state has to be
+//! kept for the whole range, i.e. the database has to be an archive. Every proposal that is
+//! finalized in the range gets a JSON and a CSV report. With `--sign-with`, both files are signed
+//! with a key from the local keystore and the signatures are written next to them, so that they
+//! can be checked with `key verify` and compared against the chain later.
+
+use crate::{
+	qv_cmd::{Instance, ReadStorage, State},
+	service::FullClient,
+};
+use node_template_runtime::{opaque::Block, AccountId, BlockNumber, Event, Hash, Runtime};
+use pallet_quadratic_voting::{ProposalIndex, ProposalStatus, VoteSide};
+use sc_cli::{DatabaseParams, GenericNumber, KeystoreParams, PruningParams, SharedParams};
+use sc_keystore::LocalKeystore;
+use sc_service::config::KeystoreConfig;
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	crypto::{ByteArray, KeyTypeId, Ss58Codec},
+	hexdisplay::HexDisplay,
+	sr25519,
+	storage::StorageKey,
+	twox_128, Bytes, Decode,
+};
+use sp_keystore::SyncCryptoStore;
+use sp_runtime::generic::BlockId;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Parameters of the `qv report` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ReportParams {
+	/// Block to start at. Proposals and votes before it are missing from the reports.
+	#[clap(long, value_name = "BLOCK")]
+	pub from: Option<GenericNumber>,
+
+	/// Last block to walk. Defaults to the best block.
+	#[clap(long, value_name = "BLOCK")]
+	pub to: Option<GenericNumber>,
+
+	/// Directory to write the reports to.
+	#[clap(long, short, value_name = "PATH", default_value = ".")]
+	pub output: PathBuf,
+
+	/// Instance of the pallet to report on.
+	#[clap(long, arg_enum, default_value = "quadratic-voting")]
+	pub instance: Instance,
+
+	/// SS58 public key of an sr25519 key in the keystore to sign the reports with.
+	#[clap(long, value_name = "PUBLIC KEY")]
+	pub sign_with: Option<String>,
+
+	/// Key type under which the signing key is stored.
+	#[clap(long, value_name = "KEY TYPE", default_value = "qvot")]
+	pub key_type: String,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+/// A block by number and hash.
+#[derive(Clone, Copy, Serialize)]
+struct BlockRef {
+	number: BlockNumber,
+	hash: Hash,
+}
+
+/// A vote as it was cast.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReportVote {
+	voter: AccountId,
+	side: VoteSide,
+	votes: String,
+	block: BlockRef,
+}
+
+/// Everything that is recorded about a finalized proposal.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+	instance: &'static str,
+	index: ProposalIndex,
+	proposer: AccountId,
+	hash: Bytes,
+	end: BlockNumber,
+	aye: String,
+	nay: String,
+	outcome: ProposalStatus,
+	/// Block the proposal was made in, if it is in the walked range.
+	proposed: Option<BlockRef>,
+	finalized: BlockRef,
+	votes: Vec<ReportVote>,
+}
+
+impl Report {
+	// One row per vote, with the proposal repeated in every row. None of the fields can contain
+	// a comma or a quote, so nothing is escaped.
+	fn csv(&self) -> String {
+		let mut csv = String::from(
+			"instance,index,proposer,hash,end,aye,nay,outcome,proposed_block,proposed_hash,\
+			 finalized_block,finalized_hash,voter,side,votes,vote_block,vote_hash\n",
+		);
+		let proposal = format!(
+			"{},{},{},0x{},{},{},{},{:?},{},{},{},{:?}",
+			self.instance,
+			self.index,
+			self.proposer,
+			HexDisplay::from(&self.hash.0),
+			self.end,
+			self.aye,
+			self.nay,
+			self.outcome,
+			self.proposed.map_or(String::new(), |block| block.number.to_string()),
+			self.proposed.map_or(String::new(), |block| format!("{:?}", block.hash)),
+			self.finalized.number,
+			self.finalized.hash,
+		);
+		if self.votes.is_empty() {
+			csv += &format!("{},,,,,\n", proposal);
+		}
+		for vote in &self.votes {
+			csv += &format!(
+				"{},{},{:?},{},{},{:?}\n",
+				proposal, vote.voter, vote.side, vote.votes, vote.block.number, vote.block.hash
+			);
+		}
+		csv
+	}
+}
+
+/// Proposal and votes of a proposal that is not finalized yet.
+#[derive(Default)]
+struct Pending {
+	proposed: Option<BlockRef>,
+	votes: Vec<ReportVote>,
+}
+
+/// Events of the pallet that go into reports, independent of the instance.
+enum Recorded {
+	Proposed(ProposalIndex),
+	Voted(ProposalIndex, AccountId, VoteSide, u128),
+	Finalized(ProposalIndex, ProposalStatus),
+}
+
+fn recorded<I: 'static>(event: pallet_quadratic_voting::Event<Runtime, I>) -> Option<Recorded>
+where
+	Runtime: pallet_quadratic_voting::Config<I>,
+{
+	use pallet_quadratic_voting::Event::*;
+	match event {
+		Proposed(index) => Some(Recorded::Proposed(index)),
+		VotedAye(index, voter, votes) => Some(Recorded::Voted(index, voter, VoteSide::Aye, votes)),
+		VotedNay(index, voter, votes) => Some(Recorded::Voted(index, voter, VoteSide::Nay, votes)),
+		Finalized(index, status) => Some(Recorded::Finalized(index, status)),
+		_ => None,
+	}
+}
+
+/// A key of the local keystore that reports are signed with.
+struct Signer {
+	keystore: LocalKeystore,
+	key_type: KeyTypeId,
+	public: sr25519::Public,
+}
+
+impl Signer {
+	fn sign(&self, data: &[u8]) -> sc_cli::Result<Vec<u8>> {
+		SyncCryptoStore::sign_with(&self.keystore, self.key_type, &self.public.into(), data)
+			.map_err(|e| format!("Unable to sign report: {}", e))?
+			.ok_or_else(|| "Signing key has been removed from the keystore".into())
+	}
+}
+
+impl ReportParams {
+	/// Walk the blocks and write a report for every proposal finalized in them.
+	pub fn run(&self, client: &FullClient, keystore: &KeystoreConfig) -> sc_cli::Result<()> {
+		let signer = self.signer(keystore)?;
+		let reports = self.reports(client)?;
+		fs::create_dir_all(&self.output)?;
+		for report in &reports {
+			self.write(report, signer.as_ref())?;
+		}
+
+		println!("Wrote {} reports to {}", reports.len(), self.output.display());
+		Ok(())
+	}
+
+	/// Reports of the proposals finalized in the walked blocks, in the order they were finalized.
+	fn reports<C>(&self, client: &C) -> sc_cli::Result<Vec<Report>>
+	where
+		C: HeaderBackend<Block> + ReadStorage,
+	{
+		let from = match &self.from {
+			Some(from) => from.parse()?,
+			None => 1,
+		};
+		let to = match &self.to {
+			Some(to) => to.parse()?,
+			None => client.info().best_number,
+		};
+
+		let mut pending = BTreeMap::<ProposalIndex, Pending>::new();
+		let mut reports = Vec::new();
+		for number in from..=to {
+			let hash = client.hash(number)?.ok_or_else(|| format!("Block {} not found", number))?;
+			let block = BlockRef { number, hash };
+			for event in self.events(client, block)? {
+				match event {
+					Recorded::Proposed(index) =>
+						pending.entry(index).or_default().proposed = Some(block),
+					Recorded::Voted(index, voter, side, votes) => pending
+						.entry(index)
+						.or_default()
+						.votes
+						.push(ReportVote { voter, side, votes: votes.to_string(), block }),
+					Recorded::Finalized(index, outcome) => {
+						let Pending { proposed, votes } =
+							pending.remove(&index).unwrap_or_default();
+						let pallet = self.instance.pallet_name();
+						let state = State { client, at: BlockId::Hash(hash), number, pallet };
+						let p = state
+							.proposal(index)?
+							.ok_or_else(|| format!("Proposal {} is not stored", index))?
+							.proposal;
+						reports.push(Report {
+							instance: pallet,
+							index,
+							proposer: p.proposer,
+							hash: p.hash.into_inner().into(),
+							end: p.end,
+							aye: p.aye.to_string(),
+							nay: p.nay.to_string(),
+							outcome,
+							proposed,
+							finalized: block,
+							votes,
+						});
+					},
+				}
+			}
+		}
+		Ok(reports)
+	}
+
+	fn signer(&self, keystore: &KeystoreConfig) -> sc_cli::Result<Option<Signer>> {
+		let public = match &self.sign_with {
+			Some(public) => sr25519::Public::from_ss58check(public)
+				.map_err(|e| format!("Invalid public key to sign with: {:?}", e))?,
+			None => return Ok(None),
+		};
+		let key_type = KeyTypeId::try_from(self.key_type.as_str())
+			.map_err(|_| "Key type must have four characters")?;
+		let keystore = match keystore {
+			KeystoreConfig::Path { path, password } => LocalKeystore::open(path, password.clone())?,
+			KeystoreConfig::InMemory =>
+				return Err("Reports can only be signed with keys on disk".into()),
+		};
+		if !SyncCryptoStore::has_keys(&keystore, &[(public.to_raw_vec(), key_type)]) {
+			return Err(
+				format!("Key {} of type {} is not in the keystore", public, self.key_type).into()
+			)
+		}
+		Ok(Some(Signer { keystore, key_type, public }))
+	}
+
+	/// Events of the selected instance in `block`.
+	fn events(&self, client: &impl ReadStorage, block: BlockRef) -> sc_cli::Result<Vec<Recorded>> {
+		let key = StorageKey([twox_128(b"System"), twox_128(b"Events")].concat());
+		let data = client.storage(&BlockId::Hash(block.hash), &key).map_err(|e| {
+			format!(
+				"State of block {} is not available, is the node an archive? {}",
+				block.number, e
+			)
+		})?;
+		let records: Vec<frame_system::EventRecord<Event, Hash>> = match data {
+			Some(data) => Decode::decode(&mut &data.0[..])
+				.map_err(|e| format!("Unable to decode events of block {}: {}", block.number, e))?,
+			None => Vec::new(),
+		};
+		Ok(records
+			.into_iter()
+			.filter_map(|record| match (self.instance, record.event) {
+				(Instance::QuadraticVoting, Event::QuadraticVoting(event)) => recorded(event),
+				(Instance::EngineeringGuild, Event::EngineeringGuild(event)) => recorded(event),
+				_ => None,
+			})
+			.collect())
+	}
+
+	// Writes `<instance>-<index>.json` and `.csv`, and `.json.sig` and `.csv.sig` with the hex
+	// encoded signatures of their exact contents if a signer is given.
+	fn write(&self, report: &Report, signer: Option<&Signer>) -> sc_cli::Result<()> {
+		let name = format!("{}-{}", report.instance, report.index);
+		let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())? + "\n";
+		for (extension, contents) in [("json", json), ("csv", report.csv())] {
+			let path = self.output.join(format!("{}.{}", name, extension));
+			fs::write(&path, &contents)?;
+			if let Some(signer) = signer {
+				let signature = signer.sign(contents.as_bytes())?;
+				let path = path.with_extension(format!("{}.sig", extension));
+				fs::write(path, format!("0x{}\n", HexDisplay::from(&signature)))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::qv_cmd::tests::{proposal, storage_key, TestChain};
+	use clap::Parser;
+	use frame_system::{EventRecord, Phase};
+	use pallet_quadratic_voting::Event::{Finalized, Proposed, VotedAye, VotedNay};
+	use sp_core::{Encode, H256};
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::traits::Header as HeaderT;
+
+	fn params(args: &[&str]) -> ReportParams {
+		ReportParams::parse_from([&["report"][..], args].concat())
+	}
+
+	fn block(number: BlockNumber) -> BlockRef {
+		BlockRef { number, hash: H256::repeat_byte(number as u8) }
+	}
+
+	fn report(votes: Vec<ReportVote>) -> Report {
+		Report {
+			instance: "QuadraticVoting",
+			index: 4,
+			proposer: Sr25519Keyring::Alice.to_account_id(),
+			hash: b"text".to_vec().into(),
+			end: 6,
+			aye: "3".into(),
+			nay: "1".into(),
+			outcome: ProposalStatus::Passed,
+			proposed: Some(block(2)),
+			finalized: block(7),
+			votes,
+		}
+	}
+
+	// Storage of `System::Events` in a block that emitted `events`.
+	fn events(events: Vec<Event>) -> (Vec<u8>, Vec<u8>) {
+		let records: Vec<EventRecord<Event, Hash>> = events
+			.into_iter()
+			.map(|event| EventRecord { phase: Phase::ApplyExtrinsic(0), event, topics: vec![] })
+			.collect();
+		(storage_key("System", "Events", &[]), records.encode())
+	}
+
+	#[test]
+	fn csv_has_a_row_per_vote() {
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let vote = ReportVote {
+			voter: bob.clone(),
+			side: VoteSide::Nay,
+			votes: "1".into(),
+			block: block(5),
+		};
+		let csv = report(vec![vote]).csv();
+		let proposal = format!(
+			"QuadraticVoting,4,{},0x74657874,6,3,1,Passed,2,{:?},7,{:?}",
+			Sr25519Keyring::Alice.to_account_id(),
+			block(2).hash,
+			block(7).hash
+		);
+		let lines: Vec<_> = csv.lines().collect();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[1], format!("{},{},Nay,1,5,{:?}", proposal, bob, block(5).hash));
+
+		// A proposal without votes still gets a row, with empty vote columns.
+		let mut report = report(vec![]);
+		report.proposed = None;
+		let csv = report.csv();
+		let proposal = proposal.replacen(&format!("Passed,2,{:?}", block(2).hash), "Passed,,", 1);
+		assert_eq!(csv.lines().nth(1), Some(format!("{},,,,,", proposal).as_str()));
+		assert!(csv.lines().all(|line| line.split(',').count() == 17));
+	}
+
+	#[test]
+	fn json_has_the_proposal_and_its_votes() {
+		let (alice, bob) =
+			(Sr25519Keyring::Alice.to_account_id(), Sr25519Keyring::Bob.to_account_id());
+		let vote = ReportVote {
+			voter: bob.clone(),
+			side: VoteSide::Aye,
+			votes: "2".into(),
+			block: block(3),
+		};
+		assert_eq!(
+			serde_json::to_value(&report(vec![vote])).unwrap(),
+			serde_json::json!({
+				"instance": "QuadraticVoting",
+				"index": 4,
+				"proposer": alice,
+				"hash": "0x74657874",
+				"end": 6,
+				"aye": "3",
+				"nay": "1",
+				"outcome": "Passed",
+				"proposed": { "number": 2, "hash": block(2).hash },
+				"finalized": { "number": 7, "hash": block(7).hash },
+				"votes": [{
+					"voter": bob,
+					"side": "Aye",
+					"votes": "2",
+					"block": { "number": 3, "hash": block(3).hash },
+				}],
+			})
+		);
+	}
+
+	#[test]
+	fn finalized_proposals_of_the_instance_are_reported() {
+		let (bob, charlie) =
+			(Sr25519Keyring::Bob.to_account_id(), Sr25519Keyring::Charlie.to_account_id());
+		let mut chain = TestChain::new();
+		let proposed = chain.push(
+			[events(vec![
+				Event::QuadraticVoting(Proposed(0)),
+				Event::EngineeringGuild(Proposed(0)),
+			])]
+			.into_iter()
+			.collect(),
+		);
+		let voted = chain.push(
+			[events(vec![
+				Event::QuadraticVoting(VotedAye(0, bob.clone(), 3)),
+				Event::EngineeringGuild(VotedNay(0, charlie.clone(), 2)),
+				Event::QuadraticVoting(VotedNay(0, charlie.clone(), 1)),
+			])]
+			.into_iter()
+			.collect(),
+		);
+		// Proposal 1 was made before the walked range.
+		let finalized = chain.push(
+			[
+				events(vec![
+					Event::QuadraticVoting(Finalized(1, ProposalStatus::Rejected)),
+					Event::QuadraticVoting(Finalized(0, ProposalStatus::Passed)),
+				]),
+				(storage_key("QuadraticVoting", "Proposals", &[&0u32]), proposal(3, 1, 2).encode()),
+				(storage_key("QuadraticVoting", "Proposals", &[&1u32]), proposal(0, 0, 1).encode()),
+			]
+			.into_iter()
+			.collect(),
+		);
+		let guild_finalized = chain.push(
+			[
+				events(vec![Event::EngineeringGuild(Finalized(0, ProposalStatus::Rejected))]),
+				(
+					storage_key("EngineeringGuild", "Proposals", &[&0u32]),
+					proposal(0, 2, 2).encode(),
+				),
+			]
+			.into_iter()
+			.collect(),
+		);
+
+		let reports = params(&["--to", "3"]).reports(&chain).unwrap();
+		let reported: Vec<_> = reports
+			.iter()
+			.map(|r| (r.index, r.outcome, r.proposed.map(|b| b.hash), r.finalized.hash))
+			.collect();
+		assert_eq!(
+			reported,
+			vec![
+				(1, ProposalStatus::Rejected, None, finalized.hash()),
+				(0, ProposalStatus::Passed, Some(proposed.hash()), finalized.hash()),
+			]
+		);
+		let votes: Vec<_> = reports[1]
+			.votes
+			.iter()
+			.map(|v| (v.voter.clone(), v.side, v.votes.as_str(), v.block.hash))
+			.collect();
+		assert_eq!(
+			votes,
+			vec![
+				(bob, VoteSide::Aye, "3", voted.hash()),
+				(charlie.clone(), VoteSide::Nay, "1", voted.hash()),
+			]
+		);
+
+		// The guild only sees its own events, up to the best block.
+		let reports = params(&["--instance", "engineering-guild"]).reports(&chain).unwrap();
+		assert_eq!(reports.len(), 1);
+		let report = &reports[0];
+		assert_eq!((report.instance, report.nay.as_str()), ("EngineeringGuild", "2"));
+		assert_eq!(report.finalized.hash, guild_finalized.hash());
+		assert_eq!(report.votes.iter().map(|v| &v.voter).collect::<Vec<_>>(), vec![&charlie]);
+
+		// Nothing is reported from blocks after the finalization.
+		assert!(params(&["--from", "4"]).reports(&chain).unwrap().is_empty());
+	}
+
+	#[test]
+	fn blocks_out_of_range_are_rejected() {
+		let chain = TestChain::new();
+		// Too large for a block number.
+		assert!(params(&["--from", "4294967296"]).reports(&chain).is_err());
+		assert!(params(&["--to", "1"]).reports(&chain).is_err());
+	}
+}
@@ -0,0 +1,438 @@
+//! A keystore that keeps its keys in another process, for `--keystore-uri`.
+//!
+//! Every call is a JSON request in the body of an HTTP `POST`, sent over TCP to
+//! `http://<host>:<port>` or over a Unix socket to `unix://<path>`, one request per connection.
+//! A request names its `method` and its `params`, see [`Request`], and the body of the response is
+//! `{"Ok": <result>}` or `{"Err": "<message>"}`. Public keys, messages and signatures are hex
+//! encoded, and key and crypto types are given by their four characters, e.g. `aura` and `sr25`.
+//!
+//! The `keystore-server` binary is a reference server that keeps the keys in a local keystore. The
+//! protocol has no authentication, so servers only listen on a Unix socket or on a loopback
+//! address, and `http://` URIs that resolve to any other address are rejected.
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sp_core::{
+	crypto::{CryptoTypeId, CryptoTypePublicPair, KeyTypeId},
+	ecdsa, ed25519, sr25519, Bytes,
+};
+use sp_keystore::{
+	vrf::{VRFSignature, VRFTranscriptData},
+	CryptoStore, Error, SyncCryptoStore,
+};
+use std::{
+	io::{self, BufRead, BufReader, Read, Write},
+	net::{SocketAddr, TcpStream, ToSocketAddrs},
+	os::unix::net::UnixStream,
+	path::PathBuf,
+	time::Duration,
+};
+
+/// How long to wait for the remote keystore to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest body of a request or response that is read.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// A call to a remote keystore, with the result it answers with.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Request {
+	/// All keys of `key_type`, as `[crypto_type, public]` pairs.
+	Keys { key_type: String },
+	/// Whether all `[public, key_type]` pairs are in the keystore, as a boolean.
+	HasKeys { keys: Vec<(Bytes, String)> },
+	/// Generate a key of `crypto_type` under `key_type`, from `seed` if it is given, and return
+	/// its public key.
+	Generate { key_type: String, crypto_type: String, seed: Option<String> },
+	/// Store the key with the secret URI `suri` and the public key `public` under `key_type`, and
+	/// return `null`.
+	InsertUnknown { key_type: String, suri: String, public: Bytes },
+	/// Sign `message` with the key `public` of `crypto_type` under `key_type`, and return the
+	/// signature, or `null` if the key is not in the keystore.
+	Sign { key_type: String, crypto_type: String, public: Bytes, message: Bytes },
+	/// Sign the 32 byte hash `message` with the ECDSA key `public` under `key_type`, and return
+	/// the signature, or `null` if the key is not in the keystore.
+	SignPrehashed { key_type: String, public: Bytes, message: Bytes },
+}
+
+/// Four characters of a key or crypto type.
+pub fn type_name(id: [u8; 4]) -> String {
+	String::from_utf8_lossy(&id).into()
+}
+
+/// Key or crypto type with the four characters `name`.
+pub fn type_id(name: &str) -> Result<[u8; 4], String> {
+	name.as_bytes()
+		.try_into()
+		.map_err(|_| format!("{} is not a type of four characters", name))
+}
+
+/// Read an HTTP message and return its first line and its body.
+pub fn read_message(stream: impl Read) -> io::Result<(String, Vec<u8>)> {
+	let mut reader = BufReader::new(stream);
+	let mut start = String::new();
+	reader.read_line(&mut start)?;
+	let mut length = 0;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 {
+			return Err(io::ErrorKind::UnexpectedEof.into())
+		}
+		let line = line.trim_end();
+		if line.is_empty() {
+			break
+		}
+		if let Some((name, value)) = line.split_once(':') {
+			if name.eq_ignore_ascii_case("content-length") {
+				length =
+					value.trim().parse().map_err(|_| invalid_data("invalid Content-Length"))?;
+			}
+		}
+	}
+	if length > MAX_BODY {
+		return Err(invalid_data("body is too large"))
+	}
+	let mut body = vec![0; length];
+	reader.read_exact(&mut body)?;
+	Ok((start.trim_end().into(), body))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Clone, Debug)]
+enum Endpoint {
+	/// Host as given in the URI, and the loopback addresses it resolved to.
+	Tcp(String, Vec<SocketAddr>),
+	Unix(PathBuf),
+}
+
+/// A keystore that forwards every call to a remote keystore.
+///
+/// Calls block until the keystore answers, like those of a `LocalKeystore` block on the disk.
+pub struct RemoteKeystore {
+	endpoint: Endpoint,
+}
+
+impl RemoteKeystore {
+	/// The keystore at `uri`, which is `http://<host>:<port>` with a host that resolves to
+	/// loopback addresses only, or `unix://<path>`. It is only connected to when it is called.
+	pub fn new(uri: &str) -> Result<Self, String> {
+		let endpoint = if let Some(address) = uri.strip_prefix("http://") {
+			let address = address.trim_end_matches('/');
+			// Resolved once, so that the host can't be pointed elsewhere later.
+			let resolved: Vec<SocketAddr> = address
+				.to_socket_addrs()
+				.map_err(|e| format!("Unable to resolve {}: {}", address, e))?
+				.collect();
+			if resolved.is_empty() || resolved.iter().any(|a| !a.ip().is_loopback()) {
+				return Err(format!(
+					"{} is not a loopback address, and the protocol has no authentication",
+					address
+				))
+			}
+			Endpoint::Tcp(address.into(), resolved)
+		} else if let Some(path) = uri.strip_prefix("unix://") {
+			Endpoint::Unix(path.into())
+		} else {
+			return Err(format!("{} is neither an http:// nor a unix:// URI", uri))
+		};
+		Ok(RemoteKeystore { endpoint })
+	}
+
+	fn call<T: DeserializeOwned>(&self, request: &Request) -> Result<T, Error> {
+		let body = serde_json::to_vec(request).map_err(|e| Error::Other(e.to_string()))?;
+		let response = match &self.endpoint {
+			Endpoint::Tcp(host, addresses) =>
+				TcpStream::connect(&addresses[..]).and_then(|stream| {
+					stream.set_read_timeout(Some(TIMEOUT))?;
+					stream.set_write_timeout(Some(TIMEOUT))?;
+					exchange(stream, host, &body)
+				}),
+			Endpoint::Unix(path) => UnixStream::connect(path).and_then(|stream| {
+				stream.set_read_timeout(Some(TIMEOUT))?;
+				stream.set_write_timeout(Some(TIMEOUT))?;
+				exchange(stream, "localhost", &body)
+			}),
+		}
+		.map_err(|e| {
+			log::warn!(target: "keystore", "Remote keystore {:?} is unavailable: {}", self.endpoint, e);
+			Error::Unavailable
+		})?;
+		let result: Result<T, String> = serde_json::from_slice(&response)
+			.map_err(|e| Error::Other(format!("Invalid response of the remote keystore: {}", e)))?;
+		result.map_err(Error::Other)
+	}
+
+	// Public keys of `crypto_type` under `id`, or none if the keystore can't be reached.
+	fn public_keys(&self, id: KeyTypeId, crypto_type: CryptoTypeId) -> Vec<Vec<u8>> {
+		match SyncCryptoStore::keys(self, id) {
+			Ok(keys) => keys
+				.into_iter()
+				.filter(|key| key.0 == crypto_type)
+				.map(|CryptoTypePublicPair(_, public)| public)
+				.collect(),
+			Err(e) => {
+				log::warn!(target: "keystore", "Unable to list keys of the remote keystore: {}", e);
+				Vec::new()
+			},
+		}
+	}
+
+	fn generate(
+		&self,
+		id: KeyTypeId,
+		crypto_type: CryptoTypeId,
+		seed: Option<&str>,
+	) -> Result<Vec<u8>, Error> {
+		let request = Request::Generate {
+			key_type: type_name(id.0),
+			crypto_type: type_name(crypto_type.0),
+			seed: seed.map(Into::into),
+		};
+		self.call::<Bytes>(&request).map(|public| public.0)
+	}
+}
+
+fn exchange(mut stream: impl Read + Write, host: &str, body: &[u8]) -> io::Result<Vec<u8>> {
+	write!(
+		stream,
+		"POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+		 Connection: close\r\n\r\n",
+		host,
+		body.len()
+	)?;
+	stream.write_all(body)?;
+	stream.flush()?;
+	let (status, body) = read_message(stream)?;
+	if status.split(' ').nth(1) != Some("200") {
+		return Err(io::Error::new(io::ErrorKind::Other, status))
+	}
+	Ok(body)
+}
+
+fn invalid_key(e: impl std::fmt::Debug) -> Error {
+	Error::Other(format!("Remote keystore returned an invalid key or signature: {:?}", e))
+}
+
+impl SyncCryptoStore for RemoteKeystore {
+	fn sr25519_public_keys(&self, id: KeyTypeId) -> Vec<sr25519::Public> {
+		let keys = self.public_keys(id, sr25519::CRYPTO_ID);
+		keys.iter()
+			.filter_map(|public| sr25519::Public::try_from(&public[..]).ok())
+			.collect()
+	}
+
+	fn sr25519_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<sr25519::Public, Error> {
+		let public = self.generate(id, sr25519::CRYPTO_ID, seed)?;
+		sr25519::Public::try_from(&public[..]).map_err(invalid_key)
+	}
+
+	fn ed25519_public_keys(&self, id: KeyTypeId) -> Vec<ed25519::Public> {
+		let keys = self.public_keys(id, ed25519::CRYPTO_ID);
+		keys.iter()
+			.filter_map(|public| ed25519::Public::try_from(&public[..]).ok())
+			.collect()
+	}
+
+	fn ed25519_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ed25519::Public, Error> {
+		let public = self.generate(id, ed25519::CRYPTO_ID, seed)?;
+		ed25519::Public::try_from(&public[..]).map_err(invalid_key)
+	}
+
+	fn ecdsa_public_keys(&self, id: KeyTypeId) -> Vec<ecdsa::Public> {
+		let keys = self.public_keys(id, ecdsa::CRYPTO_ID);
+		keys.iter()
+			.filter_map(|public| ecdsa::Public::try_from(&public[..]).ok())
+			.collect()
+	}
+
+	fn ecdsa_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ecdsa::Public, Error> {
+		let public = self.generate(id, ecdsa::CRYPTO_ID, seed)?;
+		ecdsa::Public::try_from(&public[..]).map_err(invalid_key)
+	}
+
+	fn insert_unknown(&self, id: KeyTypeId, suri: &str, public: &[u8]) -> Result<(), ()> {
+		let request = Request::InsertUnknown {
+			key_type: type_name(id.0),
+			suri: suri.into(),
+			public: public.to_vec().into(),
+		};
+		self.call::<()>(&request).map_err(|e| {
+			log::warn!(target: "keystore", "Unable to insert key into the remote keystore: {}", e);
+		})
+	}
+
+	fn supported_keys(
+		&self,
+		id: KeyTypeId,
+		keys: Vec<CryptoTypePublicPair>,
+	) -> Result<Vec<CryptoTypePublicPair>, Error> {
+		let all = SyncCryptoStore::keys(self, id)?;
+		Ok(keys.into_iter().filter(|key| all.contains(key)).collect())
+	}
+
+	fn keys(&self, id: KeyTypeId) -> Result<Vec<CryptoTypePublicPair>, Error> {
+		let keys: Vec<(String, Bytes)> = self.call(&Request::Keys { key_type: type_name(id.0) })?;
+		keys.into_iter()
+			.map(|(crypto_type, public)| {
+				let crypto_type = type_id(&crypto_type).map_err(Error::Other)?;
+				Ok(CryptoTypePublicPair(CryptoTypeId(crypto_type), public.0))
+			})
+			.collect()
+	}
+
+	fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
+		let keys = public_keys
+			.iter()
+			.map(|(public, id)| (public.clone().into(), type_name(id.0)))
+			.collect();
+		self.call(&Request::HasKeys { keys }).unwrap_or_else(|e| {
+			log::warn!(target: "keystore", "Unable to look up keys in the remote keystore: {}", e);
+			false
+		})
+	}
+
+	fn sign_with(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> Result<Option<Vec<u8>>, Error> {
+		let request = Request::Sign {
+			key_type: type_name(id.0),
+			crypto_type: type_name(key.0 .0),
+			public: key.1.clone().into(),
+			message: msg.to_vec().into(),
+		};
+		self.call::<Option<Bytes>>(&request).map(|signature| signature.map(|s| s.0))
+	}
+
+	fn sr25519_vrf_sign(
+		&self,
+		_key_type: KeyTypeId,
+		_public: &sr25519::Public,
+		_transcript_data: VRFTranscriptData,
+	) -> Result<Option<VRFSignature>, Error> {
+		// Neither Aura nor GRANDPA need VRF signatures.
+		Err(Error::Other("VRF signatures are not supported by the remote keystore".into()))
+	}
+
+	fn ecdsa_sign_prehashed(
+		&self,
+		id: KeyTypeId,
+		public: &ecdsa::Public,
+		msg: &[u8; 32],
+	) -> Result<Option<ecdsa::Signature>, Error> {
+		let request = Request::SignPrehashed {
+			key_type: type_name(id.0),
+			public: public.0.to_vec().into(),
+			message: msg.to_vec().into(),
+		};
+		match self.call::<Option<Bytes>>(&request)? {
+			Some(signature) =>
+				ecdsa::Signature::try_from(&signature[..]).map(Some).map_err(invalid_key),
+			None => Ok(None),
+		}
+	}
+}
+
+#[async_trait]
+impl CryptoStore for RemoteKeystore {
+	async fn sr25519_public_keys(&self, id: KeyTypeId) -> Vec<sr25519::Public> {
+		SyncCryptoStore::sr25519_public_keys(self, id)
+	}
+
+	async fn sr25519_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<sr25519::Public, Error> {
+		SyncCryptoStore::sr25519_generate_new(self, id, seed)
+	}
+
+	async fn ed25519_public_keys(&self, id: KeyTypeId) -> Vec<ed25519::Public> {
+		SyncCryptoStore::ed25519_public_keys(self, id)
+	}
+
+	async fn ed25519_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ed25519::Public, Error> {
+		SyncCryptoStore::ed25519_generate_new(self, id, seed)
+	}
+
+	async fn ecdsa_public_keys(&self, id: KeyTypeId) -> Vec<ecdsa::Public> {
+		SyncCryptoStore::ecdsa_public_keys(self, id)
+	}
+
+	async fn ecdsa_generate_new(
+		&self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ecdsa::Public, Error> {
+		SyncCryptoStore::ecdsa_generate_new(self, id, seed)
+	}
+
+	async fn insert_unknown(&self, id: KeyTypeId, suri: &str, public: &[u8]) -> Result<(), ()> {
+		SyncCryptoStore::insert_unknown(self, id, suri, public)
+	}
+
+	async fn supported_keys(
+		&self,
+		id: KeyTypeId,
+		keys: Vec<CryptoTypePublicPair>,
+	) -> Result<Vec<CryptoTypePublicPair>, Error> {
+		SyncCryptoStore::supported_keys(self, id, keys)
+	}
+
+	async fn keys(&self, id: KeyTypeId) -> Result<Vec<CryptoTypePublicPair>, Error> {
+		SyncCryptoStore::keys(self, id)
+	}
+
+	async fn has_keys(&self, public_keys: &[(Vec<u8>, KeyTypeId)]) -> bool {
+		SyncCryptoStore::has_keys(self, public_keys)
+	}
+
+	async fn sign_with(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> Result<Option<Vec<u8>>, Error> {
+		SyncCryptoStore::sign_with(self, id, key, msg)
+	}
+
+	async fn sr25519_vrf_sign(
+		&self,
+		key_type: KeyTypeId,
+		public: &sr25519::Public,
+		transcript_data: VRFTranscriptData,
+	) -> Result<Option<VRFSignature>, Error> {
+		SyncCryptoStore::sr25519_vrf_sign(self, key_type, public, transcript_data)
+	}
+
+	async fn ecdsa_sign_prehashed(
+		&self,
+		id: KeyTypeId,
+		public: &ecdsa::Public,
+		msg: &[u8; 32],
+	) -> Result<Option<ecdsa::Signature>, Error> {
+		SyncCryptoStore::ecdsa_sign_prehashed(self, id, public, msg)
+	}
+}
@@ -0,0 +1,607 @@
+//! RPC interface of the quadratic voting pallet.
+//!
+//! Every method is answered by the pallet's `QuadraticVotingApi` runtime API at the requested block
+//! (or the best block if none is given), so callers get the same answers the runtime computes.
+//! Every method takes the instance of the pallet to query as an optional last parameter, which is
+//! `quadraticVoting` or `engineeringGuild` and defaults to the former.
+//! Votes and balances are returned as decimal strings, because they don't fit into a JSON number.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use jsonrpsee::{
+	core::{async_trait, RpcResult},
+	proc_macros::rpc,
+	types::error::{CallError, ErrorObject},
+};
+use node_template_runtime::{
+	opaque::Block, AccountId, Balance, BlockNumber, QuadraticVotingInstance,
+};
+use pallet_quadratic_voting::{
+	runtime_api::QuadraticVotingApi as QuadraticVotingRuntimeApi, AccountHistory, Ineligibility,
+	ProposalIndex, ProposalInfo, ProposalStatus, VoteInfo, VoteSide,
+};
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+use super::quadratic_voting_events::PalletInstance;
+
+/// A proposal as it is returned over RPC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcProposal<AccountId, BlockNumber> {
+	/// Index of the proposal.
+	pub index: ProposalIndex,
+	/// Number of votes that support the proposal.
+	pub aye: String,
+	/// Number of votes against the proposal.
+	pub nay: String,
+	/// Hash of the proposal.
+	pub hash: Bytes,
+	/// Account that created the proposal.
+	pub proposer: AccountId,
+	/// Block number after which voting period is over.
+	pub end: BlockNumber,
+	/// Status of the proposal at the queried block.
+	pub status: ProposalStatus,
+}
+
+impl<AccountId, BlockNumber> From<ProposalInfo<AccountId, BlockNumber>>
+	for RpcProposal<AccountId, BlockNumber>
+{
+	fn from(info: ProposalInfo<AccountId, BlockNumber>) -> Self {
+		RpcProposal {
+			index: info.index,
+			aye: info.proposal.aye.to_string(),
+			nay: info.proposal.nay.to_string(),
+			hash: info.proposal.hash.into_inner().into(),
+			proposer: info.proposal.proposer,
+			end: info.proposal.end,
+			status: info.status,
+		}
+	}
+}
+
+/// The vote of an account as it is returned over RPC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcVote<BlockNumber> {
+	/// Side of the vote, if it is known.
+	pub side: Option<VoteSide>,
+	/// Number of votes cast.
+	pub votes: String,
+	/// Amount the votes cost.
+	pub cost: String,
+	/// Block the vote was cast at.
+	pub at_block: BlockNumber,
+	/// Whether the reserved cost has been returned to the voter.
+	pub released: bool,
+	/// Whether the cost was paid into the pot of the proposal instead of being reserved.
+	pub paid_into_pot: bool,
+}
+
+impl<BlockNumber> From<VoteInfo<Balance, BlockNumber>> for RpcVote<BlockNumber> {
+	fn from(info: VoteInfo<Balance, BlockNumber>) -> Self {
+		RpcVote {
+			side: info.vote.side,
+			votes: info.vote.votes.to_string(),
+			cost: info.vote.cost.to_string(),
+			at_block: info.vote.at_block,
+			released: info.vote.released,
+			paid_into_pot: info.paid_into_pot,
+		}
+	}
+}
+
+/// Participation history of an account as it is returned over RPC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountHistory<BlockNumber> {
+	/// Number of votes cast on all proposals.
+	pub votes_cast: String,
+	/// Amount spent on votes on all proposals.
+	pub credits_spent: String,
+	/// Amount that is still reserved for votes.
+	pub outstanding_reserves: String,
+	/// Votes whose funds have not been released yet, by proposal index.
+	pub votes: Vec<(ProposalIndex, RpcVote<BlockNumber>)>,
+}
+
+impl<BlockNumber> From<AccountHistory<Balance, BlockNumber>> for RpcAccountHistory<BlockNumber> {
+	fn from(history: AccountHistory<Balance, BlockNumber>) -> Self {
+		RpcAccountHistory {
+			votes_cast: history.stats.votes_cast.to_string(),
+			credits_spent: history.stats.credits_spent.to_string(),
+			outstanding_reserves: history.stats.outstanding_reserves.to_string(),
+			votes: history.votes.into_iter().map(|(index, vote)| (index, vote.into())).collect(),
+		}
+	}
+}
+
+/// Whether an account can vote on a proposal, as it is returned over RPC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcEligibility {
+	/// Whether the account can vote.
+	pub eligible: bool,
+	/// Why the account can't vote, if it can't.
+	pub reason: Option<Ineligibility>,
+}
+
+impl From<Result<(), Ineligibility>> for RpcEligibility {
+	fn from(result: Result<(), Ineligibility>) -> Self {
+		RpcEligibility { eligible: result.is_ok(), reason: result.err() }
+	}
+}
+
+/// Quadratic voting RPC methods.
+#[rpc(client, server)]
+pub trait QuadraticVotingApi<BlockHash, AccountId, BlockNumber> {
+	/// Get a proposal with its computed status.
+	#[method(name = "qv_proposal")]
+	fn proposal(
+		&self,
+		index: ProposalIndex,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<RpcProposal<AccountId, BlockNumber>>>;
+
+	/// Get the indexes of all proposals that are still open for voting.
+	#[method(name = "qv_activeProposals")]
+	fn active_proposals(
+		&self,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Vec<ProposalIndex>>;
+
+	/// Get the vote an account cast on a proposal.
+	#[method(name = "qv_voteOf")]
+	fn vote_of(
+		&self,
+		index: ProposalIndex,
+		who: AccountId,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<RpcVote<BlockNumber>>>;
+
+	/// Get the votes of an account whose funds have not been released yet, and its statistics.
+	#[method(name = "qv_accountHistory")]
+	fn account_history(
+		&self,
+		who: AccountId,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<RpcAccountHistory<BlockNumber>>;
+
+	/// Get the cost of casting a number of votes.
+	#[method(name = "qv_quoteCost")]
+	fn quote_cost(
+		&self,
+		votes: u128,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<String>>;
+
+	/// Check whether an account can vote on a proposal, and why not if it can't.
+	#[method(name = "qv_eligibility")]
+	fn eligibility(
+		&self,
+		index: ProposalIndex,
+		who: AccountId,
+		at: Option<BlockHash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<RpcEligibility>;
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+	/// The call to runtime failed.
+	RuntimeError,
+}
+
+impl From<Error> for i32 {
+	fn from(e: Error) -> i32 {
+		match e {
+			Error::RuntimeError => 1,
+		}
+	}
+}
+
+/// Implements the [`QuadraticVotingApiServer`] RPC trait for interacting with the pallet.
+pub struct QuadraticVoting<C, P> {
+	client: Arc<C>,
+	_marker: PhantomData<P>,
+}
+
+impl<C, P> QuadraticVoting<C, P> {
+	/// Creates a new instance of the QuadraticVoting RPC helper.
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, _marker: Default::default() }
+	}
+
+	fn block_id(&self, at: Option<<Block as BlockT>::Hash>) -> BlockId<Block>
+	where
+		C: HeaderBackend<Block>,
+	{
+		BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash))
+	}
+}
+
+fn runtime_error(message: &'static str, e: impl std::fmt::Display) -> jsonrpsee::core::Error {
+	CallError::Custom(ErrorObject::owned(
+		Error::RuntimeError.into(),
+		message,
+		Some(e.to_string()),
+	))
+	.into()
+}
+
+#[async_trait]
+impl<C> QuadraticVotingApiServer<<Block as BlockT>::Hash, AccountId, BlockNumber>
+	for QuadraticVoting<C, Block>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api:
+		QuadraticVotingRuntimeApi<Block, AccountId, Balance, BlockNumber, QuadraticVotingInstance>,
+{
+	fn proposal(
+		&self,
+		index: ProposalIndex,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<RpcProposal<AccountId, BlockNumber>>> {
+		let api = self.client.runtime_api();
+		api.proposal(&self.block_id(at), instance.unwrap_or_default().into(), index)
+			.map(|info| info.map(Into::into))
+			.map_err(|e| runtime_error("Unable to query proposal.", e))
+	}
+
+	fn active_proposals(
+		&self,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Vec<ProposalIndex>> {
+		let api = self.client.runtime_api();
+		api.active_proposals(&self.block_id(at), instance.unwrap_or_default().into())
+			.map_err(|e| runtime_error("Unable to query active proposals.", e))
+	}
+
+	fn vote_of(
+		&self,
+		index: ProposalIndex,
+		who: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<RpcVote<BlockNumber>>> {
+		let api = self.client.runtime_api();
+		api.vote_of(&self.block_id(at), instance.unwrap_or_default().into(), index, who)
+			.map(|vote| vote.map(Into::into))
+			.map_err(|e| runtime_error("Unable to query vote.", e))
+	}
+
+	fn account_history(
+		&self,
+		who: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<RpcAccountHistory<BlockNumber>> {
+		let api = self.client.runtime_api();
+		api.account_history(&self.block_id(at), instance.unwrap_or_default().into(), who)
+			.map(Into::into)
+			.map_err(|e| runtime_error("Unable to query account history.", e))
+	}
+
+	fn quote_cost(
+		&self,
+		votes: u128,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<Option<String>> {
+		let api = self.client.runtime_api();
+		api.quote_cost(&self.block_id(at), instance.unwrap_or_default().into(), votes)
+			.map(|cost| cost.map(|cost| cost.to_string()))
+			.map_err(|e| runtime_error("Unable to quote vote cost.", e))
+	}
+
+	fn eligibility(
+		&self,
+		index: ProposalIndex,
+		who: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+		instance: Option<PalletInstance>,
+	) -> RpcResult<RpcEligibility> {
+		let api = self.client.runtime_api();
+		api.eligibility(&self.block_id(at), instance.unwrap_or_default().into(), index, who)
+			.map(Into::into)
+			.map_err(|e| runtime_error("Unable to query eligibility.", e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_template_runtime::Hash;
+	use pallet_quadratic_voting::{Proposal, VoteRecord, VoterStats};
+	use jsonrpsee::types::EmptyParams;
+	use sp_api::ApiRef;
+	use sp_blockchain::{BlockStatus, Info};
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::traits::NumberFor;
+
+	/// Client that answers runtime API calls from a fixed chain state with a single proposal that
+	/// Alice created, Bob voted on with 3 votes and Charlie can still vote on. The engineering
+	/// guild has no proposals.
+	#[derive(Clone, Default)]
+	struct TestClient;
+
+	struct TestRuntimeApi;
+
+	sp_api::mock_impl_runtime_apis! {
+		impl QuadraticVotingRuntimeApi<
+			Block,
+			AccountId,
+			Balance,
+			BlockNumber,
+			QuadraticVotingInstance,
+		> for TestRuntimeApi {
+			fn proposal(
+				instance: QuadraticVotingInstance,
+				index: ProposalIndex,
+			) -> Option<ProposalInfo<AccountId, BlockNumber>> {
+				let main = instance == QuadraticVotingInstance::QuadraticVoting;
+				(main && index == 0).then(|| ProposalInfo {
+					index,
+					proposal: Proposal {
+						aye: 3,
+						nay: 0,
+						hash: b"proposal".to_vec().try_into().unwrap(),
+						proposer: Sr25519Keyring::Alice.to_account_id(),
+						end: 11,
+					},
+					status: ProposalStatus::Voting,
+				})
+			}
+
+			fn active_proposals(instance: QuadraticVotingInstance) -> Vec<ProposalIndex> {
+				match instance {
+					QuadraticVotingInstance::QuadraticVoting => vec![0],
+					QuadraticVotingInstance::EngineeringGuild => vec![],
+				}
+			}
+
+			fn vote_of(
+				instance: QuadraticVotingInstance,
+				index: ProposalIndex,
+				who: AccountId,
+			) -> Option<VoteInfo<Balance, BlockNumber>> {
+				let bob = Sr25519Keyring::Bob.to_account_id();
+				let main = instance == QuadraticVotingInstance::QuadraticVoting;
+				(main && index == 0 && who == bob).then(|| VoteInfo {
+					vote: VoteRecord {
+						side: Some(VoteSide::Aye),
+						votes: 3,
+						cost: 9,
+						at_block: 2,
+						released: false,
+					},
+					paid_into_pot: false,
+				})
+			}
+
+			fn account_history(
+				instance: QuadraticVotingInstance,
+				who: AccountId,
+			) -> AccountHistory<Balance, BlockNumber> {
+				if instance != QuadraticVotingInstance::QuadraticVoting ||
+					who != Sr25519Keyring::Bob.to_account_id()
+				{
+					return AccountHistory { stats: VoterStats::default(), votes: vec![] }
+				}
+				AccountHistory {
+					stats: VoterStats { votes_cast: 3, credits_spent: 9, outstanding_reserves: 9 },
+					votes: vec![(
+						0,
+						VoteInfo {
+							vote: VoteRecord {
+								side: Some(VoteSide::Aye),
+								votes: 3,
+								cost: 9,
+								at_block: 2,
+								released: false,
+							},
+							paid_into_pot: false,
+						},
+					)],
+				}
+			}
+
+			fn quote_cost(_instance: QuadraticVotingInstance, votes: u128) -> Option<Balance> {
+				votes.checked_mul(votes)
+			}
+
+			fn eligibility(
+				instance: QuadraticVotingInstance,
+				index: ProposalIndex,
+				who: AccountId,
+			) -> Result<(), Ineligibility> {
+				if instance != QuadraticVotingInstance::QuadraticVoting || index != 0 {
+					Err(Ineligibility::UnexistingProposal)
+				} else if who == Sr25519Keyring::Bob.to_account_id() {
+					Err(Ineligibility::AlreadyVoted)
+				} else {
+					Ok(())
+				}
+			}
+
+			fn dry_run_vote(
+				_instance: QuadraticVotingInstance,
+				_index: ProposalIndex,
+				_who: AccountId,
+				_aye: bool,
+				_votes: u128,
+			) -> Result<(), sp_runtime::DispatchError> {
+				Ok(())
+			}
+		}
+	}
+
+	impl ProvideRuntimeApi<Block> for TestClient {
+		type Api = TestRuntimeApi;
+
+		fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+			TestRuntimeApi.into()
+		}
+	}
+
+	impl HeaderBackend<Block> for TestClient {
+		fn header(
+			&self,
+			_id: BlockId<Block>,
+		) -> sp_blockchain::Result<Option<<Block as BlockT>::Header>> {
+			Ok(None)
+		}
+
+		fn info(&self) -> Info<Block> {
+			Info {
+				best_hash: Default::default(),
+				best_number: 0,
+				genesis_hash: Default::default(),
+				finalized_hash: Default::default(),
+				finalized_number: 0,
+				finalized_state: None,
+				number_leaves: 0,
+				block_gap: None,
+			}
+		}
+
+		fn status(&self, _id: BlockId<Block>) -> sp_blockchain::Result<BlockStatus> {
+			Ok(BlockStatus::InChain)
+		}
+
+		fn number(&self, _hash: Hash) -> sp_blockchain::Result<Option<NumberFor<Block>>> {
+			Ok(Some(0))
+		}
+
+		fn hash(&self, _number: NumberFor<Block>) -> sp_blockchain::Result<Option<Hash>> {
+			Ok(Some(Default::default()))
+		}
+	}
+
+	fn rpc() -> jsonrpsee::RpcModule<QuadraticVoting<TestClient, Block>> {
+		QuadraticVoting::new(Arc::new(TestClient)).into_rpc()
+	}
+
+	#[tokio::test]
+	async fn proposal_is_returned_with_votes_as_strings() {
+		let proposal: Option<RpcProposal<AccountId, BlockNumber>> =
+			rpc().call("qv_proposal", [0]).await.unwrap();
+		assert_eq!(
+			proposal,
+			Some(RpcProposal {
+				index: 0,
+				aye: "3".into(),
+				nay: "0".into(),
+				hash: b"proposal".to_vec().into(),
+				proposer: Sr25519Keyring::Alice.to_account_id(),
+				end: 11,
+				status: ProposalStatus::Voting,
+			})
+		);
+
+		let missing: Option<RpcProposal<AccountId, BlockNumber>> =
+			rpc().call("qv_proposal", [1]).await.unwrap();
+		assert_eq!(missing, None);
+	}
+
+	#[tokio::test]
+	async fn active_proposals_are_listed() {
+		let active: Vec<ProposalIndex> =
+			rpc().call("qv_activeProposals", EmptyParams::new()).await.unwrap();
+		assert_eq!(active, vec![0]);
+	}
+
+	#[tokio::test]
+	async fn vote_of_returns_amounts_as_strings() {
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let vote: Option<RpcVote<BlockNumber>> = rpc().call("qv_voteOf", (0, bob)).await.unwrap();
+		assert_eq!(
+			vote,
+			Some(RpcVote {
+				side: Some(VoteSide::Aye),
+				votes: "3".into(),
+				cost: "9".into(),
+				at_block: 2,
+				released: false,
+				paid_into_pot: false,
+			})
+		);
+
+		let charlie = Sr25519Keyring::Charlie.to_account_id();
+		let vote: Option<RpcVote<BlockNumber>> =
+			rpc().call("qv_voteOf", (0, charlie)).await.unwrap();
+		assert_eq!(vote, None);
+	}
+
+	#[tokio::test]
+	async fn account_history_lists_unreleased_votes() {
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let history: RpcAccountHistory<BlockNumber> =
+			rpc().call("qv_accountHistory", [bob]).await.unwrap();
+		assert_eq!(history.votes_cast, "3");
+		assert_eq!(history.credits_spent, "9");
+		assert_eq!(history.outstanding_reserves, "9");
+		assert_eq!(history.votes.len(), 1);
+		assert_eq!(history.votes[0].0, 0);
+		assert_eq!(history.votes[0].1.cost, "9");
+	}
+
+	#[tokio::test]
+	async fn quote_cost_handles_large_votes() {
+		let cost: Option<String> = rpc().call("qv_quoteCost", [u64::MAX]).await.unwrap();
+		assert_eq!(cost, Some("340282366920938463426481119284349108225".into()));
+
+		let at = Hash::default();
+		let cost: Option<String> = rpc().call("qv_quoteCost", (3, Some(at))).await.unwrap();
+		assert_eq!(cost, Some("9".into()));
+	}
+
+	#[tokio::test]
+	async fn engineering_guild_is_queried_when_asked_for() {
+		let at: Option<Hash> = None;
+		let guild = PalletInstance::EngineeringGuild;
+		let proposal: Option<RpcProposal<AccountId, BlockNumber>> =
+			rpc().call("qv_proposal", (0, at, guild)).await.unwrap();
+		assert_eq!(proposal, None);
+
+		let active: Vec<ProposalIndex> =
+			rpc().call("qv_activeProposals", (at, guild)).await.unwrap();
+		assert!(active.is_empty());
+
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let vote: Option<RpcVote<BlockNumber>> =
+			rpc().call("qv_voteOf", (0, bob, at, guild)).await.unwrap();
+		assert_eq!(vote, None);
+
+		let main = PalletInstance::QuadraticVoting;
+		let active: Vec<ProposalIndex> =
+			rpc().call("qv_activeProposals", (at, main)).await.unwrap();
+		assert_eq!(active, vec![0]);
+	}
+
+	#[tokio::test]
+	async fn eligibility_reports_reason() {
+		let charlie = Sr25519Keyring::Charlie.to_account_id();
+		let eligibility: RpcEligibility =
+			rpc().call("qv_eligibility", (0, charlie)).await.unwrap();
+		assert_eq!(eligibility, RpcEligibility { eligible: true, reason: None });
+
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let eligibility: RpcEligibility = rpc().call("qv_eligibility", (0, bob)).await.unwrap();
+		assert_eq!(
+			eligibility,
+			RpcEligibility { eligible: false, reason: Some(Ineligibility::AlreadyVoted) }
+		);
+	}
+}
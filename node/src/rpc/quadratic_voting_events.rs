@@ -0,0 +1,752 @@
+//! Subscriptions streaming the events of the quadratic voting pallet.
+//!
+//! Subscribers follow one instance of the pallet, on either the best or the finalized chain. Events
+//! are read from the `System::Events` storage of every block that becomes part of the followed
+//! chain, and proposals from the storage of the instance at that block. When following
+//! best blocks, a re-org first produces a `retracted` notification for every block that left the
+//! best chain, so subscribers can drop the events they received from it.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use futures::{future, FutureExt, StreamExt};
+use jsonrpsee::{proc_macros::rpc, types::SubscriptionResult, SubscriptionSink};
+use node_template_runtime::{
+	opaque::Block, AccountId, BlockNumber, Event, Hash, QuadraticVotingInstance, Runtime,
+};
+use pallet_quadratic_voting::{Proposal, ProposalIndex, ProposalInfo, ProposalStatus};
+use sc_client_api::{Backend, BlockchainEvents, StateBackend};
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use sp_core::{blake2_128, twox_128, Decode, Encode};
+use sp_runtime::generic::BlockId;
+
+use super::quadratic_voting::RpcProposal;
+
+/// Which blocks a subscription follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockSelection {
+	/// Follow the best chain. Events can be retracted by a re-org.
+	Best,
+	/// Follow the finalized chain. Events are never retracted.
+	Finalized,
+}
+
+impl Default for BlockSelection {
+	fn default() -> Self {
+		BlockSelection::Best
+	}
+}
+
+/// Instance of the quadratic voting pallet a subscription follows or a query is answered from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PalletInstance {
+	/// The main voting body.
+	QuadraticVoting,
+	/// The engineering guild.
+	EngineeringGuild,
+}
+
+impl Default for PalletInstance {
+	fn default() -> Self {
+		PalletInstance::QuadraticVoting
+	}
+}
+
+impl PalletInstance {
+	/// Name of the instance in `construct_runtime!`, which prefixes its storage.
+	fn pallet_name(&self) -> &'static str {
+		match self {
+			PalletInstance::QuadraticVoting => "QuadraticVoting",
+			PalletInstance::EngineeringGuild => "EngineeringGuild",
+		}
+	}
+}
+
+impl From<PalletInstance> for QuadraticVotingInstance {
+	fn from(instance: PalletInstance) -> Self {
+		match instance {
+			PalletInstance::QuadraticVoting => QuadraticVotingInstance::QuadraticVoting,
+			PalletInstance::EngineeringGuild => QuadraticVotingInstance::EngineeringGuild,
+		}
+	}
+}
+
+/// Options of a quadratic voting subscription.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SubscriptionOptions {
+	/// Only notify about this proposal.
+	pub proposal: Option<ProposalIndex>,
+	/// Which blocks to follow.
+	pub blocks: BlockSelection,
+	/// Which instance of the pallet to follow.
+	pub instance: PalletInstance,
+}
+
+/// Notification pushed to `qv_subscribeProposals` subscribers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ProposalNotification<AccountId, BlockNumber, Hash> {
+	/// A proposal was created.
+	#[serde(rename_all = "camelCase")]
+	Proposed {
+		/// Block that contains the proposal.
+		block_hash: Hash,
+		/// The proposal as of that block.
+		proposal: RpcProposal<AccountId, BlockNumber>,
+	},
+	/// A block left the best chain, so the proposals notified from it are no longer part of it.
+	#[serde(rename_all = "camelCase")]
+	Retracted {
+		/// Block that was retracted.
+		block_hash: Hash,
+	},
+}
+
+/// Notification pushed to `qv_subscribeVotes` subscribers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum VoteNotification<AccountId, Hash> {
+	/// A vote was cast.
+	#[serde(rename_all = "camelCase")]
+	Voted {
+		/// Block that contains the vote.
+		block_hash: Hash,
+		/// Index of the proposal that was voted on.
+		proposal: ProposalIndex,
+		/// Account that voted.
+		voter: AccountId,
+		/// Whether the vote supports the proposal.
+		aye: bool,
+		/// Number of votes cast.
+		votes: String,
+	},
+	/// A block left the best chain, so the votes notified from it are no longer part of it.
+	#[serde(rename_all = "camelCase")]
+	Retracted {
+		/// Block that was retracted.
+		block_hash: Hash,
+	},
+}
+
+/// Quadratic voting subscription methods.
+#[rpc(client, server)]
+pub trait QuadraticVotingEventsApi<AccountId, BlockNumber, Hash> {
+	/// Subscribe to proposals being created.
+	#[subscription(
+		name = "qv_subscribeProposals",
+		unsubscribe = "qv_unsubscribeProposals",
+		item = ProposalNotification<AccountId, BlockNumber, Hash>,
+	)]
+	fn subscribe_proposals(&self, options: Option<SubscriptionOptions>);
+
+	/// Subscribe to votes being cast.
+	#[subscription(
+		name = "qv_subscribeVotes",
+		unsubscribe = "qv_unsubscribeVotes",
+		item = VoteNotification<AccountId, Hash>,
+	)]
+	fn subscribe_votes(&self, options: Option<SubscriptionOptions>);
+}
+
+/// A block whose events are pushed to subscribers, or that left the best chain.
+#[derive(Clone, Copy)]
+enum ChainUpdate {
+	Enacted(Hash),
+	Retracted(Hash),
+}
+
+/// Events of either instance of the pallet that subscribers are notified about.
+enum PalletEvent {
+	Proposed(ProposalIndex),
+	Voted(ProposalIndex, AccountId, bool, u128),
+}
+
+fn pallet_event<I: 'static>(
+	event: pallet_quadratic_voting::Event<Runtime, I>,
+) -> Option<PalletEvent>
+where
+	Runtime: pallet_quadratic_voting::Config<I>,
+{
+	use pallet_quadratic_voting::Event::*;
+	match event {
+		Proposed(index) => Some(PalletEvent::Proposed(index)),
+		VotedAye(index, voter, votes) => Some(PalletEvent::Voted(index, voter, true, votes)),
+		VotedNay(index, voter, votes) => Some(PalletEvent::Voted(index, voter, false, votes)),
+		_ => None,
+	}
+}
+
+/// Implements the [`QuadraticVotingEventsApiServer`] RPC trait.
+pub struct QuadraticVotingEvents<C, B> {
+	client: Arc<C>,
+	backend: Arc<B>,
+	executor: SubscriptionTaskExecutor,
+	_marker: PhantomData<Block>,
+}
+
+impl<C, B> QuadraticVotingEvents<C, B>
+where
+	C: BlockchainEvents<Block> + Send + Sync + 'static,
+	B: Backend<Block> + Send + Sync + 'static,
+{
+	/// Creates a new instance of the QuadraticVotingEvents RPC helper.
+	pub fn new(client: Arc<C>, backend: Arc<B>, executor: SubscriptionTaskExecutor) -> Self {
+		Self { client, backend, executor, _marker: Default::default() }
+	}
+
+	/// Stream of blocks that join or leave the followed chain, in chain order.
+	fn chain_updates(
+		&self,
+		blocks: BlockSelection,
+	) -> futures::stream::BoxStream<'static, ChainUpdate> {
+		match blocks {
+			BlockSelection::Best => self
+				.client
+				.import_notification_stream()
+				.filter(|n| future::ready(n.is_new_best))
+				.flat_map(|n| {
+					let mut updates = Vec::new();
+					if let Some(route) = n.tree_route.as_ref() {
+						updates.extend(
+							route.retracted().iter().map(|b| ChainUpdate::Retracted(b.hash)),
+						);
+						updates.extend(route.enacted().iter().map(|b| ChainUpdate::Enacted(b.hash)));
+					}
+					updates.push(ChainUpdate::Enacted(n.hash));
+					futures::stream::iter(updates)
+				})
+				.boxed(),
+			BlockSelection::Finalized => self
+				.client
+				.finality_notification_stream()
+				.flat_map(|n| {
+					let mut updates: Vec<_> =
+						n.tree_route.iter().map(|hash| ChainUpdate::Enacted(*hash)).collect();
+					updates.push(ChainUpdate::Enacted(n.hash));
+					futures::stream::iter(updates)
+				})
+				.boxed(),
+		}
+	}
+
+	/// Spawn a task that pipes the notifications produced by `notify` from the events of the
+	/// followed instance into `sink`.
+	fn spawn_subscription<T, F>(
+		&self,
+		mut sink: SubscriptionSink,
+		options: &SubscriptionOptions,
+		notify: F,
+	) where
+		T: Serialize + Send + 'static,
+		F: Fn(ChainUpdate, Vec<PalletEvent>) -> Vec<T> + Send + 'static,
+	{
+		let backend = self.backend.clone();
+		let instance = options.instance;
+		let stream = self
+			.chain_updates(options.blocks)
+			.flat_map(move |update| {
+				let events = match update {
+					ChainUpdate::Enacted(hash) => block_events(&*backend, hash, instance),
+					ChainUpdate::Retracted(_) => Vec::new(),
+				};
+				futures::stream::iter(notify(update, events))
+			})
+			.boxed();
+
+		let fut = async move {
+			sink.pipe_from_stream(stream).await;
+		};
+		self.executor.spawn("quadratic-voting-rpc-subscription", Some("rpc"), fut.boxed());
+	}
+}
+
+/// Value of the storage item `item` of `pallet` in the block `hash`, under the encoded `map_key`
+/// if the item is a map hashed with `Blake2_128Concat`.
+///
+/// Blocks whose state has been pruned have no storage. Such blocks and values that don't decode
+/// are logged, since subscribers miss the notifications they would have produced.
+fn storage<B: Backend<Block>, T: Decode>(
+	backend: &B,
+	hash: Hash,
+	pallet: &str,
+	item: &str,
+	map_key: Option<Vec<u8>>,
+) -> Option<T> {
+	let mut key = [twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat();
+	if let Some(map_key) = map_key {
+		key.extend(blake2_128(&map_key));
+		key.extend(map_key);
+	}
+	let data = match backend.state_at(BlockId::hash(hash)).map(|state| state.storage(&key)) {
+		Ok(Ok(data)) => data?,
+		Ok(Err(e)) => {
+			log::warn!(
+				target: "rpc",
+				"Unable to read {}::{} at block {}: {}",
+				pallet,
+				item,
+				hash,
+				e
+			);
+			return None
+		},
+		Err(e) => {
+			log::warn!(target: "rpc", "State of block {} is unavailable, skipping it: {}", hash, e);
+			return None
+		},
+	};
+	match T::decode(&mut &data[..]) {
+		Ok(value) => Some(value),
+		Err(e) => {
+			log::warn!(
+				target: "rpc",
+				"Unable to decode {}::{} at block {}: {}",
+				pallet,
+				item,
+				hash,
+				e
+			);
+			None
+		},
+	}
+}
+
+/// Events of the `instance` of the pallet emitted in the block `hash`.
+fn block_events<B: Backend<Block>>(
+	backend: &B,
+	hash: Hash,
+	instance: PalletInstance,
+) -> Vec<PalletEvent> {
+	storage::<_, Vec<frame_system::EventRecord<Event, Hash>>>(
+		backend, hash, "System", "Events", None,
+	)
+	.unwrap_or_default()
+	.into_iter()
+	.filter_map(|record| match (instance, record.event) {
+		(PalletInstance::QuadraticVoting, Event::QuadraticVoting(event)) => pallet_event(event),
+		(PalletInstance::EngineeringGuild, Event::EngineeringGuild(event)) => pallet_event(event),
+		_ => None,
+	})
+	.collect()
+}
+
+/// The proposal at `index` of the `instance` of the pallet as of the block `hash`.
+fn proposal_at<B: Backend<Block>>(
+	backend: &B,
+	hash: Hash,
+	instance: PalletInstance,
+	index: ProposalIndex,
+) -> Option<ProposalInfo<AccountId, BlockNumber>> {
+	let pallet = instance.pallet_name();
+	let proposal: Proposal<AccountId, BlockNumber> =
+		storage(backend, hash, pallet, "Proposals", Some(index.encode()))?;
+	let number: BlockNumber = storage(backend, hash, "System", "Number", None)?;
+	// Same as `Pallet::proposal_status`, as of the block that is read.
+	let status = if number <= proposal.end {
+		ProposalStatus::Voting
+	} else if proposal.aye > proposal.nay {
+		ProposalStatus::Passed
+	} else {
+		ProposalStatus::Rejected
+	};
+	Some(ProposalInfo { index, proposal, status })
+}
+
+impl<C, B> QuadraticVotingEventsApiServer<AccountId, BlockNumber, Hash>
+	for QuadraticVotingEvents<C, B>
+where
+	C: BlockchainEvents<Block> + Send + Sync + 'static,
+	B: Backend<Block> + Send + Sync + 'static,
+{
+	fn subscribe_proposals(
+		&self,
+		sink: SubscriptionSink,
+		options: Option<SubscriptionOptions>,
+	) -> SubscriptionResult {
+		let options = options.unwrap_or_default();
+		let backend = self.backend.clone();
+		let (proposal, instance) = (options.proposal, options.instance);
+		self.spawn_subscription(sink, &options, move |update, events| match update {
+			ChainUpdate::Retracted(block_hash) =>
+				vec![ProposalNotification::Retracted { block_hash }],
+			ChainUpdate::Enacted(block_hash) => events
+				.into_iter()
+				.filter_map(|event| match event {
+					PalletEvent::Proposed(index) if proposal.map_or(true, |p| p == index) =>
+						Some(index),
+					_ => None,
+				})
+				.filter_map(|index| {
+					// Look the proposal up as of the block that created it.
+					proposal_at(&*backend, block_hash, instance, index)
+				})
+				.map(|info| ProposalNotification::Proposed { block_hash, proposal: info.into() })
+				.collect(),
+		});
+		Ok(())
+	}
+
+	fn subscribe_votes(
+		&self,
+		sink: SubscriptionSink,
+		options: Option<SubscriptionOptions>,
+	) -> SubscriptionResult {
+		let options = options.unwrap_or_default();
+		let only = options.proposal;
+		self.spawn_subscription(sink, &options, move |update, events| match update {
+			ChainUpdate::Retracted(block_hash) => vec![VoteNotification::Retracted { block_hash }],
+			ChainUpdate::Enacted(block_hash) => events
+				.into_iter()
+				.filter_map(|event| match event {
+					PalletEvent::Voted(proposal, voter, aye, votes) =>
+						Some((proposal, voter, aye, votes)),
+					_ => None,
+				})
+				.filter(|(proposal, ..)| only.map_or(true, |p| p == *proposal))
+				.map(|(proposal, voter, aye, votes)| VoteNotification::Voted {
+					block_hash,
+					proposal,
+					voter,
+					aye,
+					votes: votes.to_string(),
+				})
+				.collect(),
+		});
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_system::{EventRecord, Phase};
+	use futures::future::BoxFuture;
+	use node_template_runtime::Header;
+	use sc_client_api::{
+		backend::{BlockImportOperation, NewBlockState},
+		in_mem, BlockImportNotification, FinalityNotification, FinalityNotifications,
+		ImportNotifications, StorageEventStream,
+	};
+	use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedSender};
+	use sp_consensus::BlockOrigin;
+	use sp_core::{
+		storage::{Storage, StorageKey},
+		traits::SpawnNamed,
+	};
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::{traits::Header as HeaderT, StateVersion};
+	use std::{collections::BTreeMap, sync::Mutex};
+
+	/// Client that announces the blocks a test imports into an in-memory backend.
+	#[derive(Default)]
+	struct TestClient {
+		imports: Mutex<Vec<TracingUnboundedSender<BlockImportNotification<Block>>>>,
+		finality: Mutex<Vec<TracingUnboundedSender<FinalityNotification<Block>>>>,
+	}
+
+	impl TestClient {
+		fn import(&self, header: &Header) {
+			for sink in self.imports.lock().unwrap().iter() {
+				let _ = sink.unbounded_send(BlockImportNotification {
+					hash: header.hash(),
+					origin: BlockOrigin::Own,
+					header: header.clone(),
+					is_new_best: true,
+					tree_route: None,
+				});
+			}
+		}
+
+		fn finalize(&self, header: &Header) {
+			for sink in self.finality.lock().unwrap().iter() {
+				let _ = sink.unbounded_send(FinalityNotification {
+					hash: header.hash(),
+					header: header.clone(),
+					tree_route: Arc::new([]),
+					stale_heads: Arc::new([]),
+				});
+			}
+		}
+	}
+
+	impl BlockchainEvents<Block> for TestClient {
+		fn import_notification_stream(&self) -> ImportNotifications<Block> {
+			let (sink, stream) = tracing_unbounded("test_import_notifications");
+			self.imports.lock().unwrap().push(sink);
+			stream
+		}
+
+		fn finality_notification_stream(&self) -> FinalityNotifications<Block> {
+			let (sink, stream) = tracing_unbounded("test_finality_notifications");
+			self.finality.lock().unwrap().push(sink);
+			stream
+		}
+
+		fn storage_changes_notification_stream(
+			&self,
+			_filter_keys: Option<&[StorageKey]>,
+			_child_filter_keys: Option<&[(StorageKey, Option<Vec<StorageKey>>)]>,
+		) -> sp_blockchain::Result<StorageEventStream<Hash>> {
+			Err(sp_blockchain::Error::Msg("subscriptions do not watch storage".into()))
+		}
+	}
+
+	/// Runs subscription tasks on the runtime of the test.
+	struct TokioSpawner;
+
+	impl SpawnNamed for TokioSpawner {
+		fn spawn_blocking(
+			&self,
+			_name: &'static str,
+			_group: Option<&'static str>,
+			future: BoxFuture<'static, ()>,
+		) {
+			tokio::spawn(future);
+		}
+
+		fn spawn(
+			&self,
+			_name: &'static str,
+			_group: Option<&'static str>,
+			future: BoxFuture<'static, ()>,
+		) {
+			tokio::spawn(future);
+		}
+	}
+
+	struct TestChain {
+		client: Arc<TestClient>,
+		backend: Arc<in_mem::Backend<Block>>,
+		best: Header,
+	}
+
+	impl TestChain {
+		fn new() -> Self {
+			let backend = Arc::new(in_mem::Backend::new());
+			let genesis = import(&backend, Default::default(), 0, BTreeMap::new());
+			TestChain { client: Default::default(), backend, best: genesis }
+		}
+
+		fn rpc(
+			&self,
+		) -> jsonrpsee::RpcModule<QuadraticVotingEvents<TestClient, in_mem::Backend<Block>>> {
+			QuadraticVotingEvents::new(
+				self.client.clone(),
+				self.backend.clone(),
+				Arc::new(TokioSpawner),
+			)
+			.into_rpc()
+		}
+
+		/// Import a block on top of the best one that emitted `events` and has `storage`, and
+		/// announce it. Events in `storage` take precedence over `events`.
+		fn push(&mut self, events: Vec<Event>, mut storage: BTreeMap<Vec<u8>, Vec<u8>>) -> Header {
+			let number = self.best.number() + 1;
+			let records: Vec<_> = events
+				.into_iter()
+				.map(|event| EventRecord { phase: Phase::ApplyExtrinsic(0), event, topics: vec![] })
+				.collect();
+			storage.entry(key("System", "Events")).or_insert_with(|| records.encode());
+			storage.insert(key("System", "Number"), number.encode());
+			self.best = import(&self.backend, self.best.hash(), number, storage);
+			self.client.import(&self.best);
+			self.best.clone()
+		}
+	}
+
+	// Import a block with a state that holds exactly `storage` into `backend`.
+	fn import(
+		backend: &in_mem::Backend<Block>,
+		parent: Hash,
+		number: BlockNumber,
+		storage: BTreeMap<Vec<u8>, Vec<u8>>,
+	) -> Header {
+		let mut op = backend.begin_operation().unwrap();
+		let storage = Storage { top: storage, children_default: Default::default() };
+		let state_root = op.reset_storage(storage, StateVersion::V1).unwrap();
+		let header =
+			Header::new(number, Default::default(), state_root, parent, Default::default());
+		op.set_block_data(header.clone(), None, None, None, NewBlockState::Normal)
+			.unwrap();
+		backend.commit_operation(op).unwrap();
+		header
+	}
+
+	fn key(pallet: &str, item: &str) -> Vec<u8> {
+		[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+	}
+
+	fn proposal_key(pallet: &str, index: ProposalIndex) -> Vec<u8> {
+		[key(pallet, "Proposals"), blake2_128(&index.encode()).to_vec(), index.encode()].concat()
+	}
+
+	fn proposal(end: BlockNumber) -> Proposal<AccountId, BlockNumber> {
+		Proposal {
+			aye: 0,
+			nay: 0,
+			hash: b"proposal".to_vec().try_into().unwrap(),
+			proposer: Sr25519Keyring::Alice.to_account_id(),
+			end,
+		}
+	}
+
+	fn options(instance: PalletInstance) -> SubscriptionOptions {
+		SubscriptionOptions { instance, ..Default::default() }
+	}
+
+	#[tokio::test]
+	async fn votes_of_the_subscribed_instance_are_notified() {
+		let mut chain = TestChain::new();
+		let rpc = chain.rpc();
+		let mut main = rpc
+			.subscribe("qv_subscribeVotes", [options(PalletInstance::QuadraticVoting)])
+			.await
+			.unwrap();
+		let mut guild = rpc
+			.subscribe("qv_subscribeVotes", [options(PalletInstance::EngineeringGuild)])
+			.await
+			.unwrap();
+
+		let bob = Sr25519Keyring::Bob.to_account_id();
+		let block = chain.push(
+			vec![
+				Event::QuadraticVoting(pallet_quadratic_voting::Event::VotedAye(0, bob.clone(), 3)),
+				Event::EngineeringGuild(pallet_quadratic_voting::Event::VotedNay(
+					1,
+					bob.clone(),
+					2,
+				)),
+			],
+			BTreeMap::new(),
+		);
+
+		let (vote, _) = main.next::<VoteNotification<AccountId, Hash>>().await.unwrap().unwrap();
+		assert_eq!(
+			vote,
+			VoteNotification::Voted {
+				block_hash: block.hash(),
+				proposal: 0,
+				voter: bob.clone(),
+				aye: true,
+				votes: "3".into(),
+			}
+		);
+		let (vote, _) = guild.next::<VoteNotification<AccountId, Hash>>().await.unwrap().unwrap();
+		assert_eq!(
+			vote,
+			VoteNotification::Voted {
+				block_hash: block.hash(),
+				proposal: 1,
+				voter: bob,
+				aye: false,
+				votes: "2".into(),
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn guild_proposals_are_read_from_its_storage() {
+		let mut chain = TestChain::new();
+		let rpc = chain.rpc();
+		let mut guild = rpc
+			.subscribe("qv_subscribeProposals", [options(PalletInstance::EngineeringGuild)])
+			.await
+			.unwrap();
+
+		// A proposal of the main instance with the same index is not mixed up with it.
+		let storage = [
+			(proposal_key("EngineeringGuild", 0), proposal(6).encode()),
+			(proposal_key("QuadraticVoting", 0), proposal(11).encode()),
+		]
+		.into_iter()
+		.collect();
+		let block = chain.push(
+			vec![
+				Event::QuadraticVoting(pallet_quadratic_voting::Event::Proposed(0)),
+				Event::EngineeringGuild(pallet_quadratic_voting::Event::Proposed(0)),
+			],
+			storage,
+		);
+
+		let (notification, _) = guild
+			.next::<ProposalNotification<AccountId, BlockNumber, Hash>>()
+			.await
+			.unwrap()
+			.unwrap();
+		let info = ProposalInfo { index: 0, proposal: proposal(6), status: ProposalStatus::Voting };
+		assert_eq!(
+			notification,
+			ProposalNotification::Proposed { block_hash: block.hash(), proposal: info.into() }
+		);
+	}
+
+	#[tokio::test]
+	async fn finalized_blocks_are_followed_on_request() {
+		let mut chain = TestChain::new();
+		let rpc = chain.rpc();
+		let options = SubscriptionOptions {
+			proposal: Some(1),
+			blocks: BlockSelection::Finalized,
+			instance: PalletInstance::EngineeringGuild,
+		};
+		let mut votes = rpc.subscribe("qv_subscribeVotes", [options]).await.unwrap();
+
+		let alice = Sr25519Keyring::Alice.to_account_id();
+		let vote = |index| {
+			Event::EngineeringGuild(pallet_quadratic_voting::Event::VotedAye(
+				index,
+				alice.clone(),
+				1,
+			))
+		};
+		chain.push(vec![vote(0)], BTreeMap::new());
+		let block = chain.push(vec![vote(1)], BTreeMap::new());
+		// Only the finalized block is read, and only votes on the requested proposal notified.
+		chain.client.finalize(&block);
+
+		let (notification, _) =
+			votes.next::<VoteNotification<AccountId, Hash>>().await.unwrap().unwrap();
+		assert_eq!(
+			notification,
+			VoteNotification::Voted {
+				block_hash: block.hash(),
+				proposal: 1,
+				voter: alice.clone(),
+				aye: true,
+				votes: "1".into(),
+			}
+		);
+	}
+
+	#[tokio::test]
+	async fn blocks_with_undecodable_events_are_skipped() {
+		let mut chain = TestChain::new();
+		let rpc = chain.rpc();
+		let mut votes = rpc
+			.subscribe("qv_subscribeVotes", [SubscriptionOptions::default()])
+			.await
+			.unwrap();
+
+		chain.push(Vec::new(), [(key("System", "Events"), vec![0xff])].into_iter().collect());
+		let alice = Sr25519Keyring::Alice.to_account_id();
+		let vote = pallet_quadratic_voting::Event::VotedNay(2, alice.clone(), 1);
+		let block = chain.push(vec![Event::QuadraticVoting(vote)], BTreeMap::new());
+
+		// The subscription goes on with the next block.
+		let (notification, _) =
+			votes.next::<VoteNotification<AccountId, Hash>>().await.unwrap().unwrap();
+		assert_eq!(
+			notification,
+			VoteNotification::Voted {
+				block_hash: block.hash(),
+				proposal: 2,
+				voter: alice,
+				aye: false,
+				votes: "1".into(),
+			}
+		);
+	}
+}
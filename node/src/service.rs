@@ -1,15 +1,22 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
+use crate::remote_keystore::RemoteKeystore;
+use futures::{channel::mpsc, StreamExt};
 use node_template_runtime::{self, opaque::Block, RuntimeApi};
 use sc_client_api::{BlockBackend, ExecutorProvider};
 use sc_consensus_aura::{ImportQueueParams, SlotProportion, StartAuraParams};
+use sc_consensus_manual_seal::{
+	consensus::{aura::AuraConsensusDataProvider, timestamp::SlotTimestampProvider},
+	EngineCommand, ManualSealParams,
+};
 pub use sc_executor::NativeElseWasmExecutor;
 use sc_finality_grandpa::SharedVoterState;
-use sc_keystore::LocalKeystore;
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sc_telemetry::{Telemetry, TelemetryWorker};
+use sc_transaction_pool_api::TransactionPool;
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
-use std::{sync::Arc, time::Duration};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 // Our native executor instance.
 pub struct ExecutorDispatch;
@@ -36,6 +43,38 @@ pub(crate) type FullClient =
 type FullBackend = sc_service::TFullBackend<Block>;
 type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
 
+/// How a development node seals blocks instead of authoring them with Aura.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+	/// Seal a block as soon as a transaction enters the pool.
+	Instant,
+	/// Seal a block whenever the `engine_createBlock` RPC method is called.
+	Manual,
+	/// Seal a block every given number of milliseconds.
+	Interval(u64),
+}
+
+impl FromStr for Sealing {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"instant" => Ok(Sealing::Instant),
+			"manual" => Ok(Sealing::Manual),
+			_ => match s.strip_prefix("interval=") {
+				Some(millis) => millis
+					.parse()
+					.map(Sealing::Interval)
+					.map_err(|_| format!("Invalid sealing interval: {}", millis)),
+				None => Err(format!(
+					"Unknown sealing mode {}, expected instant, manual or interval=<ms>",
+					s
+				)),
+			},
+		}
+	}
+}
+
 pub fn new_partial(
 	config: &Configuration,
 ) -> Result<
@@ -58,10 +97,33 @@ pub fn new_partial(
 	>,
 	ServiceError,
 > {
-	if config.keystore_remote.is_some() {
-		return Err(ServiceError::Other("Remote Keystores are not supported.".into()))
-	}
+	new_partial_with_sealing(config, None)
+}
 
+/// Like [`new_partial`], but with an import queue for blocks sealed by `sealing` if it is set.
+fn new_partial_with_sealing(
+	config: &Configuration,
+	sealing: Option<Sealing>,
+) -> Result<
+	sc_service::PartialComponents<
+		FullClient,
+		FullBackend,
+		FullSelectChain,
+		sc_consensus::DefaultImportQueue<Block, FullClient>,
+		sc_transaction_pool::FullPool<Block, FullClient>,
+		(
+			sc_finality_grandpa::GrandpaBlockImport<
+				FullBackend,
+				Block,
+				FullClient,
+				FullSelectChain,
+			>,
+			sc_finality_grandpa::LinkHalf<Block, FullClient, FullSelectChain>,
+			Option<Telemetry>,
+		),
+	>,
+	ServiceError,
+> {
 	let telemetry = config
 		.telemetry_endpoints
 		.clone()
@@ -110,6 +172,24 @@ pub fn new_partial(
 		telemetry.as_ref().map(|x| x.handle()),
 	)?;
 
+	if sealing.is_some() {
+		let import_queue = sc_consensus_manual_seal::import_queue(
+			Box::new(client.clone()),
+			&task_manager.spawn_essential_handle(),
+			config.prometheus_registry(),
+		);
+		return Ok(sc_service::PartialComponents {
+			client,
+			backend,
+			task_manager,
+			import_queue,
+			keystore_container,
+			select_chain,
+			transaction_pool,
+			other: (grandpa_block_import, grandpa_link, telemetry),
+		})
+	}
+
 	let slot_duration = sc_consensus_aura::slot_duration(&*client)?;
 
 	let import_queue =
@@ -149,15 +229,28 @@ pub fn new_partial(
 	})
 }
 
-fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
-	// FIXME: here would the concrete keystore be built,
-	//        must return a concrete type (NOT `LocalKeystore`) that
-	//        implements `CryptoStore` and `SyncCryptoStore`
-	Err("Remote Keystore not supported.")
+fn remote_keystore(url: &str) -> Result<Arc<RemoteKeystore>, String> {
+	RemoteKeystore::new(url).map(Arc::new)
 }
 
-/// Builds a new service for a full client.
-pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+/// Insert the key the quadratic voting offchain worker signs with, derived from the development key
+/// seed set by `--dev` or `--alice` and friends.
+fn insert_dev_offchain_key(keystore: &SyncCryptoStorePtr, seed: &str) -> Result<(), ServiceError> {
+	SyncCryptoStore::sr25519_generate_new(
+		&**keystore,
+		pallet_quadratic_voting::crypto::KEY_TYPE,
+		Some(seed),
+	)
+	.map(|_| ())
+	.map_err(|e| ServiceError::Other(format!("Error inserting offchain worker key: {}", e)))
+}
+
+/// Builds a new service for a full client, which seals blocks with `sealing` instead of Aura and
+/// GRANDPA if it is set.
+pub fn new_full(
+	mut config: Configuration,
+	sealing: Option<Sealing>,
+) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -167,7 +260,7 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		select_chain,
 		transaction_pool,
 		other: (block_import, grandpa_link, mut telemetry),
-	} = new_partial(&config)?;
+	} = new_partial_with_sealing(&config, sealing)?;
 
 	if let Some(url) = &config.keystore_remote {
 		match remote_keystore(url) {
@@ -206,6 +299,16 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		})?;
 
 	if config.offchain_worker.enabled {
+		// Offchain workers always sign with keys of the local keystore, which the client is given
+		// when it is built, before a remote keystore can be set. With a remote keystore, no key is
+		// put into the node process, so the offchain worker has none to sign with.
+		if config.keystore_remote.is_some() {
+			log::warn!("Offchain workers have no key to sign with when keys are kept remotely");
+		} else if let (Some(seed), Some(local)) =
+			(&config.dev_key_seed, keystore_container.local_keystore())
+		{
+			insert_dev_offchain_key(&(local as SyncCryptoStorePtr), seed)?;
+		}
 		sc_service::build_offchain_workers(
 			&config,
 			task_manager.spawn_handle(),
@@ -218,16 +321,32 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 	let force_authoring = config.force_authoring;
 	let backoff_authoring_blocks: Option<()> = None;
 	let name = config.network.node_name.clone();
-	let enable_grandpa = !config.disable_grandpa;
+	let enable_grandpa = !config.disable_grandpa && sealing.is_none();
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	// Commands of the `engine_*` RPC methods, which are only served with manual sealing.
+	let (command_sink, commands_stream) = match sealing {
+		Some(Sealing::Manual) => {
+			let (sink, stream) = mpsc::channel(1024);
+			(Some(sink), Some(stream))
+		},
+		_ => (None, None),
+	};
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
-
-		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
+		let backend = backend.clone();
+
+		Box::new(move |deny_unsafe, subscription_executor| {
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				backend: backend.clone(),
+				subscription_executor,
+				deny_unsafe,
+				command_sink: command_sink.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
@@ -245,7 +364,64 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
 		telemetry: telemetry.as_mut(),
 	})?;
 
-	if role.is_authority() {
+	if let Some(sealing) = sealing {
+		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
+			task_manager.spawn_handle(),
+			client.clone(),
+			transaction_pool.clone(),
+			prometheus_registry.as_ref(),
+			telemetry.as_ref().map(|x| x.handle()),
+		);
+
+		let seal_new_block = |create_empty, finalize| EngineCommand::SealNewBlock {
+			create_empty,
+			finalize,
+			parent_hash: None,
+			sender: None,
+		};
+		let commands_stream = match sealing {
+			Sealing::Manual =>
+				commands_stream.expect("channel is created for manual sealing; qed").boxed(),
+			Sealing::Instant => transaction_pool
+				.import_notification_stream()
+				.map(move |_| seal_new_block(false, true))
+				.boxed(),
+			Sealing::Interval(millis) => futures::stream::unfold((), move |()| async move {
+				futures_timer::Delay::new(Duration::from_millis(millis)).await;
+				Some((seal_new_block(true, true), ()))
+			})
+			.boxed(),
+		};
+
+		let cidp_client = client.clone();
+		let manual_seal = sc_consensus_manual_seal::run_manual_seal(ManualSealParams {
+			block_import: client.clone(),
+			env: proposer_factory,
+			client: client.clone(),
+			pool: transaction_pool,
+			commands_stream,
+			select_chain,
+			consensus_data_provider: Some(Box::new(AuraConsensusDataProvider::new(client))),
+			create_inherent_data_providers: move |_, ()| {
+				let client = cidp_client.clone();
+				async move {
+					// Every block is one slot after its parent, however quickly it is sealed, so
+					// that the runtime accepts the slot and timestamp.
+					let timestamp =
+						SlotTimestampProvider::new_aura(client).map_err(|e| format!("{:?}", e))?;
+					let slot =
+						sp_consensus_aura::inherents::InherentDataProvider::new(timestamp.slot());
+					Ok::<_, Box<dyn std::error::Error + Send + Sync>>((timestamp, slot))
+				}
+			},
+		});
+
+		task_manager.spawn_essential_handle().spawn_blocking(
+			"manual-seal",
+			Some("block-authoring"),
+			manual_seal,
+		);
+	} else if role.is_authority() {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
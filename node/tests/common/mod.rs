@@ -0,0 +1,278 @@
+//! Helpers to run a development node and talk to it over WebSocket RPC.
+
+use jsonrpsee::{
+	core::client::{ClientT, SubscriptionClientT},
+	rpc_params,
+	ws_client::{WsClient, WsClientBuilder},
+};
+use node_template_runtime::{
+	AccountId, Balance, BlockNumber, Call, EngineeringGuild, Event, GovernanceTransfers, Hash,
+	Header, Index, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic, VERSION,
+};
+use pallet_quadratic_voting::currency::CheckpointCalls;
+use sc_transaction_pool_api::TransactionStatus;
+use sp_core::{
+	hashing::{blake2_128, twox_128},
+	storage::{StorageData, StorageKey},
+	Bytes, Decode, Encode, Pair,
+};
+use sp_keyring::Sr25519Keyring;
+use sp_runtime::generic::Era;
+use std::{
+	net::TcpListener,
+	process::{Child, Command, Stdio},
+	time::{Duration, Instant},
+};
+
+/// How long to wait for the node to come up, or for a block or transaction.
+const TIMEOUT: Duration = Duration::from_secs(180);
+
+pub type AccountInfo =
+	frame_system::AccountInfo<Index, <Runtime as frame_system::Config>::AccountData>;
+pub type EventRecord = frame_system::EventRecord<Event, Hash>;
+
+/// A `node-template --dev --tmp` process that is killed when dropped.
+pub struct Node {
+	process: Child,
+	ws_port: u16,
+}
+
+impl Node {
+	/// Start a development node with a fresh database on free local ports.
+	///
+	/// The offchain worker is disabled so that only the transactions of the test are submitted.
+	pub fn start() -> Self {
+		Self::start_with(&[])
+	}
+
+	/// Start a development node like [`Node::start`], with extra command line arguments.
+	pub fn start_with(args: &[&str]) -> Self {
+		let ws_port = free_port();
+		let process = Command::new(env!("CARGO_BIN_EXE_node-template"))
+			.args(["--dev", "--tmp", "--no-telemetry", "--no-prometheus", "--no-mdns"])
+			.args(["--offchain-worker", "never"])
+			.args(args)
+			.args(["--port", &free_port().to_string()])
+			.args(["--rpc-port", &free_port().to_string()])
+			.args(["--ws-port", &ws_port.to_string()])
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.spawn()
+			.expect("node-template binary is built for integration tests; qed");
+		Node { process, ws_port }
+	}
+
+	/// Connect to the WebSocket RPC server of the node once it accepts connections.
+	pub async fn connect(&self) -> Client {
+		let url = format!("ws://127.0.0.1:{}", self.ws_port);
+		let started = Instant::now();
+		loop {
+			match WsClientBuilder::default().build(&url).await {
+				Ok(client) => return Client(client),
+				Err(e) if started.elapsed() > TIMEOUT => panic!("node did not start: {}", e),
+				Err(_) => tokio::time::sleep(Duration::from_millis(250)).await,
+			}
+		}
+	}
+}
+
+impl Drop for Node {
+	fn drop(&mut self) {
+		let _ = self.process.kill();
+		let _ = self.process.wait();
+	}
+}
+
+/// A local port that nothing listens on right now.
+pub fn free_port() -> u16 {
+	TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Key of `item` of `pallet` in storage, for plain values.
+pub fn storage_key(pallet: &str, item: &str) -> Vec<u8> {
+	[twox_128(pallet.as_bytes()), twox_128(item.as_bytes())].concat()
+}
+
+/// Key of the entry of a `Blake2_128Concat` map of `pallet` under `key`.
+pub fn map_key(pallet: &str, item: &str, key: impl Encode) -> Vec<u8> {
+	let key = key.encode();
+	[storage_key(pallet, item), blake2_128(&key).to_vec(), key].concat()
+}
+
+/// JSON-RPC client of a running node.
+pub struct Client(WsClient);
+
+impl Client {
+	/// Decoded value at `key` in the storage of the best block.
+	pub async fn storage<T: Decode>(&self, key: Vec<u8>) -> Option<T> {
+		let data: Option<StorageData> =
+			self.0.request("state_getStorage", rpc_params![StorageKey(key)]).await.unwrap();
+		data.map(|data| T::decode(&mut &data.0[..]).expect("storage decodes; qed"))
+	}
+
+	/// Decoded result of the runtime API function `method` called with `args` at the best block.
+	pub async fn runtime_call<T: Decode>(&self, method: &str, args: impl Encode) -> T {
+		let result: Bytes = self
+			.0
+			.request("state_call", rpc_params![method, Bytes(args.encode())])
+			.await
+			.unwrap();
+		T::decode(&mut &result.0[..]).expect("runtime API result decodes; qed")
+	}
+
+	/// Account of `who` in the best block.
+	pub async fn account(&self, who: Sr25519Keyring) -> AccountInfo {
+		let key = map_key("System", "Account", who.to_account_id());
+		self.storage(key).await.unwrap_or_default()
+	}
+
+	/// Number of the best block.
+	pub async fn best_number(&self) -> BlockNumber {
+		let header: Header = self.0.request("chain_getHeader", None).await.unwrap();
+		header.number
+	}
+
+	/// Wait until the best block is at least `number`.
+	pub async fn wait_for_block(&self, number: BlockNumber) {
+		let started = Instant::now();
+		while self.best_number().await < number {
+			assert!(started.elapsed() < TIMEOUT, "block {} was not authored in time", number);
+			tokio::time::sleep(Duration::from_millis(500)).await;
+		}
+	}
+
+	/// Partial fee that `payment_queryInfo` reports for `call` signed by `signer`.
+	pub async fn query_fee(&self, signer: Sr25519Keyring, call: Call) -> Balance {
+		let extrinsic = self.sign(signer, call).await;
+		let info: serde_json::Value = self
+			.0
+			.request("payment_queryInfo", rpc_params![Bytes(extrinsic.encode())])
+			.await
+			.unwrap();
+		// Balances are serialized as strings, unless they fit into a number.
+		match &info["partialFee"] {
+			serde_json::Value::String(fee) => fee.parse().expect("fee is a number; qed"),
+			fee => fee.as_u64().expect("fee is a number; qed").into(),
+		}
+	}
+
+	/// Sign `call` by `signer`, submit it, and return the events of the block it is included in.
+	pub async fn submit(&self, signer: Sr25519Keyring, call: Call) -> Vec<Event> {
+		let extrinsic = self.sign(signer, call).await;
+
+		let mut status = self
+			.0
+			.subscribe::<TransactionStatus<Hash, Hash>>(
+				"author_submitAndWatchExtrinsic",
+				rpc_params![Bytes(extrinsic.encode())],
+				"author_unwatchExtrinsic",
+			)
+			.await
+			.unwrap();
+		let block = tokio::time::timeout(TIMEOUT, async {
+			loop {
+				match status.next().await.expect("status stream ended").unwrap() {
+					TransactionStatus::InBlock(block) => return block,
+					TransactionStatus::Ready | TransactionStatus::Future |
+					TransactionStatus::Broadcast(_) => continue,
+					other => panic!("transaction was not included: {:?}", other),
+				}
+			}
+		})
+		.await
+		.expect("transaction was not included in time");
+		self.events(block).await
+	}
+
+	/// Like [`Client::submit`], for a node started with `--sealing manual`, which only includes the
+	/// transaction once a block is sealed.
+	pub async fn submit_and_seal(&self, signer: Sr25519Keyring, call: Call) -> Vec<Event> {
+		let extrinsic = self.sign(signer, call).await;
+		let _: Hash = self
+			.0
+			.request("author_submitExtrinsic", rpc_params![Bytes(extrinsic.encode())])
+			.await
+			.unwrap();
+		let block = self.seal_block().await;
+		self.events(block).await
+	}
+
+	/// Seal and finalize a block with `engine_createBlock`, and return its hash.
+	pub async fn seal_block(&self) -> Hash {
+		let created: serde_json::Value = self
+			.0
+			.request("engine_createBlock", rpc_params![true, true, Option::<Hash>::None])
+			.await
+			.unwrap();
+		serde_json::from_value(created["hash"].clone()).expect("created block has a hash; qed")
+	}
+
+	/// Transaction of `call` by `signer` with its next nonce.
+	async fn sign(&self, signer: Sr25519Keyring, call: Call) -> UncheckedExtrinsic {
+		let genesis_hash: Hash =
+			self.0.request("chain_getBlockHash", rpc_params![0]).await.unwrap();
+		let nonce: Index = self
+			.0
+			.request("system_accountNextIndex", rpc_params![signer.to_account_id()])
+			.await
+			.unwrap();
+		signed_extrinsic(signer, call, nonce, genesis_hash)
+	}
+
+	/// Events of the block with hash `block`.
+	async fn events(&self, block: Hash) -> Vec<Event> {
+		let key = StorageKey(storage_key("System", "Events"));
+		let data: Option<StorageData> =
+			self.0.request("state_getStorage", rpc_params![key, block]).await.unwrap();
+		let events: Option<Vec<EventRecord>> =
+			data.map(|data| Decode::decode(&mut &data.0[..]).expect("events decode; qed"));
+		events.unwrap_or_default().into_iter().map(|record| record.event).collect()
+	}
+}
+
+// An immortal transaction of `call` by `signer`.
+fn signed_extrinsic(
+	signer: Sr25519Keyring,
+	call: Call,
+	nonce: Index,
+	genesis_hash: Hash,
+) -> UncheckedExtrinsic {
+	let extra: SignedExtra = (
+		frame_system::CheckNonZeroSender::<Runtime>::new(),
+		frame_system::CheckSpecVersion::<Runtime>::new(),
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(Era::Immortal),
+		frame_system::CheckNonce::<Runtime>::from(nonce),
+		frame_system::CheckWeight::<Runtime>::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+		CheckpointCalls::<Runtime, EngineeringGuild, GovernanceTransfers>::new(),
+	);
+	let payload = SignedPayload::from_raw(
+		call.clone(),
+		extra.clone(),
+		(
+			(),
+			VERSION.spec_version,
+			VERSION.transaction_version,
+			genesis_hash,
+			genesis_hash,
+			(),
+			(),
+			(),
+			(),
+		),
+	);
+	let signature = payload.using_encoded(|e| signer.pair().sign(e));
+	UncheckedExtrinsic::new_signed(
+		call,
+		AccountId::from(signer.public()).into(),
+		signature.into(),
+		extra,
+	)
+}
+
+/// Balance that is charged for `votes` votes.
+pub fn vote_cost(votes: u128) -> Balance {
+	votes * votes
+}
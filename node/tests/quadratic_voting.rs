@@ -0,0 +1,116 @@
+//! Runs a development node and drives a proposal through its lifecycle with signed extrinsics.
+
+mod common;
+
+use common::{map_key, vote_cost, Node};
+use node_template_runtime::{AccountId, BlockNumber, Call, Event, QuadraticVotingInstance};
+use pallet_quadratic_voting::{Proposal, ProposalIndex, ProposalStatus};
+use sp_keyring::Sr25519Keyring::{Alice, Bob};
+use sp_runtime::DispatchError;
+
+// Index of the proposal that was created according to `events`.
+fn proposed(events: &[Event]) -> ProposalIndex {
+	events
+		.iter()
+		.find_map(|event| match event {
+			Event::QuadraticVoting(pallet_quadratic_voting::Event::Proposed(index)) => Some(*index),
+			_ => None,
+		})
+		.expect("proposal is created")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn propose_vote_and_unreserve() {
+	let node = Node::start();
+	let client = node.connect().await;
+
+	// Alice, who has a judged identity in the development chain, proposes.
+	let call = pallet_quadratic_voting::Call::propose { hash: b"e2e".to_vec().try_into().unwrap() };
+	let index = proposed(&client.submit(Alice, Call::QuadraticVoting(call)).await);
+
+	// Bob votes for it. Votes of eligible accounts are fee-less, so only the cost is taken.
+	let before = client.account(Bob).await;
+	let call = pallet_quadratic_voting::Call::vote_aye { proposal_index: index, votes: 3 };
+	assert_eq!(client.query_fee(Bob, Call::QuadraticVoting(call.clone())).await, 0);
+	let remark = frame_system::Call::remark { remark: Vec::new() };
+	assert!(client.query_fee(Bob, Call::System(remark)).await > 0);
+	let events = client.submit(Bob, Call::QuadraticVoting(call)).await;
+	let voted = pallet_quadratic_voting::Event::VotedAye(index, Bob.to_account_id(), 3);
+	assert!(events.contains(&Event::QuadraticVoting(voted)));
+	let after_vote = client.account(Bob).await;
+	assert_eq!(after_vote.data.free, before.data.free - vote_cost(3));
+	assert_eq!(after_vote.data.reserved, before.data.reserved + vote_cost(3));
+
+	let proposal: Proposal<AccountId, BlockNumber> = client
+		.storage(map_key("QuadraticVoting", "Proposals", index))
+		.await
+		.expect("proposal is stored");
+	assert_eq!((proposal.aye, proposal.nay), (3, 0));
+
+	// Once voting is over, Bob gets the cost of the votes back.
+	client.wait_for_block(proposal.end).await;
+	let call = pallet_quadratic_voting::Call::unreserve { proposal_index: index };
+	let events = client.submit(Bob, Call::QuadraticVoting(call)).await;
+	assert!(!events
+		.iter()
+		.any(|event| matches!(event, Event::System(frame_system::Event::ExtrinsicFailed { .. }))));
+	let after_unreserve = client.account(Bob).await;
+	assert_eq!(after_unreserve.data.free, before.data.free);
+	assert_eq!(after_unreserve.data.reserved, before.data.reserved);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_vote_through_the_runtime_api() {
+	let node = Node::start();
+	let client = node.connect().await;
+
+	let call = pallet_quadratic_voting::Call::propose { hash: b"dry".to_vec().try_into().unwrap() };
+	let index = proposed(&client.submit(Alice, Call::QuadraticVoting(call)).await);
+
+	// A vote Bob can afford succeeds, a vote Bob can't afford reports the dispatch error.
+	let method = "QuadraticVotingApi_dry_run_vote";
+	let main = QuadraticVotingInstance::QuadraticVoting;
+	let result: Result<(), DispatchError> =
+		client.runtime_call(method, (main, index, Bob.to_account_id(), true, 3u128)).await;
+	assert_eq!(result, Ok(()));
+	let votes = u64::MAX as u128;
+	let result: Result<(), DispatchError> =
+		client.runtime_call(method, (main, index, Bob.to_account_id(), false, votes)).await;
+	assert!(result.is_err());
+	// The guild has no proposal at that index.
+	let guild = QuadraticVotingInstance::EngineeringGuild;
+	let result: Result<(), DispatchError> =
+		client.runtime_call(method, (guild, index, Bob.to_account_id(), true, 3u128)).await;
+	assert!(result.is_err());
+
+	// Neither of them is persisted.
+	let proposal: Proposal<AccountId, BlockNumber> = client
+		.storage(map_key("QuadraticVoting", "Proposals", index))
+		.await
+		.expect("proposal is stored");
+	assert_eq!((proposal.aye, proposal.nay), (0, 0));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn manual_sealing_skips_through_the_voting_period() {
+	let node = Node::start_with(&["--sealing", "manual"]);
+	let client = node.connect().await;
+
+	let call =
+		pallet_quadratic_voting::Call::propose { hash: b"sealed".to_vec().try_into().unwrap() };
+	let index = proposed(&client.submit_and_seal(Alice, Call::QuadraticVoting(call)).await);
+	let proposal: Proposal<AccountId, BlockNumber> = client
+		.storage(map_key("QuadraticVoting", "Proposals", index))
+		.await
+		.expect("proposal is stored");
+
+	// Empty blocks are sealed on request, without waiting for slots.
+	while client.best_number().await <= proposal.end {
+		client.seal_block().await;
+	}
+
+	let call = pallet_quadratic_voting::Call::finalize { proposal_index: index };
+	let events = client.submit_and_seal(Alice, Call::QuadraticVoting(call)).await;
+	let finalized = pallet_quadratic_voting::Event::Finalized(index, ProposalStatus::Rejected);
+	assert!(events.contains(&Event::QuadraticVoting(finalized)));
+}
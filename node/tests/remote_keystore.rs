@@ -0,0 +1,191 @@
+//! Talks to the reference keystore server through the remote keystore, and runs a development node
+//! that authors blocks with keys that are only kept by the server.
+
+// Only the node and the client are used by these tests.
+#[allow(dead_code)]
+mod common;
+
+use common::{free_port, Node};
+use node_template::{keystore_server, remote_keystore::RemoteKeystore};
+use sc_keystore::LocalKeystore;
+use sp_core::{
+	crypto::{ByteArray, KeyTypeId},
+	ecdsa, sr25519, Pair,
+};
+use sp_keyring::{Ed25519Keyring, Sr25519Keyring};
+use sp_keystore::SyncCryptoStore;
+use std::{
+	net::TcpListener,
+	os::unix::{fs::PermissionsExt, net::UnixListener},
+	process::{Child, Command, Stdio},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+// Key types to test with. A local keystore lists every key of a key type for all crypto types, so
+// keys of different crypto types are kept under different key types.
+const TEST: KeyTypeId = KeyTypeId(*b"test");
+const OTHER: KeyTypeId = KeyTypeId(*b"othr");
+
+// Serve an in-memory keystore on a free local port, and return its URI.
+fn serve_tcp() -> (Arc<LocalKeystore>, String) {
+	let keystore = Arc::new(LocalKeystore::in_memory());
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let uri = format!("http://{}", listener.local_addr().unwrap());
+	let served = keystore.clone();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			keystore_server::serve(&served, stream.unwrap()).unwrap();
+		}
+	});
+	(keystore, uri)
+}
+
+#[test]
+fn keys_are_generated_listed_and_used_remotely() {
+	let (local, uri) = serve_tcp();
+	let remote = RemoteKeystore::new(&uri).unwrap();
+
+	let alice = SyncCryptoStore::sr25519_generate_new(&remote, TEST, Some("//Alice")).unwrap();
+	assert_eq!(alice, Sr25519Keyring::Alice.public());
+	let random = SyncCryptoStore::ed25519_generate_new(&remote, OTHER, None).unwrap();
+
+	// The keys are kept by the server only.
+	assert_eq!(SyncCryptoStore::sr25519_public_keys(&remote, TEST), vec![alice]);
+	assert_eq!(SyncCryptoStore::ed25519_public_keys(&remote, OTHER), vec![random]);
+	assert_eq!(SyncCryptoStore::ed25519_public_keys(&*local, OTHER), vec![random]);
+	assert!(SyncCryptoStore::has_keys(&remote, &[(alice.to_raw_vec(), TEST)]));
+	assert!(!SyncCryptoStore::has_keys(&remote, &[(alice.to_raw_vec(), KeyTypeId(*b"none"))]));
+
+	let signature = SyncCryptoStore::sign_with(&remote, TEST, &alice.into(), b"message")
+		.unwrap()
+		.expect("Alice's key is in the keystore");
+	let signature = sr25519::Signature::from_slice(&signature).unwrap();
+	assert!(sr25519::Pair::verify(&signature, b"message", &alice));
+
+	// Signing with a key that is not there is not an error.
+	let bob = Sr25519Keyring::Bob.public();
+	assert_eq!(SyncCryptoStore::sign_with(&remote, TEST, &bob.into(), b"message").unwrap(), None);
+}
+
+#[test]
+fn keys_are_inserted_and_signed_with_over_a_unix_socket() {
+	let keystore = Arc::new(LocalKeystore::in_memory());
+	let directory = std::env::temp_dir().join(format!("keystore-{}", std::process::id()));
+	std::fs::create_dir_all(&directory).unwrap();
+	let path = directory.join("keystore.sock");
+	let _ = std::fs::remove_file(&path);
+	let listener = UnixListener::bind(&path).unwrap();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			keystore_server::serve(&keystore, stream.unwrap()).unwrap();
+		}
+	});
+	let remote = RemoteKeystore::new(&format!("unix://{}", path.display())).unwrap();
+
+	let pair = ecdsa::Pair::from_string("//Charlie", None).unwrap();
+	SyncCryptoStore::insert_unknown(&remote, TEST, "//Charlie", pair.public().as_ref()).unwrap();
+	assert_eq!(SyncCryptoStore::ecdsa_public_keys(&remote, TEST), vec![pair.public()]);
+
+	let hash = sp_core::blake2_256(b"message");
+	let signature = SyncCryptoStore::ecdsa_sign_prehashed(&remote, TEST, &pair.public(), &hash)
+		.unwrap()
+		.expect("Charlie's key is in the keystore");
+	assert!(ecdsa::Pair::verify_prehashed(&signature, &hash, &pair.public()));
+	std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn unreachable_keystore_has_no_keys() {
+	let remote = RemoteKeystore::new(&format!("http://127.0.0.1:{}", free_port())).unwrap();
+	assert!(SyncCryptoStore::sr25519_public_keys(&remote, TEST).is_empty());
+	assert!(SyncCryptoStore::sr25519_generate_new(&remote, TEST, None).is_err());
+	assert!(RemoteKeystore::new("ws://127.0.0.1:9955").is_err());
+}
+
+#[test]
+fn keystores_off_the_loopback_interface_are_refused() {
+	assert!(RemoteKeystore::new("http://192.0.2.1:9955").is_err());
+	assert!(RemoteKeystore::new("http://localhost:9955").is_ok());
+	let status = Command::new(env!("CARGO_BIN_EXE_keystore-server"))
+		.args(["--listen", "0.0.0.0:9955"])
+		.stderr(Stdio::null())
+		.status()
+		.expect("keystore-server binary is built for integration tests; qed");
+	assert!(!status.success());
+}
+
+/// A `keystore-server` process with an in-memory keystore that is killed when dropped.
+struct Server(Child);
+
+impl Drop for Server {
+	fn drop(&mut self) {
+		let _ = self.0.kill();
+		let _ = self.0.wait();
+	}
+}
+
+#[test]
+fn unix_sockets_are_only_accessible_to_their_owner() {
+	let directory = std::env::temp_dir().join(format!("keystore-server-{}", std::process::id()));
+	std::fs::create_dir_all(&directory).unwrap();
+	let path = directory.join("keystore.sock");
+	let _ = std::fs::remove_file(&path);
+	let _server = Server(
+		Command::new(env!("CARGO_BIN_EXE_keystore-server"))
+			.arg("--unix-socket")
+			.arg(&path)
+			.stderr(Stdio::null())
+			.spawn()
+			.expect("keystore-server binary is built for integration tests; qed"),
+	);
+
+	// The permissions are set right after the socket is bound.
+	let started = Instant::now();
+	loop {
+		let mode = std::fs::metadata(&path).map(|metadata| metadata.permissions().mode() & 0o777);
+		match mode {
+			Ok(0o600) => break,
+			_ if started.elapsed() > Duration::from_secs(30) =>
+				panic!("socket is not private: {:?}", mode.map(|mode| format!("{:o}", mode))),
+			_ => std::thread::sleep(Duration::from_millis(100)),
+		}
+	}
+	std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn node_authors_blocks_with_remote_keys() {
+	let address = format!("127.0.0.1:{}", free_port());
+	let _server = Server(
+		Command::new(env!("CARGO_BIN_EXE_keystore-server"))
+			.args(["--listen", &address])
+			.stderr(Stdio::null())
+			.spawn()
+			.expect("keystore-server binary is built for integration tests; qed"),
+	);
+	let uri = format!("http://{}", address);
+	let remote = RemoteKeystore::new(&uri).unwrap();
+
+	// Alice is the only authority of the development chain, so Alice's Aura and GRANDPA keys go to
+	// the server once it is up.
+	let started = Instant::now();
+	let aura = loop {
+		match SyncCryptoStore::sr25519_generate_new(&remote, KeyTypeId(*b"aura"), Some("//Alice")) {
+			Ok(public) => break public,
+			Err(e) if started.elapsed() > Duration::from_secs(30) =>
+				panic!("keystore server did not start: {}", e),
+			Err(_) => std::thread::sleep(Duration::from_millis(100)),
+		}
+	};
+	assert_eq!(aura, Sr25519Keyring::Alice.public());
+	let grandpa =
+		SyncCryptoStore::ed25519_generate_new(&remote, KeyTypeId(*b"gran"), Some("//Alice"))
+			.unwrap();
+	assert_eq!(grandpa, Ed25519Keyring::Alice.public());
+
+	// Aura and GRANDPA only use the remote keystore, so blocks are authored with the keys above.
+	let node = Node::start_with(&["--keystore-uri", &uri]);
+	let client = node.connect().await;
+	client.wait_for_block(3).await;
+}
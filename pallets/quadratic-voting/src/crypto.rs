@@ -0,0 +1,29 @@
+//! Keys of the offchain worker.
+//!
+//! The offchain worker signs its settlement transactions with an sr25519 key of type [`KEY_TYPE`]
+//! from the keystore of the node.
+
+use sp_core::sr25519::{Public as Sr25519Public, Signature as Sr25519Signature};
+use sp_runtime::{
+	app_crypto::{app_crypto, sr25519},
+	KeyTypeId, MultiSignature, MultiSigner,
+};
+
+/// Key type of the offchain worker of the quadratic voting pallet.
+pub const KEY_TYPE: KeyTypeId = KeyTypeId(*b"qvot");
+
+app_crypto!(sr25519, KEY_TYPE);
+
+/// Key of the offchain storage entry that holds the last block in which an offchain worker
+/// submitted a transaction. Shared by all instances, since they sign with the same keys.
+pub const SUBMISSION_LOCK: &[u8] = b"quadratic-voting::last-submission";
+
+/// Signs offchain worker transactions with a key of type [`KEY_TYPE`], for runtimes whose
+/// accounts are identified by a `MultiSigner`.
+pub struct OffchainAuthId;
+
+impl frame_system::offchain::AppCrypto<MultiSigner, MultiSignature> for OffchainAuthId {
+	type RuntimeAppPublic = Public;
+	type GenericPublic = Sr25519Public;
+	type GenericSignature = Sr25519Signature;
+}
@@ -0,0 +1,221 @@
+//! Ready-made [`VoteCurrency`] backends.
+//!
+//! [`NativeReserve`] charges vote costs in a reservable currency such as `pallet_balances`.
+//! [`FrozenAsset`] and [`HeldAsset`] charge them in a single asset of a `fungibles` implementation,
+//! e.g. a governance token of `pallet_assets`. `pallet_assets` supports freezes but not holds.
+//!
+//! [`CheckpointOnChange`] wraps the account store of `pallet_balances`, so that the checkpoints of
+//! snapshot proposals follow native balance changes. [`CheckpointCalls`] does the same for calls
+//! that change balances kept elsewhere, such as those of `pallet_assets`.
+
+use crate::{OnBalanceChange, VoteCurrency};
+use codec::{Decode, Encode};
+use frame_support::{
+	dispatch::{DispatchError, DispatchResult},
+	traits::{
+		fungibles::{Inspect, InspectHold, MutateHold, Transfer},
+		ExistenceRequirement, Get, ReservableCurrency, StoredMap,
+	},
+	CloneNoBound, DebugNoBound, EqNoBound, PartialEqNoBound,
+};
+use scale_info::TypeInfo;
+use sp_runtime::{
+	traits::{DispatchInfoOf, PostDispatchInfoOf, SignedExtension},
+	transaction_validity::TransactionValidityError,
+	TokenError,
+};
+use sp_std::{marker::PhantomData, prelude::*};
+
+/// Reserves vote costs of the currency `C`.
+pub struct NativeReserve<C>(PhantomData<C>);
+
+impl<AccountId, C: ReservableCurrency<AccountId>> VoteCurrency<AccountId> for NativeReserve<C> {
+	type Balance = C::Balance;
+
+	fn free_balance(who: &AccountId) -> Self::Balance {
+		C::free_balance(who)
+	}
+
+	fn hold(who: &AccountId, amount: Self::Balance) -> DispatchResult {
+		C::reserve(who, amount)
+	}
+
+	fn release(who: &AccountId, amount: Self::Balance) {
+		C::unreserve(who, amount);
+	}
+
+	fn held_balance(who: &AccountId) -> Self::Balance {
+		C::reserved_balance(who)
+	}
+
+	fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult {
+		C::transfer(source, dest, amount, ExistenceRequirement::KeepAlive)
+	}
+}
+
+/// Freezes vote costs of the asset `Id` of `F`.
+///
+/// Nothing is moved or locked by this backend itself: the asset pallet has to be told about the
+/// frozen amount, e.g. by a `pallet_assets::FrozenBalance` implementation returning
+/// [`Pallet::frozen_balance`](crate::Pallet::frozen_balance) for the asset `Id`.
+pub struct FrozenAsset<F, Id>(PhantomData<(F, Id)>);
+
+impl<AccountId, F, Id> VoteCurrency<AccountId> for FrozenAsset<F, Id>
+where
+	F: Inspect<AccountId> + Transfer<AccountId>,
+	Id: Get<F::AssetId>,
+{
+	type Balance = F::Balance;
+
+	fn free_balance(who: &AccountId) -> Self::Balance {
+		F::reducible_balance(Id::get(), who, false)
+	}
+
+	fn hold(who: &AccountId, amount: Self::Balance) -> DispatchResult {
+		// The asset pallet already excludes what is frozen from the reducible balance.
+		if F::reducible_balance(Id::get(), who, false) < amount {
+			return Err(TokenError::NoFunds.into())
+		}
+		Ok(())
+	}
+
+	fn release(_who: &AccountId, _amount: Self::Balance) {}
+
+	fn held_balance(who: &AccountId) -> Self::Balance {
+		F::balance(Id::get(), who)
+	}
+
+	fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult {
+		F::transfer(Id::get(), source, dest, amount, true).map(|_| ())
+	}
+}
+
+/// Puts vote costs of the asset `Id` of `F` on hold.
+pub struct HeldAsset<F, Id>(PhantomData<(F, Id)>);
+
+impl<AccountId, F, Id> VoteCurrency<AccountId> for HeldAsset<F, Id>
+where
+	F: MutateHold<AccountId> + Transfer<AccountId>,
+	Id: Get<F::AssetId>,
+{
+	type Balance = F::Balance;
+
+	fn free_balance(who: &AccountId) -> Self::Balance {
+		F::reducible_balance(Id::get(), who, false)
+	}
+
+	fn hold(who: &AccountId, amount: Self::Balance) -> DispatchResult {
+		F::hold(Id::get(), who, amount)
+	}
+
+	fn release(who: &AccountId, amount: Self::Balance) {
+		let _ = F::release(Id::get(), who, amount, true);
+	}
+
+	fn held_balance(who: &AccountId) -> Self::Balance {
+		F::balance_on_hold(Id::get(), who)
+	}
+
+	fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult {
+		F::transfer(Id::get(), source, dest, amount, true).map(|_| ())
+	}
+}
+
+/// Account store `S` that tells `H` about every change of an account.
+///
+/// Used as the `AccountStore` of `pallet_balances`, e.g.
+/// `CheckpointOnChange<System, QuadraticVoting>`, it makes the pallet record a checkpoint whenever
+/// a balance changes.
+pub struct CheckpointOnChange<S, H>(PhantomData<(S, H)>);
+
+impl<K, T, S, H> StoredMap<K, T> for CheckpointOnChange<S, H>
+where
+	T: Default,
+	S: StoredMap<K, T>,
+	H: OnBalanceChange<K>,
+{
+	fn get(k: &K) -> T {
+		S::get(k)
+	}
+
+	fn try_mutate_exists<R, E: From<DispatchError>>(
+		k: &K,
+		f: impl FnOnce(&mut Option<T>) -> Result<R, E>,
+	) -> Result<R, E> {
+		H::before_change(k);
+		let result = S::try_mutate_exists(k, f)?;
+		H::after_change(k);
+		Ok(result)
+	}
+}
+
+/// Accounts whose balance a call may change, as told to [`CheckpointCalls`].
+pub trait ChangedAccounts<AccountId, Call> {
+	/// Accounts whose balance `call` may change when dispatched by `who`.
+	fn changed_accounts(who: &AccountId, call: &Call) -> Vec<AccountId>;
+}
+
+/// Signed extension that tells `H` about the accounts `A` finds in a call, before and after it is
+/// dispatched.
+///
+/// `pallet_assets` keeps its balances in its own storage, so the checkpoints of an instance charging
+/// an asset follow transfers of the asset this way, e.g. with
+/// `CheckpointCalls<Runtime, EngineeringGuild, GovernanceTransfers>` in the `SignedExtra` of the
+/// runtime. Calls that are not submitted as signed transactions, such as enacted proposals, are
+/// not seen.
+#[derive(Encode, Decode, CloneNoBound, DebugNoBound, EqNoBound, PartialEqNoBound, TypeInfo)]
+#[scale_info(skip_type_params(T, H, A))]
+pub struct CheckpointCalls<T, H, A>(PhantomData<(T, H, A)>);
+
+impl<T, H, A> CheckpointCalls<T, H, A> {
+	/// Create the extension, which carries no data.
+	pub fn new() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<T, H, A> Default for CheckpointCalls<T, H, A> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<T, H, A> SignedExtension for CheckpointCalls<T, H, A>
+where
+	T: frame_system::Config + Send + Sync,
+	H: OnBalanceChange<T::AccountId> + Send + Sync + 'static,
+	A: ChangedAccounts<T::AccountId, T::Call> + Send + Sync + 'static,
+{
+	const IDENTIFIER: &'static str = "CheckpointCalls";
+	type AccountId = T::AccountId;
+	type Call = T::Call;
+	type AdditionalSigned = ();
+	type Pre = Vec<T::AccountId>;
+
+	fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+		Ok(())
+	}
+
+	fn pre_dispatch(
+		self,
+		who: &Self::AccountId,
+		call: &Self::Call,
+		_info: &DispatchInfoOf<Self::Call>,
+		_len: usize,
+	) -> Result<Self::Pre, TransactionValidityError> {
+		let accounts = A::changed_accounts(who, call);
+		accounts.iter().for_each(H::before_change);
+		Ok(accounts)
+	}
+
+	fn post_dispatch(
+		pre: Option<Self::Pre>,
+		_info: &DispatchInfoOf<Self::Call>,
+		_post_info: &PostDispatchInfoOf<Self::Call>,
+		_len: usize,
+		_result: &DispatchResult,
+	) -> Result<(), TransactionValidityError> {
+		pre.unwrap_or_default().iter().for_each(H::after_change);
+		Ok(())
+	}
+}
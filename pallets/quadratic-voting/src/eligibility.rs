@@ -0,0 +1,104 @@
+//! Ready-made [`VoterEligibility`] adapters.
+//!
+//! Each adapter answers the eligibility question from a single source. They can be combined with
+//! [`And`] and [`Or`], and limited with [`Capped`], to express the policy of a voting body, e.g.
+//! `And<IsMember<Council>, MinBalance<Balances, MinStake>>`.
+
+use crate::{Refusal, VoterEligibility};
+use frame_support::traits::{Currency, Get, SortedMembers};
+use sp_std::marker::PhantomData;
+
+/// Eligible if the account has an identity in `pallet_identity`.
+pub struct HasIdentity<T>(PhantomData<T>);
+
+impl<T: pallet_identity::Config> VoterEligibility<T::AccountId> for HasIdentity<T> {
+	fn eligibility(who: &T::AccountId) -> Result<Option<u128>, Refusal> {
+		if pallet_identity::Pallet::<T>::has_identity(who, 0) {
+			Ok(None)
+		} else {
+			Err(Refusal::MissingIdentity)
+		}
+	}
+}
+
+/// Eligible if the account is in the member set `M`, such as an instance of `pallet_membership`.
+pub struct IsMember<M>(PhantomData<M>);
+
+impl<AccountId: Ord, M: SortedMembers<AccountId>> VoterEligibility<AccountId> for IsMember<M> {
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal> {
+		if M::contains(who) {
+			Ok(None)
+		} else {
+			Err(Refusal::NotMember)
+		}
+	}
+}
+
+/// Eligible if the free balance of the account in `C` is at least `Min`.
+pub struct MinBalance<C, Min>(PhantomData<(C, Min)>);
+
+impl<AccountId, C, Min> VoterEligibility<AccountId> for MinBalance<C, Min>
+where
+	C: Currency<AccountId>,
+	Min: Get<C::Balance>,
+{
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal> {
+		if C::free_balance(who) >= Min::get() {
+			Ok(None)
+		} else {
+			Err(Refusal::InsufficientBalance)
+		}
+	}
+}
+
+/// Eligible if `E` deems the account eligible, with at most `Max` votes on a single proposal.
+pub struct Capped<E, Max>(PhantomData<(E, Max)>);
+
+impl<AccountId, E, Max> VoterEligibility<AccountId> for Capped<E, Max>
+where
+	E: VoterEligibility<AccountId>,
+	Max: Get<u128>,
+{
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal> {
+		let cap = E::eligibility(who)?;
+		Ok(Some(cap.map_or(Max::get(), |cap| cap.min(Max::get()))))
+	}
+}
+
+/// Eligible if both `A` and `B` deem the account eligible. The lower of their caps applies.
+pub struct And<A, B>(PhantomData<(A, B)>);
+
+impl<AccountId, A, B> VoterEligibility<AccountId> for And<A, B>
+where
+	A: VoterEligibility<AccountId>,
+	B: VoterEligibility<AccountId>,
+{
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal> {
+		let a = A::eligibility(who)?;
+		let b = B::eligibility(who)?;
+		Ok(match (a, b) {
+			(Some(a), Some(b)) => Some(a.min(b)),
+			(cap, None) | (None, cap) => cap,
+		})
+	}
+}
+
+/// Eligible if either `A` or `B` deems the account eligible. The higher of their caps applies.
+///
+/// If neither does, the refusal of `A` is reported.
+pub struct Or<A, B>(PhantomData<(A, B)>);
+
+impl<AccountId, A, B> VoterEligibility<AccountId> for Or<A, B>
+where
+	A: VoterEligibility<AccountId>,
+	B: VoterEligibility<AccountId>,
+{
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal> {
+		match (A::eligibility(who), B::eligibility(who)) {
+			(Ok(Some(a)), Ok(Some(b))) => Ok(Some(a.max(b))),
+			(Ok(_), Ok(_)) => Ok(None),
+			(Ok(cap), Err(_)) | (Err(_), Ok(cap)) => Ok(cap),
+			(Err(refusal), Err(_)) => Err(refusal),
+		}
+	}
+}
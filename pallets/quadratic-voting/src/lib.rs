@@ -33,21 +33,62 @@
 //! - `vote_aye` - Vote for proposal at proposal index with one or more votes.
 //! - `vote_nay` - Vote against proposal at proposal index with one or more votes.
 //! - `unreserve` - Unreserve tokens after voting period is ended.
+//! - `cancel` - Withdraw a proposal that nobody has voted on yet.
+//! - `finalize` - Close an ended proposal and record its outcome.
+//! - `refund_votes` - Unreserve a bounded batch of votes of a finalized proposal on behalf of voters.
+//! - `enact` - Dispatch the call of a passed proposal. Scheduled by `finalize`.
+//! - `cancel_enactment` - Cancel the scheduled enactment of a passed proposal.
+//!
+//! Budget-balanced protocol:
+//! - `propose_with_pot` - Create a proposal whose vote costs are paid into a pot instead of being
+//!   reserved.
+//! - `claim_share` - Enroll for, or collect, a share of the pot once the proposal has ended.
+//! - `payout_shares` - Pay out a bounded batch of outstanding shares on behalf of recipients.
+//!
+//! ### Redistribution
+//!
+//! In the Posner–Weyl design of quadratic voting the collected payments are returned evenly to the
+//! participants, which makes the mechanism budget-balanced. Proposals created with
+//! `propose_with_pot` take the vote cost from the voter and keep it in the pallet account until the
+//! proposal ends. The pot is then split equally among:
+//! - `Redistribution::Voters` - every account that voted on the proposal. Shares can be claimed as
+//!   soon as the voting period is over.
+//! - `Redistribution::Identities` - every eligible account that enrolls through
+//!   `claim_share` during the `ClaimPeriod` that follows the end of voting. Shares can be claimed
+//!   once the claim period is over.
 
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use codec::{Decode, Encode, MaxEncodedLen};
 use scale_info::TypeInfo;
-use frame_support::{ RuntimeDebug, BoundedVec, traits::{Currency,ConstU32 }};
+use frame_support::{
+	dispatch::DispatchResult, RuntimeDebug, BoundedVec, traits::{tokens, ConstU32 },
+};
+use sp_runtime::traits::{
+	AccountIdConversion, IdentifyAccount, IntegerSquareRoot, One, SaturatedConversion, Saturating,
+	Verify, Zero,
+};
+use sp_std::prelude::*;
+#[cfg(any(feature = "try-runtime", test))]
+use sp_std::collections::btree_map::BTreeMap;
 pub use pallet::*;
 
+pub mod crypto;
+pub mod currency;
+pub mod eligibility;
+pub mod migrations;
+pub mod runtime_api;
+
 #[cfg(test)]
 mod mock;
 
 #[cfg(test)]
 mod tests;
 
+#[cfg(test)]
+mod proptests;
+
 /// An index of a proposal. Just a `u32`.
 pub type ProposalIndex = u32;
 
@@ -55,7 +96,8 @@ pub type ProposalIndex = u32;
 type AccountIdOf<T> = <T as frame_system::Config>::AccountId;
 
 /// A type alias for the balance type from this pallet's point of view.
-pub type BalanceOf<T> = <<T as Config>::Currency as Currency<AccountIdOf<T>>>::Balance;
+pub type BalanceOf<T, I = ()> =
+	<<T as Config<I>>::Currency as VoteCurrency<AccountIdOf<T>>>::Balance;
 
 #[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, Default, MaxEncodedLen, TypeInfo)]
@@ -72,9 +114,265 @@ pub struct Proposal<AccountId, BlockNumber> {
 	pub end: BlockNumber,
 }
 
-/// A trait to allow the Quadratic pallet to verify that account setup identity.
-pub trait IdentityVerifier<AccountId> {
-	fn has_identity(who: &AccountId, fields: u64) -> bool;
+/// Side of a vote.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub enum VoteSide {
+	/// Vote supports the proposal.
+	Aye,
+	/// Vote is against the proposal.
+	Nay,
+}
+
+/// A vote an account cast on a proposal.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct VoteRecord<Balance, BlockNumber> {
+	/// Side of the vote. `None` for votes migrated from storage that did not record the side, when
+	/// it could not be inferred from the proposal tallies.
+	pub side: Option<VoteSide>,
+	/// Number of votes cast.
+	pub votes: u128,
+	/// Amount the votes cost.
+	pub cost: Balance,
+	/// Block the vote was cast at.
+	pub at_block: BlockNumber,
+	/// Whether the reserved cost of the vote has been returned to the voter. The record is kept
+	/// so that the proposal tallies can still be checked against it.
+	pub released: bool,
+}
+
+/// A vote that `voter` authorizes someone else to submit on its behalf.
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct VotePermit<AccountId, BlockNumber> {
+	/// Account that casts the vote and pays its cost.
+	pub voter: AccountId,
+	/// Proposal to vote on.
+	pub proposal_index: ProposalIndex,
+	/// Side of the vote.
+	pub side: VoteSide,
+	/// Number of votes to cast.
+	pub votes: u128,
+	/// Permit nonce of `voter`, so that every permit can only be used once.
+	pub nonce: u32,
+	/// Last block the permit can be used at.
+	pub deadline: BlockNumber,
+}
+
+/// Set of accounts among which the pot of a budget-balanced proposal is split.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub enum Redistribution {
+	/// Split among all accounts that voted on the proposal.
+	Voters,
+	/// Split among all eligible accounts that enroll during the claim period.
+	Identities,
+}
+
+/// Vote costs collected by a budget-balanced proposal that are still to be paid out.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct Pot<Balance> {
+	/// Who the collected costs are returned to.
+	pub target: Redistribution,
+	/// Amount held in the pallet account that has not been paid out yet.
+	pub remaining: Balance,
+	/// Number of recipients that have not received their share yet.
+	pub recipients: u32,
+}
+
+/// Status of a proposal at a given block.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub enum ProposalStatus {
+	/// Voting period is ongoing.
+	Voting,
+	/// Voting period is over and there are more votes for than against the proposal.
+	Passed,
+	/// Voting period is over and the proposal did not get more votes for than against it.
+	Rejected,
+}
+
+/// A proposal together with its index and computed status.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct ProposalInfo<AccountId, BlockNumber> {
+	/// Index of the proposal.
+	pub index: ProposalIndex,
+	/// The proposal as it is stored.
+	pub proposal: Proposal<AccountId, BlockNumber>,
+	/// Status of the proposal at the block that was queried.
+	pub status: ProposalStatus,
+}
+
+/// The vote an account cast on a proposal, and what happened to its cost.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct VoteInfo<Balance, BlockNumber> {
+	/// The vote as it is stored.
+	pub vote: VoteRecord<Balance, BlockNumber>,
+	/// Whether the cost was paid into the pot of the proposal instead of being reserved.
+	pub paid_into_pot: bool,
+}
+
+/// Aggregate participation of an account in voting.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, Default, MaxEncodedLen, TypeInfo)]
+pub struct VoterStats<Balance> {
+	/// Number of votes cast on all proposals.
+	pub votes_cast: u128,
+	/// Amount spent on votes on all proposals, whether it was reserved or paid into a pot.
+	pub credits_spent: Balance,
+	/// Amount that is still reserved for votes and has not been unreserved yet.
+	pub outstanding_reserves: Balance,
+}
+
+/// Participation history of an account together with its aggregate statistics.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct AccountHistory<Balance, BlockNumber> {
+	/// Aggregate statistics of the account.
+	pub stats: VoterStats<Balance>,
+	/// Votes of the account whose funds have not been released yet, by proposal index.
+	pub votes: Vec<(ProposalIndex, VoteInfo<Balance, BlockNumber>)>,
+}
+
+/// Reason why an account cannot vote on a proposal.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub enum Ineligibility {
+	/// Proposal does not exist.
+	UnexistingProposal,
+	/// Voting period is over.
+	VotingEnded,
+	/// Account does not have an identity.
+	MissingIdentity,
+	/// Account has already voted on the proposal.
+	AlreadyVoted,
+	/// Account is not a member of the voting body.
+	NotMember,
+	/// Account does not hold the minimum balance required to vote.
+	InsufficientBalance,
+	/// Account was not eligible at the snapshot block of the proposal, or its checkpoint of that
+	/// block was dropped to make room for newer ones.
+	NotEligibleAtSnapshot,
+}
+
+/// Reason why `VoterEligibility` refuses an account.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub enum Refusal {
+	/// Account does not have an identity.
+	MissingIdentity,
+	/// Account is not a member of the voting body.
+	NotMember,
+	/// Account does not hold the minimum balance required to vote.
+	InsufficientBalance,
+}
+
+impl From<Refusal> for Ineligibility {
+	fn from(refusal: Refusal) -> Self {
+		match refusal {
+			Refusal::MissingIdentity => Ineligibility::MissingIdentity,
+			Refusal::NotMember => Ineligibility::NotMember,
+			Refusal::InsufficientBalance => Ineligibility::InsufficientBalance,
+		}
+	}
+}
+
+/// Eligibility and free balance of an account, as recorded at some block.
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct Checkpoint<Balance> {
+	/// Outcome of `VoterEligibility` for the account.
+	pub eligibility: Result<Option<u128>, Refusal>,
+	/// Free balance of the account.
+	pub balance: Balance,
+}
+
+/// A trait to allow the Quadratic pallet to decide which accounts may propose and vote.
+///
+/// See the [`eligibility`] module for ready-made implementations.
+pub trait VoterEligibility<AccountId> {
+	/// Check whether `who` may propose and vote.
+	///
+	/// Returns the maximum number of votes `who` may cast on a single proposal, or `None` if it
+	/// is not capped.
+	fn eligibility(who: &AccountId) -> Result<Option<u128>, Refusal>;
+}
+
+/// Every account is eligible, without a cap.
+impl<AccountId> VoterEligibility<AccountId> for () {
+	fn eligibility(_who: &AccountId) -> Result<Option<u128>, Refusal> {
+		Ok(None)
+	}
+}
+
+/// A trait to allow the Quadratic pallet to charge vote costs in any currency or asset.
+///
+/// Costs of votes on proposals without a pot are put on hold until the voter unreserves them. The
+/// pallet also keeps the total on hold of every account in `VoterStatistics`, so backends that
+/// freeze funds through another pallet can report it from [`Pallet::frozen_balance`].
+///
+/// See the [`currency`] module for ready-made implementations.
+pub trait VoteCurrency<AccountId> {
+	/// Balance type that vote costs are charged in.
+	type Balance: tokens::Balance;
+
+	/// Amount `who` can spend on votes.
+	fn free_balance(who: &AccountId) -> Self::Balance;
+
+	/// Put `amount` of `who` on hold, failing if it can't be spent.
+	fn hold(who: &AccountId, amount: Self::Balance) -> DispatchResult;
+
+	/// Release `amount` of `who` that was put on hold.
+	fn release(who: &AccountId, amount: Self::Balance);
+
+	/// Amount of `who` that is on hold, including what other pallets put on hold. Backends that
+	/// freeze vote costs return the whole balance, which frozen amounts are part of.
+	fn held_balance(who: &AccountId) -> Self::Balance;
+
+	/// Transfer `amount` from `source` to `dest`, keeping `source` alive.
+	///
+	/// Pots are held by the [`Pallet::account_id`] of the instance, which therefore has to exist
+	/// before the first vote is paid into it, e.g. by being endowed with the minimum balance at
+	/// genesis.
+	fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult;
+}
+
+/// A trait to tell the Quadratic pallet about changes of account balances, so that its
+/// checkpoints follow them.
+///
+/// Implemented by the pallet and by pairs of implementations, so several instances can be told.
+/// See [`currency::CheckpointOnChange`] for an account store that calls it.
+pub trait OnBalanceChange<AccountId> {
+	/// The balance of `who` is about to change.
+	fn before_change(who: &AccountId);
+
+	/// The balance of `who` has changed.
+	fn after_change(who: &AccountId);
+}
+
+impl<AccountId> OnBalanceChange<AccountId> for () {
+	fn before_change(_who: &AccountId) {}
+
+	fn after_change(_who: &AccountId) {}
+}
+
+impl<AccountId, A, B> OnBalanceChange<AccountId> for (A, B)
+where
+	A: OnBalanceChange<AccountId>,
+	B: OnBalanceChange<AccountId>,
+{
+	fn before_change(who: &AccountId) {
+		A::before_change(who);
+		B::before_change(who);
+	}
+
+	fn after_change(who: &AccountId) {
+		A::after_change(who);
+		B::after_change(who);
+	}
 }
 
 #[frame_support::pallet]
@@ -82,56 +380,339 @@ pub mod pallet {
 	use super::*;
 	use frame_support::pallet_prelude::*;
 	use frame_system::pallet_prelude::*;
-	use frame_support::traits::ReservableCurrency;
+	use frame_support::{
+		dispatch::{Dispatchable, GetDispatchInfo, PostDispatchInfo},
+		storage::{with_transaction, TransactionOutcome},
+		traits::{
+			schedule::{self, DispatchTime, Named as ScheduleNamed},
+			PreimageProvider,
+		},
+		PalletId,
+	};
+	use frame_system::offchain::{
+		AppCrypto, CreateSignedTransaction, SendSignedTransaction, Signer,
+	};
+	use sp_runtime::{
+		offchain::storage::{StorageRetrievalError, StorageValueRef},
+		traits::BadOrigin,
+	};
 
 	#[pallet::config]
-	pub trait Config: frame_system::Config {
+	pub trait Config<I: 'static = ()>:
+		CreateSignedTransaction<Call<Self, I>> + frame_system::Config
+	{
 		/// Because this pallet emits events, it depends on the runtime's definition of an event.
-		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
-		/// The currency trait.
-		type Currency: ReservableCurrency<Self::AccountId>;
-		/// Identity checking trait.
-		type IdentityVerifier: IdentityVerifier<Self::AccountId>;
+		type Event: From<Event<Self, I>> + IsType<<Self as frame_system::Config>::Event>;
+		/// The currency or asset that vote costs are charged in.
+		type Currency: VoteCurrency<Self::AccountId>;
+		/// Decides which accounts may propose and vote, and how many votes they may cast.
+		type VoterEligibility: VoterEligibility<Self::AccountId>;
 		/// Number of blocks that voting is open since the creation of proposal.
 		type VotingPeriod: Get<Self::BlockNumber>;
+		/// Identifier of the account that holds the pots of budget-balanced proposals.
+		#[pallet::constant]
+		type PalletId: Get<PalletId>;
+		/// Number of blocks after the end of voting during which eligible accounts can enroll for a
+		/// share of a pot.
+		#[pallet::constant]
+		type ClaimPeriod: Get<Self::BlockNumber>;
+		/// Maximum number of shares paid out by a single `payout_shares` call.
+		#[pallet::constant]
+		type MaxPayoutsPerBatch: Get<u32>;
+		/// Maximum number of finalized proposals whose pots can await their payout at once.
+		#[pallet::constant]
+		type MaxDuePayouts: Get<u32>;
+		/// Maximum number of proposals that can be open or awaiting finalization at once.
+		#[pallet::constant]
+		type MaxActiveProposals: Get<u32>;
+		/// Maximum number of proposals an account can have created, not counting cancelled ones.
+		#[pallet::constant]
+		type MaxProposalsPerProposer: Get<u32>;
+		/// Maximum number of proposals an account can have unreleased votes on at once.
+		#[pallet::constant]
+		type MaxVotedProposals: Get<u32>;
+		/// Maximum number of checkpoints kept per account. The oldest one is dropped to make room.
+		#[pallet::constant]
+		type MaxCheckpoints: Get<u32>;
+		/// Maximum number of fee-less calls an eligible account can make per proposal.
+		#[pallet::constant]
+		type MaxFreeCalls: Get<u32>;
+		/// Transaction pool priority of calls that are expected to be fee-less.
+		#[pallet::constant]
+		type FreeCallPriority: Get<TransactionPriority>;
+		/// Signature of vote permits.
+		type PermitSignature: Verify<Signer = Self::PermitSigner> + Parameter;
+		/// Signer of vote permits, which identifies the voting account.
+		type PermitSigner: IdentifyAccount<AccountId = Self::AccountId>;
+		/// Key that the offchain worker signs settlement transactions with.
+		type AuthorityId: AppCrypto<Self::Public, Self::Signature>;
+		/// Call that a passed proposal enacts, i.e. the runtime's outer call.
+		type Proposal: Parameter
+			+ Dispatchable<Origin = Self::Origin, PostInfo = PostDispatchInfo>
+			+ GetDispatchInfo
+			+ From<Call<Self, I>>;
+		/// Caller origin of the runtime, which enactments are scheduled with.
+		type PalletsOrigin: From<frame_system::RawOrigin<Self::AccountId>>;
+		/// Origin that passed proposals are enacted with, e.g. root for a chain-wide voting body,
+		/// or the account of the instance for a body with narrower powers.
+		type EnactmentOrigin: Get<frame_system::RawOrigin<Self::AccountId>>;
+		/// Priority of enactments in the scheduler.
+		#[pallet::constant]
+		type EnactmentPriority: Get<schedule::Priority>;
+		/// Minimum number of votes, aye and nay together, that a passed proposal needs to be
+		/// enacted.
+		#[pallet::constant]
+		type MinEnactmentTurnout: Get<u128>;
+		/// Schedules the enactment of passed proposals.
+		type Scheduler: ScheduleNamed<Self::BlockNumber, Self::Proposal, Self::PalletsOrigin>;
+		/// Stores the calls that proposal hashes refer to.
+		type Preimages: PreimageProvider<Self::Hash>;
+		/// Number of blocks between the finalization of a passed proposal and its enactment. Must
+		/// be at least one.
+		#[pallet::constant]
+		type EnactmentDelay: Get<Self::BlockNumber>;
+		/// Maximum weight of a call that a proposal enacts.
+		#[pallet::constant]
+		type MaxEnactmentWeight: Get<Weight>;
+		/// Origin that can cancel a scheduled enactment.
+		type CancelOrigin: EnsureOrigin<Self::Origin>;
 	}
 
+	/// The current storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(3);
+
 	#[pallet::pallet]
 	#[pallet::generate_store(pub(super) trait Store)]
-	pub struct Pallet<T>(_);
+	#[pallet::storage_version(STORAGE_VERSION)]
+	pub struct Pallet<T, I = ()>(PhantomData<(T, I)>);
 
 	/// Number of proposals that have been made.
 	#[pallet::storage]
 	#[pallet::getter(fn proposal_count)]
-	pub(super) type ProposalCount<T: Config> = StorageValue<_, ProposalIndex, OptionQuery>;
+	pub(super) type ProposalCount<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, ProposalIndex, OptionQuery>;
 
 	/// Map of all proposals that have been made.
 	#[pallet::storage]
 	#[pallet::getter(fn proposals)]
-	pub(super) type Proposals<T: Config> = 
+	pub(super) type Proposals<T: Config<I>, I: 'static = ()> = 
 		StorageMap<_, Blake2_128Concat, u32, Proposal<T::AccountId, T::BlockNumber>, OptionQuery>;
 
-	/// Map of all tokens reservations that are used for voting.
+	/// Votes cast on each proposal, by proposal and voter.
+	///
+	/// The cost of a vote is reserved from the voter until it is unreserved, unless the proposal
+	/// has a pot, in which case it is paid into the pot. Records are kept once the funds are
+	/// released, and marked as such.
 	#[pallet::storage]
-	#[pallet::getter(fn resreved_tokens)]
-	pub(super) type ReservedTokens<T: Config> = 
-		StorageMap<_, Blake2_128Concat, (u32, T::AccountId), BalanceOf<T>, OptionQuery>;
-	
+	#[pallet::getter(fn votes)]
+	pub(super) type Votes<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		u32,
+		Blake2_128Concat,
+		T::AccountId,
+		VoteRecord<BalanceOf<T, I>, T::BlockNumber>,
+		OptionQuery,
+	>;
+
+	/// Pots of budget-balanced proposals.
+	#[pallet::storage]
+	#[pallet::getter(fn pots)]
+	pub(super) type Pots<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, u32, Pot<BalanceOf<T, I>>, OptionQuery>;
+
+	/// Accounts that are still owed a share of the pot of a proposal.
+	#[pallet::storage]
+	pub(super) type PotRecipients<T: Config<I>, I: 'static = ()> =
+		StorageDoubleMap<_, Blake2_128Concat, u32, Blake2_128Concat, T::AccountId, (), OptionQuery>;
+
+	/// Shares of a pot that could not be paid out in a batch, kept for their recipient to claim.
+	#[pallet::storage]
+	#[pallet::getter(fn unpaid_shares)]
+	pub(super) type UnpaidShares<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		u32,
+		Blake2_128Concat,
+		T::AccountId,
+		BalanceOf<T, I>,
+		OptionQuery,
+	>;
+
+	/// Proposals that have not been finalized yet, ordered by the block their voting ends at.
+	#[pallet::storage]
+	#[pallet::getter(fn active_proposal_index)]
+	pub(super) type ActiveProposals<T: Config<I>, I: 'static = ()> = StorageValue<
+		_,
+		BoundedVec<(T::BlockNumber, ProposalIndex), T::MaxActiveProposals>,
+		ValueQuery,
+	>;
+
+	/// Proposals by the account that created them, at most `MaxProposalsPerProposer` per account.
+	#[pallet::storage]
+	pub(super) type ProposalsByProposer<T: Config<I>, I: 'static = ()> =
+		StorageDoubleMap<_, Blake2_128Concat, T::AccountId, Blake2_128Concat, u32, (), OptionQuery>;
+
+	/// Proposals each account has voted on, in ascending order, until the funds of the vote are
+	/// released.
+	#[pallet::storage]
+	#[pallet::getter(fn voted_proposals)]
+	pub(super) type VotedProposals<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		BoundedVec<ProposalIndex, T::MaxVotedProposals>,
+		ValueQuery,
+	>;
+
+	/// Aggregate participation of each account that has voted.
+	#[pallet::storage]
+	#[pallet::getter(fn voter_stats)]
+	pub(super) type VoterStatistics<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, VoterStats<BalanceOf<T, I>>, ValueQuery>;
+
+	/// Snapshot blocks of proposals whose eligibility and vote caps are taken from checkpoints.
+	#[pallet::storage]
+	#[pallet::getter(fn snapshots)]
+	pub(super) type Snapshots<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, u32, T::BlockNumber, OptionQuery>;
+
+	/// First and last block in which balance changes are recorded as checkpoints: from the
+	/// creation of a snapshot proposal while none was open, until the end of the last one.
+	#[pallet::storage]
+	#[pallet::getter(fn recording_period)]
+	pub(super) type RecordingPeriod<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, (T::BlockNumber, T::BlockNumber), OptionQuery>;
+
+	/// Checkpoints of each account, in ascending order of the block they were recorded at.
+	#[pallet::storage]
+	#[pallet::getter(fn checkpoints)]
+	pub(super) type Checkpoints<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Blake2_128Concat,
+		T::AccountId,
+		BoundedVec<(T::BlockNumber, Checkpoint<BalanceOf<T, I>>), T::MaxCheckpoints>,
+		ValueQuery,
+	>;
+
+	/// Finalized proposals whose pots have shares left to pay out, ordered by the block after which
+	/// they are payable.
+	#[pallet::storage]
+	#[pallet::getter(fn due_payouts)]
+	pub(super) type DuePayouts<T: Config<I>, I: 'static = ()> = StorageValue<
+		_,
+		BoundedVec<(T::BlockNumber, ProposalIndex), T::MaxDuePayouts>,
+		ValueQuery,
+	>;
+
+	/// Finalized proposals without a pot that still have reserved votes.
+	#[pallet::storage]
+	pub(super) type PendingRefunds<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, u32, (), OptionQuery>;
+
+	/// Unfinalized proposals that did not fit into `ActiveProposals` when it was introduced by
+	/// [`migrations::v1::MigrateToV1`]. They are finalized like active ones.
+	#[pallet::storage]
+	#[pallet::getter(fn overflow_proposals)]
+	pub(super) type OverflowProposals<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, u32, (), OptionQuery>;
+
+	/// Nonce that the next vote permit of each account has to carry.
+	#[pallet::storage]
+	#[pallet::getter(fn permit_nonce)]
+	pub(super) type PermitNonces<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+	/// Number of fee-less calls each account made on a proposal, until its vote is settled.
+	#[pallet::storage]
+	#[pallet::getter(fn free_calls)]
+	pub(super) type FreeCalls<T: Config<I>, I: 'static = ()> = StorageDoubleMap<
+		_,
+		Blake2_128Concat,
+		ProposalIndex,
+		Blake2_128Concat,
+		T::AccountId,
+		u32,
+		ValueQuery,
+	>;
+
+	/// Passed proposals whose call is scheduled for enactment, with the block it is enacted at.
+	#[pallet::storage]
+	#[pallet::getter(fn enactments)]
+	pub(super) type Enactments<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Blake2_128Concat, u32, T::BlockNumber, OptionQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config<I>, I: 'static = ()> {
+		/// Proposals that are open for voting at genesis, by proposer and hash. Proposals with a
+		/// redistribution target pay their vote costs into a pot.
+		pub proposals: Vec<(T::AccountId, BoundedVec<u8, ConstU32<32>>, Option<Redistribution>)>,
+		/// Number of blocks the genesis proposals are open for. Defaults to `VotingPeriod`.
+		pub voting_period: Option<T::BlockNumber>,
+		pub phantom: PhantomData<I>,
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config<I>, I: 'static> Default for GenesisConfig<T, I> {
+		fn default() -> Self {
+			Self { proposals: Vec::new(), voting_period: None, phantom: Default::default() }
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config<I>, I: 'static> GenesisBuild<T, I> for GenesisConfig<T, I> {
+		fn build(&self) {
+			let voting_period = self.voting_period.unwrap_or_else(T::VotingPeriod::get);
+			for (proposer, hash, target) in self.proposals.iter() {
+				// Eligibility may depend on state that is not set up yet, so it is not checked.
+				let proposal_index =
+					Pallet::<T, I>::insert_proposal(proposer.clone(), hash.clone(), voting_period)
+						.expect("genesis proposals must not exceed the proposal bounds");
+				if let Some(target) = target {
+					let pot = Pot { target: *target, remaining: Zero::zero(), recipients: 0 };
+					Pots::<T, I>::insert(proposal_index, pot);
+				}
+			}
+		}
+	}
 
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
-	pub enum Event<T: Config> {
+	pub enum Event<T: Config<I>, I: 'static = ()> {
 		/// New proposal has been created with determined index proposal.
 		Proposed(u32),
-		/// User supported some proposal with his votes.
-		VotedAye(u32),
-		/// User voted against some proposal with his votes.
-		VotedNay(u32),
+		/// User supported some proposal with his votes. \[proposal_index, voter, votes\]
+		VotedAye(u32, T::AccountId, u128),
+		/// User voted against some proposal with his votes. \[proposal_index, voter, votes\]
+		VotedNay(u32, T::AccountId, u128),
+		/// Account enrolled for a share of the pot of a proposal.
+		ShareEnrolled(u32, T::AccountId),
+		/// Account received its share of the pot of a proposal.
+		SharePaid(u32, T::AccountId, BalanceOf<T, I>),
+		/// Proposal has been withdrawn by its proposer.
+		Cancelled(u32),
+		/// Proposal has been closed with its final status.
+		Finalized(u32, ProposalStatus),
+		/// Eligibility and balance of an account have been recorded.
+		Checkpointed(T::AccountId),
+		/// Reserved cost of a vote has been returned to the voter by someone else.
+		Refunded(u32, T::AccountId, BalanceOf<T, I>),
+		/// Call of a passed proposal has been scheduled for enactment. \[proposal_index, when\]
+		Scheduled(u32, T::BlockNumber),
+		/// Call of a proposal has been dispatched. \[proposal_index, result\]
+		Enacted(u32, DispatchResult),
+		/// Call of a passed proposal could not be scheduled or dispatched. \[proposal_index, error\]
+		EnactmentFailed(u32, DispatchError),
+		/// Scheduled enactment of a proposal has been cancelled.
+		EnactmentCancelled(u32),
+		/// Share of the pot of a proposal could not be paid out and is kept for the account to
+		/// claim. \[proposal_index, recipient, share, error\]
+		ShareUnpaid(u32, T::AccountId, BalanceOf<T, I>, DispatchError),
 	}
 
 	// Errors inform users that something went wrong.
 	#[pallet::error]
-	pub enum Error<T> {
+	pub enum Error<T, I = ()> {
 		/// Proposal at provided index does not exist.
 		UnexistingProposal,
 		/// Voting period is over.
@@ -144,155 +725,1322 @@ pub mod pallet {
 		MissingIdentity,
 		/// User has tried to vote multiple time on one proposal, but possible only once.
 		AlreadyVoted,
+		/// Proposal does not redistribute its vote costs.
+		NoPot,
+		/// Account is not entitled to a share of the pot.
+		NotRecipient,
+		/// Account has already enrolled for a share of the pot.
+		AlreadyEnrolled,
+		/// Shares cannot be enrolled for or paid out at this point of the proposal lifecycle.
+		ClaimNotOpen,
+		/// Too many proposals are awaiting finalization to create a new one.
+		TooManyActiveProposals,
+		/// Only the proposer can cancel a proposal.
+		NotProposer,
+		/// Proposal has votes and cannot be cancelled.
+		ProposalHasVotes,
+		/// Proposal has already been finalized.
+		AlreadyFinalized,
+		/// Vote cost was paid into the pot of the proposal, so nothing is reserved.
+		NotReserved,
+		/// Account has too many votes whose funds have not been released yet.
+		TooManyVotedProposals,
+		/// Account is not a member of the voting body.
+		NotMember,
+		/// Account does not hold the minimum balance required to vote.
+		InsufficientBalance,
+		/// Account may not cast this many votes on a single proposal.
+		VoteCapExceeded,
+		/// Account was not eligible at the snapshot block of the proposal, or its checkpoint of that
+		/// block was dropped to make room for newer ones.
+		NotEligibleAtSnapshot,
+		/// Deadline of the vote permit has passed.
+		PermitExpired,
+		/// Vote permit does not carry the next permit nonce of the voter.
+		BadPermitNonce,
+		/// Vote permit is not signed by the voter.
+		BadPermitSignature,
+		/// Proposal has no enactment scheduled.
+		NotScheduled,
+		/// Preimage of the proposal hash is not available.
+		PreimageMissing,
+		/// Preimage of the proposal hash is not a call.
+		PreimageInvalid,
+		/// Call of the proposal weighs more than `MaxEnactmentWeight`.
+		EnactmentTooHeavy,
+		/// Proposal has not been finalized yet.
+		NotFinalized,
+		/// At least one vote has to be cast.
+		ZeroVotes,
+		/// Cost of the votes does not fit into the balance type, or the tally of the proposal
+		/// overflows.
+		Overflow,
+		/// Account has already created `MaxProposalsPerProposer` proposals.
+		TooManyProposals,
+		/// Funds of the vote have already been released.
+		AlreadyReleased,
+		/// Too many pots are awaiting their payout to finalize a proposal with a pot.
+		TooManyDuePayouts,
+		/// Too few votes were cast on the passed proposal to enact it.
+		TurnoutTooLow,
+	}
+
+	#[pallet::hooks]
+	impl<T: Config<I>, I: 'static> Hooks<BlockNumberFor<T>> for Pallet<T, I> {
+		/// Submit a transaction that settles an ended proposal, if any is pending and the node has
+		/// a key of type [`KEY_TYPE`](crate::crypto::KEY_TYPE).
+		///
+		/// Transactions of one account submitted in the same block would share a nonce, so at most
+		/// one is submitted per block, over all instances that sign with the same keys. Instances
+		/// take their turn in the order of the runtime, once the ones before them are settled.
+		fn offchain_worker(now: T::BlockNumber) {
+			let signer = Signer::<T, T::AuthorityId>::any_account();
+			if !signer.can_sign() {
+				return
+			}
+			let call = match Self::next_settlement(now) {
+				Some(call) => call,
+				None => return,
+			};
+			let submitted = StorageValueRef::persistent(crate::crypto::SUBMISSION_LOCK).mutate(
+				|last: Result<Option<T::BlockNumber>, StorageRetrievalError>| match last {
+					Ok(Some(last)) if last >= now => Err(()),
+					_ => Ok(now),
+				},
+			);
+			if submitted.is_ok() {
+				// Failures are retried with the next block.
+				let _ = signer.send_signed_transaction(|_| call.clone());
+			}
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			Self::do_try_state()
+		}
 	}
 
 	#[pallet::call]
-	impl<T: Config> Pallet<T> {
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
 
 		/// Create a proposal for voting using quadratic voting system.
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,2))]
 		pub fn propose(
 			origin: OriginFor<T>, 
 			hash: BoundedVec<u8, ConstU32<32>>
 		) -> DispatchResult {
 			let sender = ensure_signed(origin)?;
-			ensure!(T::IdentityVerifier::has_identity(&sender, 0), Error::<T>::MissingIdentity);
-			let proposal_index = ProposalCount::<T>::get().unwrap_or_default();
-			let block_number = <frame_system::Pallet<T>>::block_number() + T::VotingPeriod::get();
-			let proposal = Proposal {
-				aye: 0,
-				nay: 0,
-				hash: hash,
-				proposer: sender.clone(),
-				end: block_number
-			};
-
-			Proposals::<T>::insert(proposal_index, proposal);
-			ProposalCount::<T>::put(proposal_index + 1u32);
+			let proposal_index = Self::do_propose(sender, hash)?;
 			Self::deposit_event(Event::Proposed(proposal_index));
 			Ok(())
 		}
 
 		/// Vote for proposal at proposal index with one or more votes.
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		///
+		/// Fee-less for eligible accounts, up to `MaxFreeCalls` calls per proposal.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6,5))]
 		pub fn vote_aye(
 			origin: OriginFor<T>, 
 			proposal_index: u32,
 			votes: u128,
-		) -> DispatchResult {
+		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
-			// Check that user has identity.
-			ensure!(T::IdentityVerifier::has_identity(&sender, 0), Error::<T>::MissingIdentity);
-			// Check that proposal that user is voting exists.
-			ensure!(Proposals::<T>::contains_key(&proposal_index), Error::<T>::UnexistingProposal);
-			// Fetch our proposal from storage.
-			let mut p = Self::proposals(proposal_index)
-				.expect("Already checked that value exsits; so it is safe to unwrap. QED!");
-			// Fetch current block number and check that voting period is still ongoing.
-			let now = <frame_system::Pallet<T>>::block_number();
-			ensure!(
-				now <= p.end,
-				Error::<T>::VotingEnded
-			);
-			// Check if user has already voted.
-			ensure!(!ReservedTokens::<T>::contains_key((proposal_index.clone(), sender.clone())), Error::<T>::AlreadyVoted);
-			// Calculate amount of tokens that needs to be reserved from users to get desired number of votes.
-			let reserved_amount = Self::u128_to_balance(Self::calculate_price(votes));
-			// Try to reserve funds, and fail fast if the user can't afford it.
-			T::Currency::reserve(&sender, reserved_amount.clone())?;
+			let mut p = Self::do_vote(&sender, proposal_index, votes, VoteSide::Aye)?;
 			// Increment number of supporting votes.
-			p.aye = p.aye + votes;
+			p.aye = p.aye.checked_add(votes).ok_or(Error::<T, I>::Overflow)?;
 			// Store the updated proposal in storage.
-			Proposals::<T>::insert(proposal_index, p);
-			// Create new entry for reserved tokens for this voting.
-			ReservedTokens::<T>::insert((proposal_index, sender), reserved_amount);
+			Proposals::<T, I>::insert(proposal_index, p);
 			// Deposit event that voting for proposal with proposal_index happened.
-			Self::deposit_event(Event::VotedAye(proposal_index));
+			Self::deposit_event(Event::VotedAye(proposal_index, sender.clone(), votes));
 
-			Ok(())
+			Ok(Self::use_free_call(&sender, proposal_index).into())
 		}
 
 		/// Vote against proposal at proposal index with one or more votes.
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		///
+		/// Fee-less for eligible accounts, up to `MaxFreeCalls` calls per proposal.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(6,5))]
 		pub fn vote_nay(
 			origin: OriginFor<T>, 
 			proposal_index: u32,
 			votes: u128,
-		) -> DispatchResult {
+		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
-			// Check that user has identity.
-			ensure!(T::IdentityVerifier::has_identity(&sender, 0), Error::<T>::MissingIdentity);
-			// Check that proposal that user is voting exists.
-			ensure!(Proposals::<T>::contains_key(&proposal_index), Error::<T>::UnexistingProposal);
-			// Fetch our proposal from storage.
-			let mut p = Self::proposals(proposal_index)
-				.expect("Already checked that value exsits; so it is safe to unwrap. QED!");
-			// Fetch current block number and check that voting period is still ongoing.
-			let now = <frame_system::Pallet<T>>::block_number();
-			ensure!(
-				now <= p.end,
-				Error::<T>::VotingEnded
-			);
-			// Check if user has already voted.
-			ensure!(!ReservedTokens::<T>::contains_key((proposal_index.clone(), sender.clone())), Error::<T>::AlreadyVoted);
-			// Calculate amount of tokens that needs to be reserved from users to get desired number of votes.
-			let reserved_amount = Self::u128_to_balance(Self::calculate_price(votes));
-			// Try to reserve funds, and fail fast if the user can't afford it.
-			T::Currency::reserve(&sender, reserved_amount.clone())?;
+			let mut p = Self::do_vote(&sender, proposal_index, votes, VoteSide::Nay)?;
 			// Increment number of votes against proposal.
-			p.nay = p.nay + votes;
+			p.nay = p.nay.checked_add(votes).ok_or(Error::<T, I>::Overflow)?;
 			// Store the updated proposal in storage.
-			Proposals::<T>::insert(proposal_index, p);
-			// Create new entry for reserved tokens for this voting.
-			ReservedTokens::<T>::insert((proposal_index, sender), reserved_amount);
+			Proposals::<T, I>::insert(proposal_index, p);
 			// Deposit event that voting against proposal with proposal_index happened.
-			Self::deposit_event(Event::VotedNay(proposal_index));
+			Self::deposit_event(Event::VotedNay(proposal_index, sender.clone(), votes));
 			
-			Ok(())
+			Ok(Self::use_free_call(&sender, proposal_index).into())
 		}
 
 		/// Unreserve tokens after voting period is ended.
-		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1,1))]
+		///
+		/// Fee-less for eligible accounts, up to `MaxFreeCalls` calls per proposal.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(5,5))]
 		pub fn unreserve(
 			origin: OriginFor<T>, 
 			proposal_index: u32,
-		) -> DispatchResult {
+		) -> DispatchResultWithPostInfo {
 			let sender = ensure_signed(origin)?;
 			// Check that proposal that user is voting exists.
-			ensure!(Proposals::<T>::contains_key(&proposal_index), Error::<T>::UnexistingProposal);
+			ensure!(Proposals::<T, I>::contains_key(&proposal_index), Error::<T, I>::UnexistingProposal);
 			// Fetch our proposal from storage.
 			let p = Self::proposals(proposal_index)
 				.expect("Already checked that value exsits; so it is safe to unwrap. QED!");
 			// Fetch current block number and check that voting period is over.
 			let now = <frame_system::Pallet<T>>::block_number();
 			ensure!(
-				now >= p.end,
-				Error::<T>::VotingNotEnded
+				now > p.end,
+				Error::<T, I>::VotingNotEnded
 			);
-			// Check that there is mapped tokens entry for this user in storage.
-			ensure!(ReservedTokens::<T>::contains_key((proposal_index.clone(), sender.clone())), Error::<T>::NotVoted);
-			// Fetch token reservation details from storage.
-			let reservation = ReservedTokens::<T>::take((proposal_index, sender.clone()))
-				.expect("Already checked that value exsits; so it is safe to unwrap. QED!");
-			// Unreserve tokens for the user.
-			T::Currency::unreserve(&sender, reservation);
+			// Check that there is a vote entry for this user in storage.
+			let vote = Self::votes(proposal_index, &sender).ok_or(Error::<T, I>::NotVoted)?;
+			ensure!(!vote.released, Error::<T, I>::AlreadyReleased);
+			// Costs paid into a pot were never reserved.
+			ensure!(!Pots::<T, I>::contains_key(proposal_index), Error::<T, I>::NotReserved);
+			let pays = Self::use_free_call(&sender, proposal_index);
+			Self::release_vote(&sender, proposal_index);
+
+			Ok(pays.into())
+		}
+
+		/// Withdraw a proposal that nobody has voted on yet.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,5))]
+		pub fn cancel(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			ensure!(p.proposer == sender, Error::<T, I>::NotProposer);
+			// Votes may have reserved or paid tokens that depend on the proposal.
+			ensure!(p.aye == 0 && p.nay == 0, Error::<T, I>::ProposalHasVotes);
+			// Fetch current block number and check that voting period is still ongoing.
+			let now = <frame_system::Pallet<T>>::block_number();
+			ensure!(now <= p.end, Error::<T, I>::VotingEnded);
+
+			ActiveProposals::<T, I>::mutate(|active| {
+				active.retain(|(_, index)| *index != proposal_index)
+			});
+			ProposalsByProposer::<T, I>::remove(&sender, proposal_index);
+			Pots::<T, I>::remove(proposal_index);
+			Snapshots::<T, I>::remove(proposal_index);
+			Proposals::<T, I>::remove(proposal_index);
+			Self::deposit_event(Event::Cancelled(proposal_index));
+			Ok(())
+		}
+
+		/// Close a proposal whose voting period is over and record its outcome.
+		///
+		/// Can be called by anyone.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4,2))]
+		pub fn finalize(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			// Fetch current block number and check that voting period is over.
+			let now = <frame_system::Pallet<T>>::block_number();
+			ensure!(now > p.end, Error::<T, I>::VotingNotEnded);
+
+			let was_active = ActiveProposals::<T, I>::mutate(|active| {
+				let position = active.binary_search(&(p.end, proposal_index));
+				position.map(|position| active.remove(position)).is_ok()
+			});
+			ensure!(
+				was_active || OverflowProposals::<T, I>::take(proposal_index).is_some(),
+				Error::<T, I>::AlreadyFinalized
+			);
+			// Remember reserves that are still to be returned, for `refund_votes`.
+			if !Pots::<T, I>::contains_key(proposal_index) &&
+				Votes::<T, I>::iter_prefix_values(proposal_index).any(|vote| !vote.released)
+			{
+				PendingRefunds::<T, I>::insert(proposal_index, ());
+			}
+			// Remember pots that are still to be paid out, for `payout_shares`.
+			if let Some(pot) = Self::pots(proposal_index) {
+				if pot.target == Redistribution::Identities || pot.recipients > 0 {
+					let entry = (Self::payable_after(&p, &pot), proposal_index);
+					DuePayouts::<T, I>::try_mutate(|due| {
+						let position = due.binary_search(&entry).unwrap_or_else(|position| position);
+						due.try_insert(position, entry).map_err(|_| Error::<T, I>::TooManyDuePayouts)
+					})?;
+				}
+			}
+			let status = Self::proposal_status(&p);
+			Self::deposit_event(Event::Finalized(proposal_index, status));
+			if status == ProposalStatus::Passed {
+				Self::schedule_enactment(proposal_index, &p, now);
+			}
+			Ok(())
+		}
+
+		/// Dispatch the call of a passed proposal from `EnactmentOrigin`.
+		///
+		/// Dispatched by the scheduler from `EnactmentOrigin` once the enactment delay is over.
+		#[pallet::weight(
+			10_000 + T::MaxEnactmentWeight::get() + T::DbWeight::get().reads_writes(3,2)
+		)]
+		pub fn enact(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			let origin: Result<frame_system::RawOrigin<T::AccountId>, OriginFor<T>> = origin.into();
+			ensure!(origin.ok() == Some(T::EnactmentOrigin::get()), BadOrigin);
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			Enactments::<T, I>::take(proposal_index).ok_or(Error::<T, I>::NotScheduled)?;
+			// Only proposals with a decodable hash are scheduled.
+			let hash = T::Hash::decode(&mut &p.hash[..]).map_err(|_| Error::<T, I>::NotScheduled)?;
+			// Unrequesting may drop the preimage, so the call is fetched first.
+			let call = Self::preimage_call(&hash);
+			T::Preimages::unrequest_preimage(&hash);
+
+			match call {
+				Ok(call) => {
+					let result = call.dispatch(T::EnactmentOrigin::get().into());
+					Self::deposit_event(Event::Enacted(
+						proposal_index,
+						result.map(|_| ()).map_err(|e| e.error),
+					));
+				},
+				Err(error) =>
+					Self::deposit_event(Event::EnactmentFailed(proposal_index, error.into())),
+			}
+			Ok(())
+		}
+
+		/// Cancel the scheduled enactment of a passed proposal.
+		///
+		/// The dispatch origin must be `CancelOrigin`.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,2))]
+		pub fn cancel_enactment(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			T::CancelOrigin::ensure_origin(origin)?;
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			ensure!(
+				Enactments::<T, I>::contains_key(proposal_index),
+				Error::<T, I>::NotScheduled
+			);
+			T::Scheduler::cancel_named(Self::enactment_id(proposal_index))
+				.map_err(|_| Error::<T, I>::NotScheduled)?;
+
+			Enactments::<T, I>::remove(proposal_index);
+			if let Ok(hash) = T::Hash::decode(&mut &p.hash[..]) {
+				T::Preimages::unrequest_preimage(&hash);
+			}
+			Self::deposit_event(Event::EnactmentCancelled(proposal_index));
+			Ok(())
+		}
+
+		/// Unreserve a bounded batch of votes of a finalized proposal on behalf of their voters.
+		///
+		/// Can be called by anyone.
+		#[pallet::weight(
+			10_000 + T::DbWeight::get().reads_writes(
+				2 + 4 * T::MaxPayoutsPerBatch::get() as u64,
+				1 + 4 * T::MaxPayoutsPerBatch::get() as u64,
+			)
+		)]
+		pub fn refund_votes(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			ensure!(
+				PendingRefunds::<T, I>::contains_key(proposal_index),
+				Error::<T, I>::NotFinalized
+			);
+
+			let batch: Vec<T::AccountId> = Votes::<T, I>::iter_prefix(proposal_index)
+				.filter(|(_, vote)| !vote.released)
+				.map(|(who, _)| who)
+				.take(T::MaxPayoutsPerBatch::get() as usize)
+				.collect();
+			for who in batch {
+				let cost = Self::release_vote(&who, proposal_index);
+				Self::deposit_event(Event::Refunded(proposal_index, who, cost));
+			}
+
+			Ok(())
+		}
+
+		/// Create a proposal whose vote costs are paid into a pot that is split equally among
+		/// `target` once the proposal has ended.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2,3))]
+		pub fn propose_with_pot(
+			origin: OriginFor<T>,
+			hash: BoundedVec<u8, ConstU32<32>>,
+			target: Redistribution,
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			let proposal_index = Self::do_propose(sender, hash)?;
+			let pot = Pot { target, remaining: Zero::zero(), recipients: 0 };
+			Pots::<T, I>::insert(proposal_index, pot);
+			Self::deposit_event(Event::Proposed(proposal_index));
+			Ok(())
+		}
+
+		/// Create a proposal whose eligibility and vote caps are taken from the checkpoints of voters
+		/// at the end of the block before its creation.
+		///
+		/// Balance changes are only recorded while snapshot proposals are open. A proposal that
+		/// starts the recording takes its snapshot at the end of its own block instead.
+		///
+		/// Voters can cast at most the square root of their free balance at that block in votes.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4,5))]
+		pub fn propose_with_snapshot(
+			origin: OriginFor<T>,
+			hash: BoundedVec<u8, ConstU32<32>>,
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			let now = <frame_system::Pallet<T>>::block_number();
+			let end = now + T::VotingPeriod::get();
+			let (first, last) = match Self::recording_period() {
+				Some((first, last)) if now <= last => (first, last.max(end)),
+				_ => (now, end),
+			};
+			RecordingPeriod::<T, I>::put((first, last));
+			let proposal_index = Self::do_propose(sender, hash)?;
+			let snapshot = now.saturating_sub(One::one()).max(first);
+			Snapshots::<T, I>::insert(proposal_index, snapshot);
+			Self::deposit_event(Event::Proposed(proposal_index));
+			Ok(())
+		}
+
+		/// Record the current eligibility and free balance of `who`.
+		///
+		/// Balance changes are recorded as they happen while snapshot proposals are open. Can be
+		/// called by anyone, so that changes of eligibility that don't touch the balance, such as a
+		/// new membership, are recorded too.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3,1))]
+		pub fn checkpoint(
+			origin: OriginFor<T>,
+			who: T::AccountId,
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			Self::record_checkpoint(&who);
+			Self::deposit_event(Event::Checkpointed(who));
+			Ok(())
+		}
+
+		/// Cast a vote on behalf of the voter of `permit`, who signed it off-chain.
+		///
+		/// Can be submitted as an unsigned transaction, or by a relayer that pays the transaction
+		/// fee. The fee is waived like that of `vote_aye` and `vote_nay` for the voter. The vote
+		/// cost is charged to the voter.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(8,6))]
+		pub fn vote_with_permit(
+			origin: OriginFor<T>,
+			permit: VotePermit<T::AccountId, T::BlockNumber>,
+			signature: T::PermitSignature,
+		) -> DispatchResultWithPostInfo {
+			if ensure_signed(origin.clone()).is_err() {
+				ensure_none(origin)?;
+			}
+			ensure!(
+				permit.nonce == Self::permit_nonce(&permit.voter),
+				Error::<T, I>::BadPermitNonce
+			);
+			Self::check_permit(&permit, &signature)?;
+			let VotePermit { voter, proposal_index, side, votes, .. } = permit;
+			let mut p = Self::do_vote(&voter, proposal_index, votes, side)?;
+			PermitNonces::<T, I>::mutate(&voter, |nonce| *nonce = nonce.saturating_add(1));
+			let event = match side {
+				VoteSide::Aye => {
+					p.aye = p.aye.checked_add(votes).ok_or(Error::<T, I>::Overflow)?;
+					Event::VotedAye(proposal_index, voter.clone(), votes)
+				},
+				VoteSide::Nay => {
+					p.nay = p.nay.checked_add(votes).ok_or(Error::<T, I>::Overflow)?;
+					Event::VotedNay(proposal_index, voter.clone(), votes)
+				},
+			};
+			Proposals::<T, I>::insert(proposal_index, p);
+			Self::deposit_event(event);
+
+			Ok(Self::use_free_call(&voter, proposal_index).into())
+		}
+
+		/// Claim a share of the pot of an ended proposal.
+		///
+		/// For `Redistribution::Identities` pots this enrolls the caller while the claim period is
+		/// running, and pays the share out once it is over.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4,3))]
+		pub fn claim_share(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			let sender = ensure_signed(origin)?;
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			let pot = Self::pots(proposal_index).ok_or(Error::<T, I>::NoPot)?;
+			let now = <frame_system::Pallet<T>>::block_number();
+			// Shares are never handed out while voting is ongoing.
+			ensure!(now > p.end, Error::<T, I>::ClaimNotOpen);
+
+			if let Some(share) = Self::unpaid_shares(proposal_index, &sender) {
+				Self::pay_out(&sender, share)?;
+				UnpaidShares::<T, I>::remove(proposal_index, &sender);
+				Self::deposit_event(Event::SharePaid(proposal_index, sender, share));
+				return Ok(())
+			}
+
+			if pot.target == Redistribution::Identities && now <= p.end + T::ClaimPeriod::get() {
+				// Check that user is eligible.
+				T::VoterEligibility::eligibility(&sender).map_err(Error::<T, I>::from)?;
+				ensure!(
+					!PotRecipients::<T, I>::contains_key(proposal_index, &sender),
+					Error::<T, I>::AlreadyEnrolled
+				);
+				PotRecipients::<T, I>::insert(proposal_index, &sender, ());
+				Pots::<T, I>::insert(
+					proposal_index,
+					Pot { recipients: pot.recipients.saturating_add(1), ..pot },
+				);
+				Self::deposit_event(Event::ShareEnrolled(proposal_index, sender));
+				return Ok(())
+			}
+
+			ensure!(
+				PotRecipients::<T, I>::contains_key(proposal_index, &sender),
+				Error::<T, I>::NotRecipient
+			);
+			Self::pay_share(proposal_index, &sender, false)
+		}
+
+		/// Pay out up to `MaxPayoutsPerBatch` outstanding shares of the pot of a proposal.
+		///
+		/// Can be called by anyone once shares of the pot are payable. Shares that can't be
+		/// transferred are kept for their recipient to claim with `claim_share`.
+		#[pallet::weight(
+			10_000 + T::DbWeight::get().reads_writes(
+				2 + 2 * T::MaxPayoutsPerBatch::get() as u64,
+				4 * T::MaxPayoutsPerBatch::get() as u64,
+			)
+		)]
+		pub fn payout_shares(
+			origin: OriginFor<T>,
+			proposal_index: u32,
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			let p = Self::proposals(proposal_index).ok_or(Error::<T, I>::UnexistingProposal)?;
+			let pot = Self::pots(proposal_index).ok_or(Error::<T, I>::NoPot)?;
+			let now = <frame_system::Pallet<T>>::block_number();
+			ensure!(now > Self::payable_after(&p, &pot), Error::<T, I>::ClaimNotOpen);
+
+			let batch: Vec<T::AccountId> = PotRecipients::<T, I>::iter_key_prefix(proposal_index)
+				.take(T::MaxPayoutsPerBatch::get() as usize)
+				.collect();
+			for who in batch.iter() {
+				Self::pay_share(proposal_index, who, true)?;
+			}
+			// Pots nobody enrolled for are done as well.
+			if pot.recipients == 0 {
+				Self::remove_due_payout(proposal_index);
+			}
 
 			Ok(())
 		}
 		
 	}
 
-	impl<T: Config> Pallet<T> {
-		// Helper function to calculate price in tokens for given amount of votes.
-		pub fn calculate_price(amount: u128) -> u128 {
-			amount.checked_mul(amount).unwrap()
+	impl<T: Config<I>, I: 'static> From<Ineligibility> for Error<T, I> {
+		fn from(reason: Ineligibility) -> Self {
+			match reason {
+				Ineligibility::UnexistingProposal => Error::<T, I>::UnexistingProposal,
+				Ineligibility::VotingEnded => Error::<T, I>::VotingEnded,
+				Ineligibility::MissingIdentity => Error::<T, I>::MissingIdentity,
+				Ineligibility::AlreadyVoted => Error::<T, I>::AlreadyVoted,
+				Ineligibility::NotMember => Error::<T, I>::NotMember,
+				Ineligibility::InsufficientBalance => Error::<T, I>::InsufficientBalance,
+				Ineligibility::NotEligibleAtSnapshot => Error::<T, I>::NotEligibleAtSnapshot,
+			}
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> From<Refusal> for Error<T, I> {
+		fn from(refusal: Refusal) -> Self {
+			Ineligibility::from(refusal).into()
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> OnBalanceChange<T::AccountId> for Pallet<T, I> {
+		fn before_change(who: &T::AccountId) {
+			// Genesis configs of other pallets may not be built yet, so nothing is recorded.
+			if <frame_system::Pallet<T>>::block_number().is_zero() {
+				return
+			}
+			if Self::is_recording() {
+				Self::ensure_checkpoint(who);
+			} else if Checkpoints::<T, I>::contains_key(who) {
+				// The checkpoints would go stale, so the current state is taken from now on.
+				Checkpoints::<T, I>::remove(who);
+			}
+		}
+
+		fn after_change(who: &T::AccountId) {
+			if !<frame_system::Pallet<T>>::block_number().is_zero() && Self::is_recording() {
+				Self::record_checkpoint(who);
+			}
 		}
+	}
+
+	#[pallet::validate_unsigned]
+	impl<T: Config<I>, I: 'static> ValidateUnsigned for Pallet<T, I> {
+		type Call = Call<T, I>;
 
-		// Helper function to convert number of votes (u128) to balance.
-		pub fn u128_to_balance(cost: u128) -> BalanceOf<T> {
-			TryInto::<BalanceOf::<T>>::try_into(cost).ok().unwrap()
-		}	
+		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+			let (permit, signature) = match call {
+				Call::vote_with_permit { permit, signature } => (permit, signature),
+				_ => return InvalidTransaction::Call.into(),
+			};
+			let next_nonce = Self::permit_nonce(&permit.voter);
+			if permit.nonce < next_nonce {
+				return InvalidTransaction::Stale.into()
+			}
+			Self::check_permit(permit, signature).map_err(|error| match error {
+				Error::<T, I>::BadPermitSignature => InvalidTransaction::BadProof,
+				_ => InvalidTransaction::Stale,
+			})?;
+			// Nobody pays for unsigned votes that fail, so keep them out of the pool.
+			let (_, cap) = Self::check_eligibility(&permit.voter, permit.proposal_index)
+				.map_err(|_| InvalidTransaction::Call)?;
+			if permit.votes == 0 || cap.map_or(false, |cap| permit.votes > cap) {
+				return InvalidTransaction::Call.into()
+			}
+			let cost = Self::quote_cost(permit.votes).ok_or(InvalidTransaction::Call)?;
+			if T::Currency::free_balance(&permit.voter) < cost {
+				return InvalidTransaction::Payment.into()
+			}
+
+			let now = <frame_system::Pallet<T>>::block_number();
+			let mut validity = ValidTransaction::with_tag_prefix("QuadraticVotingPermit")
+				.priority(T::FreeCallPriority::get())
+				.and_provides((T::PalletId::get(), &permit.voter, permit.nonce))
+				.longevity(permit.deadline.saturating_sub(now).saturated_into::<u64>().max(1));
+			// Later permits of the voter wait in the pool for the earlier ones.
+			if permit.nonce > next_nonce {
+				validity =
+					validity.and_requires((T::PalletId::get(), &permit.voter, permit.nonce - 1));
+			}
+			validity.build()
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		// Helper function to calculate price in tokens for given amount of votes, or `None` if it
+		// overflows.
+		pub fn calculate_price(amount: u128) -> Option<u128> {
+			amount.checked_mul(amount)
+		}
+
+		// Helper function to convert number of votes (u128) to balance, or `None` if it doesn't fit.
+		pub fn u128_to_balance(cost: u128) -> Option<BalanceOf<T, I>> {
+			cost.try_into().ok()
+		}
+
+		/// The account that holds the pots of budget-balanced proposals.
+		pub fn account_id() -> T::AccountId {
+			T::PalletId::get().into_account_truncating()
+		}
+
+		// Store a new proposal created by `sender` and return its index.
+		fn do_propose(
+			sender: T::AccountId,
+			hash: BoundedVec<u8, ConstU32<32>>,
+		) -> Result<ProposalIndex, DispatchError> {
+			T::VoterEligibility::eligibility(&sender).map_err(Error::<T, I>::from)?;
+			let proposal_index = Self::insert_proposal(sender.clone(), hash, T::VotingPeriod::get())?;
+			Self::record_checkpoint(&sender);
+			Ok(proposal_index)
+		}
+
+		// Store a new proposal that is open for `voting_period` blocks and return its index.
+		fn insert_proposal(
+			sender: T::AccountId,
+			hash: BoundedVec<u8, ConstU32<32>>,
+			voting_period: T::BlockNumber,
+		) -> Result<ProposalIndex, DispatchError> {
+			let proposal_index = ProposalCount::<T, I>::get().unwrap_or_default();
+			let block_number = <frame_system::Pallet<T>>::block_number() + voting_period;
+			// Counting stops at the bound, so this reads at most `MaxProposalsPerProposer` keys.
+			let created = ProposalsByProposer::<T, I>::iter_key_prefix(&sender)
+				.take(T::MaxProposalsPerProposer::get() as usize)
+				.count();
+			ensure!(
+				created < T::MaxProposalsPerProposer::get() as usize,
+				Error::<T, I>::TooManyProposals
+			);
+			// Keep the active proposals ordered by the block their voting ends at.
+			ActiveProposals::<T, I>::try_mutate(|active| -> DispatchResult {
+				let entry = (block_number, proposal_index);
+				let position = active.binary_search(&entry).unwrap_or_else(|position| position);
+				active
+					.try_insert(position, entry)
+					.map_err(|_| Error::<T, I>::TooManyActiveProposals.into())
+			})?;
+			ProposalsByProposer::<T, I>::insert(&sender, proposal_index, ());
+			let proposal = Proposal {
+				aye: 0,
+				nay: 0,
+				hash: hash,
+				proposer: sender,
+				end: block_number
+			};
+
+			Proposals::<T, I>::insert(proposal_index, proposal);
+			ProposalCount::<T, I>::put(proposal_index + 1u32);
+			Ok(proposal_index)
+		}
+
+		// Check that `sender` may vote on the proposal, charge the cost of `votes`, record the vote
+		// and return the proposal so the caller can update its tally.
+		fn do_vote(
+			sender: &T::AccountId,
+			proposal_index: u32,
+			votes: u128,
+			side: VoteSide,
+		) -> Result<Proposal<T::AccountId, T::BlockNumber>, DispatchError> {
+			ensure!(votes > 0, Error::<T, I>::ZeroVotes);
+			let (p, cap) =
+				Self::check_eligibility(sender, proposal_index).map_err(Error::<T, I>::from)?;
+			ensure!(cap.map_or(true, |cap| votes <= cap), Error::<T, I>::VoteCapExceeded);
+			// Remember the proposal until the funds of the vote are released.
+			let mut voted = Self::voted_proposals(sender);
+			let position = voted.binary_search(&proposal_index).unwrap_or_else(|position| position);
+			voted
+				.try_insert(position, proposal_index)
+				.map_err(|_| Error::<T, I>::TooManyVotedProposals)?;
+			// Calculate amount of tokens that needs to be reserved from users to get desired number of votes.
+			let cost = Self::quote_cost(votes).ok_or(Error::<T, I>::Overflow)?;
+			// Backends such as `FrozenAsset` don't report the charge, so the state before it is
+			// recorded here.
+			Self::ensure_checkpoint(sender);
+
+			let paid_into_pot = match Self::pots(proposal_index) {
+				Some(mut pot) => {
+					// Move the cost into the pot, and fail fast if the user can't afford it.
+					T::Currency::transfer(sender, &Self::account_id(), cost)?;
+					pot.remaining = pot.remaining.saturating_add(cost);
+					if pot.target == Redistribution::Voters {
+						pot.recipients = pot.recipients.saturating_add(1);
+						PotRecipients::<T, I>::insert(proposal_index, sender, ());
+					}
+					Pots::<T, I>::insert(proposal_index, pot);
+					true
+				},
+				None => {
+					// Try to reserve funds, and fail fast if the user can't afford it.
+					T::Currency::hold(sender, cost)?;
+					false
+				},
+			};
+			// Create new entry for this vote.
+			let at_block = <frame_system::Pallet<T>>::block_number();
+			let record = VoteRecord { side: Some(side), votes, cost, at_block, released: false };
+			Votes::<T, I>::insert(proposal_index, sender, record);
+			VotedProposals::<T, I>::insert(sender, voted);
+			VoterStatistics::<T, I>::mutate(sender, |stats| {
+				stats.votes_cast = stats.votes_cast.saturating_add(votes);
+				stats.credits_spent = stats.credits_spent.saturating_add(cost);
+				if !paid_into_pot {
+					stats.outstanding_reserves = stats.outstanding_reserves.saturating_add(cost);
+				}
+			});
+			Self::record_checkpoint(sender);
+
+			Ok(p)
+		}
+
+		// Check that `who` may vote on the proposal and return the proposal and the vote cap of
+		// `who` if so.
+		fn check_eligibility(
+			who: &T::AccountId,
+			proposal_index: u32,
+		) -> Result<(Proposal<T::AccountId, T::BlockNumber>, Option<u128>), Ineligibility> {
+			// Check that user is eligible.
+			let mut cap = T::VoterEligibility::eligibility(who)?;
+			// Check that proposal that user is voting exists.
+			let p = Self::proposals(proposal_index).ok_or(Ineligibility::UnexistingProposal)?;
+			// Fetch current block number and check that voting period is still ongoing.
+			let now = <frame_system::Pallet<T>>::block_number();
+			ensure!(
+				now <= p.end,
+				Ineligibility::VotingEnded
+			);
+			// Check if user has already voted.
+			ensure!(!Votes::<T, I>::contains_key(proposal_index, who), Ineligibility::AlreadyVoted);
+			// Check that user was eligible at the snapshot, so moving funds or identities around
+			// after the proposal was created does not help.
+			if let Some(snapshot) = Self::snapshots(proposal_index) {
+				let checkpoint =
+					Self::checkpoint_at(who, snapshot).ok_or(Ineligibility::NotEligibleAtSnapshot)?;
+				let snapshot_cap =
+					checkpoint.eligibility.map_err(|_| Ineligibility::NotEligibleAtSnapshot)?;
+				// Votes cost the square of their number, so the recorded balance pays for at most
+				// its square root.
+				let balance_cap = checkpoint.balance.saturated_into::<u128>().integer_sqrt();
+				cap = [cap, snapshot_cap, Some(balance_cap)].into_iter().flatten().min();
+			}
+			Ok((p, cap))
+		}
+
+		// Release the reserved cost of the vote of `who` on the proposal, and return it.
+		fn release_vote(who: &T::AccountId, proposal_index: u32) -> BalanceOf<T, I> {
+			let cost = Votes::<T, I>::mutate(proposal_index, who, |maybe_vote| match maybe_vote {
+				Some(vote) if !vote.released => {
+					vote.released = true;
+					vote.cost
+				},
+				_ => Zero::zero(),
+			});
+			Self::before_change(who);
+			T::Currency::release(who, cost);
+			Self::after_change(who);
+			VoterStatistics::<T, I>::mutate(who, |stats| {
+				stats.outstanding_reserves = stats.outstanding_reserves.saturating_sub(cost);
+			});
+			Self::prune_voted_proposal(who, proposal_index);
+			if Votes::<T, I>::iter_prefix_values(proposal_index).all(|vote| vote.released) {
+				PendingRefunds::<T, I>::remove(proposal_index);
+			}
+			cost
+		}
+
+		// Name of the scheduled enactment of the proposal. Instances have distinct pallet ids.
+		fn enactment_id(proposal_index: u32) -> Vec<u8> {
+			(T::PalletId::get().0, proposal_index).encode()
+		}
+
+		// Schedule the call of the passed proposal `p` for enactment, if its hash refers to one.
+		fn schedule_enactment(
+			proposal_index: u32,
+			p: &Proposal<T::AccountId, T::BlockNumber>,
+			now: T::BlockNumber,
+		) {
+			// Proposals identified by a short string have nothing to enact.
+			let hash = match T::Hash::decode(&mut &p.hash[..]) {
+				Ok(hash) => hash,
+				Err(_) => return,
+			};
+			if p.aye.saturating_add(p.nay) < T::MinEnactmentTurnout::get() {
+				let error = Error::<T, I>::TurnoutTooLow.into();
+				Self::deposit_event(Event::EnactmentFailed(proposal_index, error));
+				return
+			}
+			let when = now.saturating_add(T::EnactmentDelay::get());
+			let scheduled = T::Scheduler::schedule_named(
+				Self::enactment_id(proposal_index),
+				DispatchTime::At(when),
+				None,
+				T::EnactmentPriority::get(),
+				T::EnactmentOrigin::get().into(),
+				Call::enact { proposal_index }.into(),
+			);
+			match scheduled {
+				Ok(_) => {
+					// Keep the preimage around until the call is enacted.
+					T::Preimages::request_preimage(&hash);
+					Enactments::<T, I>::insert(proposal_index, when);
+					Self::deposit_event(Event::Scheduled(proposal_index, when));
+				},
+				Err(error) =>
+					Self::deposit_event(Event::EnactmentFailed(proposal_index, error.into())),
+			}
+		}
+
+		// The call that `hash` is the preimage hash of.
+		fn preimage_call(hash: &T::Hash) -> Result<T::Proposal, Error<T, I>> {
+			let bytes = T::Preimages::get_preimage(hash).ok_or(Error::<T, I>::PreimageMissing)?;
+			let call =
+				T::Proposal::decode(&mut &bytes[..]).map_err(|_| Error::<T, I>::PreimageInvalid)?;
+			ensure!(
+				call.get_dispatch_info().weight <= T::MaxEnactmentWeight::get(),
+				Error::<T, I>::EnactmentTooHeavy
+			);
+			Ok(call)
+		}
+
+		// The next transaction the offchain worker should submit to settle ended proposals, as of
+		// block `now`: finalizing them, then paying out their pots or refunding their votes.
+		fn next_settlement(now: T::BlockNumber) -> Option<Call<T, I>> {
+			// Proposals with a pot wait for a free slot in `DuePayouts`, which payouts make.
+			let due_full =
+				DuePayouts::<T, I>::decode_len().unwrap_or(0) as u32 >= T::MaxDuePayouts::get();
+			// Calls are executed in the next block at the earliest.
+			if let Some((_, proposal_index)) =
+				ActiveProposals::<T, I>::get().into_iter().find(|(end, proposal_index)| {
+					*end <= now && !(due_full && Pots::<T, I>::contains_key(proposal_index))
+				}) {
+				return Some(Call::finalize { proposal_index })
+			}
+			if let Some((_, proposal_index)) =
+				DuePayouts::<T, I>::get().first().filter(|(payable_after, _)| *payable_after <= now)
+			{
+				return Some(Call::payout_shares { proposal_index: *proposal_index })
+			}
+			PendingRefunds::<T, I>::iter_keys()
+				.next()
+				.map(|proposal_index| Call::refund_votes { proposal_index })
+		}
+
+		// Check that `permit` is signed by its voter and has not expired. Its nonce is checked by
+		// the callers, since the pool also accepts permits with later nonces.
+		fn check_permit(
+			permit: &VotePermit<T::AccountId, T::BlockNumber>,
+			signature: &T::PermitSignature,
+		) -> Result<(), Error<T, I>> {
+			let now = <frame_system::Pallet<T>>::block_number();
+			ensure!(now <= permit.deadline, Error::<T, I>::PermitExpired);
+			ensure!(
+				signature.verify(&Self::permit_payload(permit)[..], &permit.voter),
+				Error::<T, I>::BadPermitSignature
+			);
+			Ok(())
+		}
+
+		// Count a successful call of `who` on the proposal towards its fee-less calls on it, and
+		// return whether it pays a fee.
+		fn use_free_call(who: &T::AccountId, proposal_index: ProposalIndex) -> Pays {
+			if T::VoterEligibility::eligibility(who).is_err() {
+				return Pays::Yes
+			}
+			let calls = Self::free_calls(proposal_index, who);
+			if calls >= T::MaxFreeCalls::get() {
+				return Pays::Yes
+			}
+			FreeCalls::<T, I>::insert(proposal_index, who, calls + 1);
+			Pays::No
+		}
+
+		// Eligibility and free balance of `who` at this point.
+		fn current_checkpoint(who: &T::AccountId) -> Checkpoint<BalanceOf<T, I>> {
+			Checkpoint {
+				eligibility: T::VoterEligibility::eligibility(who),
+				balance: T::Currency::free_balance(who),
+			}
+		}
+
+		// Whether balance changes are recorded in the current block.
+		fn is_recording() -> bool {
+			let now = <frame_system::Pallet<T>>::block_number();
+			Self::recording_period().map_or(false, |(_, last)| now <= last)
+		}
+
+		// Record the state of `who` at block zero if it has no checkpoint yet. Balance changes are
+		// recorded while snapshot proposals are open and drop the checkpoints otherwise, so an
+		// account without checkpoints has had its current state since the recording started.
+		fn ensure_checkpoint(who: &T::AccountId) {
+			if Checkpoints::<T, I>::decode_len(who).unwrap_or(0) == 0 {
+				let checkpoint = Self::current_checkpoint(who);
+				Checkpoints::<T, I>::mutate(who, |checkpoints| {
+					// Only fails if `MaxCheckpoints` is zero.
+					let _ = checkpoints.try_push((Zero::zero(), checkpoint));
+				});
+			}
+		}
+
+		// Record the current eligibility and free balance of `who`, replacing a checkpoint that
+		// was already recorded in this block.
+		fn record_checkpoint(who: &T::AccountId) {
+			Self::ensure_checkpoint(who);
+			let now = <frame_system::Pallet<T>>::block_number();
+			let checkpoint = Self::current_checkpoint(who);
+			Checkpoints::<T, I>::mutate(who, |checkpoints| {
+				if checkpoints.last().map_or(false, |(_, last)| *last == checkpoint) {
+					return
+				}
+				if checkpoints.last().map_or(false, |(block, _)| *block == now) {
+					checkpoints.remove(checkpoints.len() - 1);
+				}
+				if !checkpoints.is_empty() && checkpoints.len() as u32 >= T::MaxCheckpoints::get() {
+					checkpoints.remove(Self::unused_checkpoint(checkpoints, now));
+				}
+				// Only fails if `MaxCheckpoints` is zero.
+				let _ = checkpoints.try_push((now, checkpoint));
+			});
+		}
+
+		// Position of the oldest of `checkpoints` that no snapshot will look up anymore, or of the
+		// oldest one if all of them may still be looked up. Dropping a checkpoint that is still
+		// needed makes the lookup fail, which rejects the vote.
+		//
+		// A checkpoint is current until the block before the next one. Proposals created from now
+		// on take their snapshot at the previous block at the earliest, so only the snapshots of
+		// unfinalized proposals can fall into checkpoints superseded before it.
+		fn unused_checkpoint(
+			checkpoints: &[(T::BlockNumber, Checkpoint<BalanceOf<T, I>>)],
+			now: T::BlockNumber,
+		) -> usize {
+			let previous = now.saturating_sub(One::one());
+			let snapshots: Vec<T::BlockNumber> = ActiveProposals::<T, I>::get()
+				.into_iter()
+				.filter_map(|(_, index)| Self::snapshots(index))
+				.collect();
+			checkpoints
+				.windows(2)
+				.position(|pair| {
+					let (from, until) = (pair[0].0, pair[1].0);
+					until <= previous &&
+						!snapshots.iter().any(|snapshot| from <= *snapshot && *snapshot < until)
+				})
+				.unwrap_or(0)
+		}
+
+		// The checkpoint of `who` that was current at the end of block `at`, or `None` if it was
+		// dropped to make room for newer ones.
+		fn checkpoint_at(
+			who: &T::AccountId,
+			at: T::BlockNumber,
+		) -> Option<Checkpoint<BalanceOf<T, I>>> {
+			let checkpoints = Checkpoints::<T, I>::get(who);
+			if checkpoints.is_empty() {
+				return Some(Self::current_checkpoint(who))
+			}
+			let position = checkpoints.partition_point(|(block, _)| *block <= at);
+			position.checked_sub(1).map(|position| checkpoints[position].1.clone())
+		}
+
+		// Block after which the shares of `pot` are payable.
+		fn payable_after(
+			p: &Proposal<T::AccountId, T::BlockNumber>,
+			pot: &Pot<BalanceOf<T, I>>,
+		) -> T::BlockNumber {
+			match pot.target {
+				Redistribution::Voters => p.end,
+				Redistribution::Identities => p.end + T::ClaimPeriod::get(),
+			}
+		}
+
+		// Transfer `amount` from the pot account to `who`, recording checkpoints of `who` around it
+		// for backends that don't report the transfer themselves.
+		fn pay_out(who: &T::AccountId, amount: BalanceOf<T, I>) -> DispatchResult {
+			Self::before_change(who);
+			T::Currency::transfer(&Self::account_id(), who, amount)?;
+			Self::after_change(who);
+			Ok(())
+		}
+
+		// Forget the payout of the pot of the proposal once it has no shares left to pay out.
+		fn remove_due_payout(proposal_index: ProposalIndex) {
+			DuePayouts::<T, I>::mutate(|due| due.retain(|(_, index)| *index != proposal_index));
+		}
+
+		// Pay `who` an equal share of what is left in the pot of the proposal. If the transfer
+		// fails and `keep_unpaid` is set, the share is taken out of the pot and kept in
+		// `UnpaidShares` instead, so one recipient can't hold up a batch.
+		fn pay_share(proposal_index: u32, who: &T::AccountId, keep_unpaid: bool) -> DispatchResult {
+			Pots::<T, I>::try_mutate(proposal_index, |maybe_pot| -> DispatchResult {
+				let pot = maybe_pot.as_mut().ok_or(Error::<T, I>::NoPot)?;
+				ensure!(pot.recipients > 0, Error::<T, I>::NotRecipient);
+				// Dividing what is left by the number of outstanding recipients hands the rounding
+				// remainder to the last recipient, so the pot is always emptied completely.
+				let share = pot.remaining / pot.recipients.into();
+				match Self::pay_out(who, share) {
+					Ok(()) =>
+						Self::deposit_event(Event::SharePaid(proposal_index, who.clone(), share)),
+					Err(error) if keep_unpaid => {
+						UnpaidShares::<T, I>::insert(proposal_index, who, share);
+						Self::deposit_event(Event::ShareUnpaid(
+							proposal_index,
+							who.clone(),
+							share,
+							error,
+						));
+					},
+					Err(error) => return Err(error),
+				}
+				pot.remaining = pot.remaining.saturating_sub(share);
+				pot.recipients -= 1;
+				PotRecipients::<T, I>::remove(proposal_index, who);
+				if pot.recipients == 0 {
+					Self::remove_due_payout(proposal_index);
+				}
+				// Being paid out settles the vote of a voter on the proposal.
+				Self::prune_voted_proposal(who, proposal_index);
+				Ok(())
+			})
+		}
+
+		// Forget that `who` voted on the proposal once the funds of its vote are released. No call
+		// of `who` on the proposal can succeed anymore, so its fee-less calls are forgotten too.
+		fn prune_voted_proposal(who: &T::AccountId, proposal_index: u32) {
+			FreeCalls::<T, I>::remove(proposal_index, who);
+			VotedProposals::<T, I>::mutate_exists(who, |maybe_voted| {
+				if let Some(voted) = maybe_voted {
+					if let Ok(position) = voted.binary_search(&proposal_index) {
+						voted.remove(position);
+					}
+					if voted.is_empty() {
+						*maybe_voted = None;
+					}
+				}
+			});
+		}
+
+		/// Status of the proposal `p` at the current block.
+		pub fn proposal_status(p: &Proposal<T::AccountId, T::BlockNumber>) -> ProposalStatus {
+			if <frame_system::Pallet<T>>::block_number() <= p.end {
+				ProposalStatus::Voting
+			} else if p.aye > p.nay {
+				ProposalStatus::Passed
+			} else {
+				ProposalStatus::Rejected
+			}
+		}
+
+		/// The proposal at `index` together with its current status.
+		pub fn proposal_info(index: ProposalIndex) -> Option<ProposalInfo<T::AccountId, T::BlockNumber>> {
+			Self::proposals(index).map(|proposal| ProposalInfo {
+				index,
+				status: Self::proposal_status(&proposal),
+				proposal,
+			})
+		}
+
+		/// Indexes of all proposals that are still open for voting, ordered by the block their
+		/// voting ends at.
+		pub fn active_proposals() -> Vec<ProposalIndex> {
+			let now = <frame_system::Pallet<T>>::block_number();
+			ActiveProposals::<T, I>::get()
+				.into_iter()
+				.filter(|(end, _)| now <= *end)
+				.map(|(_, index)| index)
+				.collect()
+		}
+
+		/// Indexes of all proposals created by `who`, in ascending order.
+		pub fn proposals_by(who: &T::AccountId) -> Vec<ProposalIndex> {
+			let mut proposals: Vec<ProposalIndex> =
+				ProposalsByProposer::<T, I>::iter_key_prefix(who).collect();
+			proposals.sort();
+			proposals
+		}
+
+		/// Check that the proposal indexes, tallies and reserves are consistent with `Proposals`
+		/// and `Votes`, and that reserved vote costs are on hold in the currency.
+		#[cfg(any(feature = "try-runtime", test))]
+		pub fn do_try_state() -> Result<(), &'static str> {
+			let count = Self::proposal_count().unwrap_or(0);
+			let active = ActiveProposals::<T, I>::get();
+			ensure!(
+				active.windows(2).all(|pair| pair[0] < pair[1]),
+				"ActiveProposals is not strictly ordered by end block"
+			);
+			for (end, index) in active.iter() {
+				let p = Self::proposals(index).ok_or("ActiveProposals entry without proposal")?;
+				ensure!(p.end == *end, "ActiveProposals entry does not match proposal end");
+			}
+			for (index, ()) in OverflowProposals::<T, I>::iter() {
+				ensure!(
+					Proposals::<T, I>::contains_key(index),
+					"OverflowProposals entry without proposal"
+				);
+			}
+			for (proposer, index, ()) in ProposalsByProposer::<T, I>::iter() {
+				let p = Self::proposals(index).ok_or("ProposalsByProposer entry without proposal")?;
+				ensure!(p.proposer == proposer, "ProposalsByProposer entry for wrong proposer");
+			}
+			for (index, p) in Proposals::<T, I>::iter() {
+				ensure!(index < count, "Proposal index is not below ProposalCount");
+				ensure!(
+					ProposalsByProposer::<T, I>::contains_key(&p.proposer, index),
+					"Proposal missing from ProposalsByProposer"
+				);
+				let (mut aye, mut nay, mut total) = (0u128, 0u128, 0u128);
+				for vote in Votes::<T, I>::iter_prefix_values(index) {
+					match vote.side {
+						Some(VoteSide::Aye) => aye = aye.saturating_add(vote.votes),
+						Some(VoteSide::Nay) => nay = nay.saturating_add(vote.votes),
+						None => (),
+					}
+					total = total.saturating_add(vote.votes);
+				}
+				// Migrated votes may not know their side, so only their total has to match.
+				ensure!(aye <= p.aye && nay <= p.nay, "Recorded votes exceed proposal tallies");
+				ensure!(
+					total == p.aye.saturating_add(p.nay),
+					"Proposal tallies do not match recorded votes"
+				);
+			}
+			for (index, _) in Enactments::<T, I>::iter() {
+				ensure!(Proposals::<T, I>::contains_key(index), "Enactment without proposal");
+			}
+			for (_, checkpoints) in Checkpoints::<T, I>::iter() {
+				ensure!(
+					checkpoints.windows(2).all(|pair| pair[0].0 < pair[1].0),
+					"Checkpoints are not strictly ordered by block"
+				);
+			}
+			for (who, voted) in VotedProposals::<T, I>::iter() {
+				ensure!(
+					voted.windows(2).all(|pair| pair[0] < pair[1]),
+					"VotedProposals is not strictly ordered"
+				);
+				for index in voted.iter() {
+					ensure!(
+						Self::votes(index, &who).map_or(false, |vote| !vote.released),
+						"VotedProposals entry without unreleased vote"
+					);
+				}
+			}
+			// Costs of votes that are held rather than paid into a pot, by voter.
+			let mut reserved = BTreeMap::<T::AccountId, BalanceOf<T, I>>::new();
+			for (index, who, vote) in Votes::<T, I>::iter() {
+				if !vote.released && !Pots::<T, I>::contains_key(index) {
+					let total = reserved.entry(who).or_insert_with(Zero::zero);
+					*total = total.saturating_add(vote.cost);
+				}
+			}
+			for (who, cost) in reserved.iter() {
+				ensure!(
+					T::Currency::held_balance(who) >= *cost,
+					"Reserved votes are not on hold in the currency"
+				);
+			}
+			for (who, stats) in VoterStatistics::<T, I>::iter() {
+				ensure!(
+					stats.outstanding_reserves == reserved.remove(&who).unwrap_or_else(Zero::zero),
+					"VoterStatistics outstanding reserves do not match reserved votes"
+				);
+			}
+			ensure!(
+				reserved.values().all(|cost| cost.is_zero()),
+				"Reserved votes of an account without VoterStatistics"
+			);
+			Ok(())
+		}
+
+		/// The vote `who` cast on the proposal at `index`.
+		pub fn vote_of(
+			index: ProposalIndex,
+			who: &T::AccountId,
+		) -> Option<VoteInfo<BalanceOf<T, I>, T::BlockNumber>> {
+			Self::votes(index, who).map(|vote| VoteInfo {
+				vote,
+				paid_into_pot: Pots::<T, I>::contains_key(index),
+			})
+		}
+
+		/// Votes of `who` whose funds have not been released yet, together with its aggregate
+		/// statistics.
+		pub fn account_history(
+			who: &T::AccountId,
+		) -> AccountHistory<BalanceOf<T, I>, T::BlockNumber> {
+			let votes = Self::voted_proposals(who)
+				.into_iter()
+				.filter_map(|index| Self::vote_of(index, who).map(|vote| (index, vote)))
+				.collect();
+			AccountHistory { stats: Self::voter_stats(who), votes }
+		}
+
+		/// Message the voter of `permit` signs.
+		///
+		/// It is the encoding of the genesis hash, the pallet id of this instance, and the
+		/// proposal index, side, votes, nonce and deadline of the permit, so permits can't be
+		/// replayed on another chain or instance.
+		pub fn permit_payload(permit: &VotePermit<T::AccountId, T::BlockNumber>) -> Vec<u8> {
+			let genesis_hash = <frame_system::Pallet<T>>::block_hash(T::BlockNumber::zero());
+			(
+				genesis_hash,
+				T::PalletId::get(),
+				permit.proposal_index,
+				permit.side,
+				permit.votes,
+				permit.nonce,
+				permit.deadline,
+			)
+				.encode()
+		}
+
+		/// Transaction pool validity of `call` by `who` if it is expected to succeed without a fee.
+		///
+		/// Such calls get `FreeCallPriority`, and at most one of them per account and proposal is
+		/// kept in the pool at a time. Returns `None` for calls that pay a fee, or are expected to fail.
+		pub fn free_call_validity(
+			who: &T::AccountId,
+			call: &Call<T, I>,
+		) -> Option<ValidTransaction> {
+			let proposal_index = match call {
+				Call::vote_aye { proposal_index, votes } |
+				Call::vote_nay { proposal_index, votes } => {
+					let (_, cap) = Self::check_eligibility(who, *proposal_index).ok()?;
+					if *votes == 0 || cap.map_or(false, |cap| *votes > cap) {
+						return None
+					}
+					*proposal_index
+				},
+				Call::unreserve { proposal_index } => {
+					T::VoterEligibility::eligibility(who).ok()?;
+					let p = Self::proposals(proposal_index)?;
+					let now = <frame_system::Pallet<T>>::block_number();
+					let reserved = Self::votes(proposal_index, who)
+						.map_or(false, |vote| !vote.released) &&
+						!Pots::<T, I>::contains_key(proposal_index);
+					if now <= p.end || !reserved {
+						return None
+					}
+					*proposal_index
+				},
+				_ => return None,
+			};
+			let calls = Self::free_calls(proposal_index, who);
+			if calls >= T::MaxFreeCalls::get() {
+				return None
+			}
+			ValidTransaction::with_tag_prefix("QuadraticVotingFreeCall")
+				.priority(T::FreeCallPriority::get())
+				.and_provides((T::PalletId::get(), who, proposal_index, calls))
+				.build()
+				.ok()
+		}
+
+		/// Amount of `who` that is on hold for votes, to be reported by backends that freeze it.
+		pub fn frozen_balance(who: &T::AccountId) -> BalanceOf<T, I> {
+			Self::voter_stats(who).outstanding_reserves
+		}
+
+		/// All votes cast on the proposal at `index`, including released ones.
+		pub fn votes_on(
+			index: ProposalIndex,
+		) -> Vec<(T::AccountId, VoteRecord<BalanceOf<T, I>, T::BlockNumber>)> {
+			Votes::<T, I>::iter_prefix(index).collect()
+		}
+
+		/// Cost of casting `votes` votes, or `None` if it does not fit into the balance type.
+		pub fn quote_cost(votes: u128) -> Option<BalanceOf<T, I>> {
+			Self::calculate_price(votes).and_then(Self::u128_to_balance)
+		}
+
+		/// Whether `who` can vote on the proposal at `index`, and the reason if it can't.
+		pub fn eligibility(index: ProposalIndex, who: &T::AccountId) -> Result<(), Ineligibility> {
+			Self::check_eligibility(who, index).map(|_| ())
+		}
+
+		/// Cast a vote of `who` and roll back all of its effects.
+		pub fn dry_run_vote(
+			index: ProposalIndex,
+			who: T::AccountId,
+			aye: bool,
+			votes: u128,
+		) -> DispatchResult {
+			with_transaction(|| {
+				let origin = frame_system::RawOrigin::Signed(who).into();
+				let result = if aye {
+					Self::vote_aye(origin, index, votes)
+				} else {
+					Self::vote_nay(origin, index, votes)
+				};
+				TransactionOutcome::Rollback(Ok(result.map(|_| ()).map_err(|e| e.error)))
+			})
+			.unwrap_or_else(Err)
+		}
 	}
 	
 }
-
@@ -0,0 +1,212 @@
+//! Storage migrations for the quadratic voting pallet.
+
+use crate::{
+	pallet::{ActiveProposals, OverflowProposals, Proposals, ProposalsByProposer},
+	Config, Pallet,
+};
+use frame_support::{
+	pallet_prelude::*,
+	traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion},
+	weights::Weight,
+};
+use sp_std::{collections::btree_map::BTreeMap, marker::PhantomData, prelude::*};
+
+const LOG_TARGET: &str = "runtime::quadratic-voting";
+
+/// Migration to storage version 1, which builds the `ActiveProposals` and `ProposalsByProposer`
+/// indexes from the existing proposals.
+pub mod v1 {
+	use super::*;
+
+	/// Populate the proposal indexes.
+	///
+	/// Proposals could not be finalized before this version, so every proposal is added to
+	/// `ActiveProposals`, including ended ones that still await finalization. If there are more of
+	/// them than `MaxActiveProposals`, the ones ending first are kept and the others go to
+	/// `OverflowProposals`, from where they are finalized like active ones. Proposers with more
+	/// proposals than `MaxProposalsPerProposer` keep all of them, but cannot create new ones.
+	pub struct MigrateToV1<T, I = ()>(PhantomData<(T, I)>);
+
+	impl<T: Config<I>, I: 'static> OnRuntimeUpgrade for MigrateToV1<T, I> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T, I>::on_chain_storage_version() >= 1 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let mut reads = 1u64;
+			let mut writes = 2u64;
+			let mut active = Vec::new();
+			let mut created = BTreeMap::<T::AccountId, u32>::new();
+			for (index, p) in Proposals::<T, I>::iter() {
+				reads += 1;
+				ProposalsByProposer::<T, I>::insert(&p.proposer, index, ());
+				writes += 1;
+				*created.entry(p.proposer).or_default() += 1;
+				active.push((p.end, index));
+			}
+			active.sort();
+			let max_active = T::MaxActiveProposals::get() as usize;
+			let overflow = active.split_off(max_active.min(active.len()));
+			if !overflow.is_empty() {
+				log::warn!(
+					target: LOG_TARGET,
+					"{} proposals do not fit into ActiveProposals and are kept in OverflowProposals",
+					overflow.len(),
+				);
+			}
+			for (_, index) in overflow {
+				OverflowProposals::<T, I>::insert(index, ());
+				writes += 1;
+			}
+			let over_bound = created
+				.values()
+				.filter(|count| **count > T::MaxProposalsPerProposer::get())
+				.count();
+			if over_bound > 0 {
+				log::warn!(
+					target: LOG_TARGET,
+					"{} accounts have more than MaxProposalsPerProposer proposals",
+					over_bound,
+				);
+			}
+			let active: BoundedVec<_, T::MaxActiveProposals> =
+				active.try_into().expect("split at the bound above; qed");
+			ActiveProposals::<T, I>::put(active);
+			StorageVersion::new(1).put::<Pallet<T, I>>();
+
+			T::DbWeight::get().reads_writes(reads, writes)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			ensure!(Pallet::<T, I>::on_chain_storage_version() >= 1, "storage version not updated");
+			Pallet::<T, I>::do_try_state()
+		}
+	}
+}
+
+/// Migration to storage version 2, which moves the reserved vote costs of `ReservedTokens` into
+/// `Votes` records.
+pub mod v2 {
+	use super::*;
+	use crate::{pallet::Votes, AccountIdOf, BalanceOf, VoteRecord, VoteSide};
+	use frame_support::storage_alias;
+	use sp_runtime::traits::{IntegerSquareRoot, SaturatedConversion};
+
+	#[storage_alias]
+	type ReservedTokens<T: Config<I>, I: 'static> =
+		StorageMap<Pallet<T, I>, Blake2_128Concat, (u32, AccountIdOf<T>), BalanceOf<T, I>>;
+
+	/// Move vote costs into `Votes` records.
+	///
+	/// The old layout did not store the number of votes, the side or the block of a vote. The
+	/// number of votes is recovered from the quadratic cost. The side is inferred from the
+	/// proposal tallies when all tallied votes on the proposal are on the same side, and left
+	/// empty otherwise, including when nothing was tallied. The block is set to the block of the migration.
+	pub struct MigrateToV2<T, I = ()>(PhantomData<(T, I)>);
+
+	impl<T: Config<I>, I: 'static> MigrateToV2<T, I> {
+		fn record(
+			proposal_index: u32,
+			cost: BalanceOf<T, I>,
+			at_block: T::BlockNumber,
+		) -> VoteRecord<BalanceOf<T, I>, T::BlockNumber> {
+			// Proposals without any tallied votes tell nothing about the side.
+			let side = Proposals::<T, I>::get(proposal_index).and_then(|p| match (p.aye, p.nay) {
+				(aye, 0) if aye > 0 => Some(VoteSide::Aye),
+				(0, nay) if nay > 0 => Some(VoteSide::Nay),
+				_ => None,
+			});
+			let votes = cost.saturated_into::<u128>().integer_sqrt();
+			VoteRecord { side, votes, cost, at_block, released: false }
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> OnRuntimeUpgrade for MigrateToV2<T, I> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T, I>::on_chain_storage_version() >= 2 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let now = <frame_system::Pallet<T>>::block_number();
+			let mut migrated = 0u64;
+			for ((proposal_index, who), cost) in ReservedTokens::<T, I>::drain() {
+				let record = Self::record(proposal_index, cost, now);
+				Votes::<T, I>::insert(proposal_index, who, record);
+				migrated += 1;
+			}
+			StorageVersion::new(2).put::<Pallet<T, I>>();
+
+			T::DbWeight::get().reads_writes(1 + 2 * migrated, 1 + 2 * migrated)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			ensure!(Pallet::<T, I>::on_chain_storage_version() >= 2, "storage version not updated");
+			ensure!(ReservedTokens::<T, I>::iter().next().is_none(), "ReservedTokens not drained");
+			Pallet::<T, I>::do_try_state()
+		}
+	}
+}
+
+/// Migration to storage version 3, which builds the `VotedProposals` index and the
+/// `VoterStatistics` of every account from the existing `Votes`.
+pub mod v3 {
+	use super::*;
+	use crate::{
+		pallet::{Pots, VotedProposals, VoterStatistics, Votes},
+		BalanceOf, VoterStats,
+	};
+	use sp_runtime::traits::Saturating;
+
+	/// Populate the per-account participation history.
+	///
+	/// Only votes whose funds are still reserved are indexed, since votes paid into a pot before
+	/// this migration cannot tell whether their share was already paid out. If an account has
+	/// more of them than `MaxVotedProposals`, the lowest proposal indexes are kept. Statistics
+	/// count every stored vote.
+	pub struct MigrateToV3<T, I = ()>(PhantomData<(T, I)>);
+
+	impl<T: Config<I>, I: 'static> OnRuntimeUpgrade for MigrateToV3<T, I> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T, I>::on_chain_storage_version() >= 3 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let mut reads = 1u64;
+			let mut voted = BTreeMap::<T::AccountId, Vec<u32>>::new();
+			let mut stats = BTreeMap::<T::AccountId, VoterStats<BalanceOf<T, I>>>::new();
+			for (proposal_index, who, vote) in Votes::<T, I>::iter() {
+				reads += 2;
+				let entry = stats.entry(who.clone()).or_insert_with(VoterStats::default);
+				entry.votes_cast = entry.votes_cast.saturating_add(vote.votes);
+				entry.credits_spent = entry.credits_spent.saturating_add(vote.cost);
+				if !Pots::<T, I>::contains_key(proposal_index) {
+					entry.outstanding_reserves =
+						entry.outstanding_reserves.saturating_add(vote.cost);
+					voted.entry(who).or_default().push(proposal_index);
+				}
+			}
+			let writes = (voted.len() + stats.len()) as u64 + 1;
+			for (who, mut indexes) in voted {
+				indexes.sort();
+				indexes.truncate(T::MaxVotedProposals::get() as usize);
+				let indexes: BoundedVec<_, T::MaxVotedProposals> =
+					indexes.try_into().expect("truncated to the bound above; qed");
+				VotedProposals::<T, I>::insert(who, indexes);
+			}
+			for (who, entry) in stats {
+				VoterStatistics::<T, I>::insert(who, entry);
+			}
+			StorageVersion::new(3).put::<Pallet<T, I>>();
+
+			T::DbWeight::get().reads_writes(reads, writes)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			ensure!(Pallet::<T, I>::on_chain_storage_version() == 3, "storage version not updated");
+			Pallet::<T, I>::do_try_state()
+		}
+	}
+}
@@ -1,15 +1,20 @@
 use crate as pallet_quadratic_voting;
+use crate::{
+	currency::{ChangedAccounts, CheckpointOnChange, FrozenAsset, NativeReserve},
+	eligibility::{Capped, HasIdentity},
+};
 use frame_support::{
-	parameter_types, BoundedVec, assert_ok,
-	traits::{ConstU16, ConstU64}
+	parameter_types, BoundedVec, assert_ok, PalletId,
+	instances::Instance1,
+	traits::{ConstU128, ConstU16, ConstU32, ConstU64, ConstU8, EqualPrivilegeOnly}
 };
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
-	testing::Header,
-	traits::{BlakeTwo256, IdentityLookup},
+	testing::{Header, TestSignature, TestXt, UintAuthorityId},
+	traits::{AccountIdConversion, BlakeTwo256, Extrinsic as ExtrinsicT, IdentityLookup},
 };
-use frame_system::{EnsureRoot};
+use frame_system::{EnsureRoot, RawOrigin};
 use pallet_identity::{Data, IdentityInfo};
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
@@ -28,6 +33,10 @@ frame_support::construct_runtime!(
 		QuadraticVoting: pallet_quadratic_voting,
 		Identity: pallet_identity,
 		Balances: pallet_balances,
+		Guild: pallet_quadratic_voting::<Instance1>,
+		Assets: pallet_assets,
+		Scheduler: pallet_scheduler,
+		Preimage: pallet_preimage,
 	}
 );
 
@@ -58,6 +67,11 @@ impl system::Config for Test {
 	type MaxConsumers = frame_support::traits::ConstU32<16>;
 }
 
+parameter_types! {
+	// Tests that need a larger existential deposit set it before building the genesis storage.
+	pub static ExistentialDeposit: u64 = 1;
+}
+
 impl pallet_balances::Config for Test {
 	type MaxLocks = ();
 	type MaxReserves = ();
@@ -65,8 +79,8 @@ impl pallet_balances::Config for Test {
 	type Balance = u64;
 	type Event = Event;
 	type DustRemoval = ();
-	type ExistentialDeposit = ConstU64<1>;
-	type AccountStore = System;
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = CheckpointOnChange<System, QuadraticVoting>;
 	type WeightInfo = ();
 }
 
@@ -95,32 +109,229 @@ impl pallet_identity::Config for Test {
 	type WeightInfo = ();
 }
 
-pub struct VotingIdentityVerifier;
-impl crate::IdentityVerifier<AccountId> for VotingIdentityVerifier {
-	fn has_identity(who: &AccountId, fields: u64) -> bool {
-		Identity::has_identity(who, fields)
+pub type Extrinsic = TestXt<Call, ()>;
+
+impl frame_system::offchain::SigningTypes for Test {
+	type Public = UintAuthorityId;
+	type Signature = TestSignature;
+}
+
+impl<LocalCall> frame_system::offchain::SendTransactionTypes<LocalCall> for Test
+where
+	Call: From<LocalCall>,
+{
+	type OverarchingCall = Call;
+	type Extrinsic = Extrinsic;
+}
+
+impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for Test
+where
+	Call: From<LocalCall>,
+{
+	fn create_transaction<C: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: Call,
+		_public: UintAuthorityId,
+		account: AccountId,
+		_nonce: u64,
+	) -> Option<(Call, <Extrinsic as ExtrinsicT>::SignaturePayload)> {
+		Some((call, (account, ())))
+	}
+}
+
+// Offchain worker keys, which sign with the test keys set through `UintAuthorityId::set_all_keys`.
+pub struct TestAuthId;
+
+impl frame_system::offchain::AppCrypto<UintAuthorityId, TestSignature> for TestAuthId {
+	type RuntimeAppPublic = UintAuthorityId;
+	type GenericPublic = UintAuthorityId;
+	type GenericSignature = TestSignature;
+}
+
+// Asset that the guild charges vote costs in.
+pub const GUILD_ASSET: u32 = 7;
+
+// Reports the votes of guild members on hold as frozen guild assets.
+pub struct GuildFreezer;
+
+impl pallet_assets::FrozenBalance<u32, AccountId, u64> for GuildFreezer {
+	fn frozen_balance(asset: u32, who: &AccountId) -> Option<u64> {
+		let frozen = Guild::frozen_balance(who);
+		if asset == GUILD_ASSET && frozen > 0 {
+			Some(frozen)
+		} else {
+			None
+		}
+	}
+
+	fn died(_asset: u32, _who: &AccountId) {}
+}
+
+// Accounts whose guild assets a transfer moves, for the checkpoints of the guild.
+pub struct GuildTransfers;
+
+impl ChangedAccounts<AccountId, Call> for GuildTransfers {
+	fn changed_accounts(who: &AccountId, call: &Call) -> Vec<AccountId> {
+		match call {
+			Call::Assets(pallet_assets::Call::transfer { id: GUILD_ASSET, target, .. }) =>
+				vec![*who, *target],
+			_ => Vec::new(),
+		}
 	}
 }
 
+impl pallet_assets::Config for Test {
+	type Event = Event;
+	type Balance = u64;
+	type AssetId = u32;
+	type Currency = Balances;
+	type ForceOrigin = EnsureRoot<AccountId>;
+	type AssetDeposit = ConstU64<1>;
+	type AssetAccountDeposit = ConstU64<1>;
+	type MetadataDepositBase = ConstU64<1>;
+	type MetadataDepositPerByte = ConstU64<1>;
+	type ApprovalDeposit = ConstU64<1>;
+	type StringLimit = ConstU32<50>;
+	type Freezer = GuildFreezer;
+	type Extra = ();
+	type WeightInfo = ();
+}
+
+impl pallet_preimage::Config for Test {
+	type WeightInfo = ();
+	type Event = Event;
+	type Currency = Balances;
+	type ManagerOrigin = EnsureRoot<AccountId>;
+	type MaxSize = ConstU32<1024>;
+	type BaseDeposit = ConstU64<1>;
+	type ByteDeposit = ConstU64<0>;
+}
+
+impl pallet_scheduler::Config for Test {
+	type Event = Event;
+	type Origin = Origin;
+	type PalletsOrigin = OriginCaller;
+	type Call = Call;
+	type MaximumWeight = ConstU64<2_000_000_000_000>;
+	type ScheduleOrigin = EnsureRoot<AccountId>;
+	type OriginPrivilegeCmp = EqualPrivilegeOnly;
+	type MaxScheduledPerBlock = ConstU32<10>;
+	type WeightInfo = ();
+	type PreimageProvider = Preimage;
+	type NoPreimagePostponement = ();
+}
+
 parameter_types! {
 	pub const VotingPeriod: u64 = 10; // Number of blocks that voting period lasts
+	pub const QuadraticVotingPalletId: PalletId = PalletId(*b"py/qvote");
+	pub const GuildVotingPeriod: u64 = 5;
+	pub const GuildPalletId: PalletId = PalletId(*b"py/qvgld");
+	pub const GuildAsset: u32 = GUILD_ASSET;
+	pub RootEnactment: RawOrigin<AccountId> = RawOrigin::Root;
+	pub GuildEnactment: RawOrigin<AccountId> =
+		RawOrigin::Signed(GuildPalletId::get().into_account_truncating());
 }
 
 impl pallet_quadratic_voting::Config for Test {
 	type Event = Event;
-	type Currency = Balances;
-	type IdentityVerifier = VotingIdentityVerifier;
+	type Currency = NativeReserve<Balances>;
+	type VoterEligibility = HasIdentity<Test>;
 	type VotingPeriod = VotingPeriod;
+	type PalletId = QuadraticVotingPalletId;
+	type ClaimPeriod = ConstU64<5>;
+	type MaxPayoutsPerBatch = ConstU32<2>;
+	type MaxDuePayouts = ConstU32<2>;
+	type MaxActiveProposals = ConstU32<3>;
+	type MaxProposalsPerProposer = ConstU32<4>;
+	type MaxVotedProposals = ConstU32<3>;
+	type MaxCheckpoints = ConstU32<3>;
+	type MaxFreeCalls = ConstU32<1>;
+	type FreeCallPriority = ConstU64<1>;
+	type PermitSignature = TestSignature;
+	type PermitSigner = UintAuthorityId;
+	type AuthorityId = TestAuthId;
+	type Proposal = Call;
+	type PalletsOrigin = OriginCaller;
+	type Scheduler = Scheduler;
+	type Preimages = Preimage;
+	type EnactmentDelay = ConstU64<2>;
+	type MaxEnactmentWeight = ConstU64<1_000_000_000>;
+	type EnactmentOrigin = RootEnactment;
+	type EnactmentPriority = ConstU8<63>;
+	type MinEnactmentTurnout = ConstU128<2>;
+	type CancelOrigin = EnsureRoot<AccountId>;
+}
+
+impl pallet_quadratic_voting::Config<Instance1> for Test {
+	type Event = Event;
+	type Currency = FrozenAsset<Assets, GuildAsset>;
+	type VoterEligibility = Capped<HasIdentity<Test>, ConstU128<2>>;
+	type VotingPeriod = GuildVotingPeriod;
+	type PalletId = GuildPalletId;
+	type ClaimPeriod = ConstU64<5>;
+	type MaxPayoutsPerBatch = ConstU32<2>;
+	type MaxDuePayouts = ConstU32<2>;
+	type MaxActiveProposals = ConstU32<3>;
+	type MaxProposalsPerProposer = ConstU32<4>;
+	type MaxVotedProposals = ConstU32<3>;
+	type MaxCheckpoints = ConstU32<3>;
+	type MaxFreeCalls = ConstU32<2>;
+	type FreeCallPriority = ConstU64<1>;
+	type PermitSignature = TestSignature;
+	type PermitSigner = UintAuthorityId;
+	type AuthorityId = TestAuthId;
+	type Proposal = Call;
+	type PalletsOrigin = OriginCaller;
+	type Scheduler = Scheduler;
+	type Preimages = Preimage;
+	type EnactmentDelay = ConstU64<2>;
+	type MaxEnactmentWeight = ConstU64<1_000_000_000>;
+	type EnactmentOrigin = GuildEnactment;
+	type EnactmentPriority = ConstU8<63>;
+	type MinEnactmentTurnout = ConstU128<1>;
+	type CancelOrigin = EnsureRoot<AccountId>;
 }
 
 
+// Default identity info set up for the predefined accounts.
+pub fn identity_info() -> IdentityInfo<MaxAdditionalFields> {
+	IdentityInfo {
+		additional: BoundedVec::default(),
+		display: Data::Raw(b"name".to_vec().try_into().unwrap()),
+		legal: Data::default(),
+		web: Data::Raw(b"website".to_vec().try_into().unwrap()),
+		riot: Data::default(),
+		email: Data::default(),
+		pgp_fingerprint: None,
+		image: Data::default(),
+		twitter: Data::default(),
+	}
+}
+
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
 	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
 	
-	// Prefund 3 accounts.
+	// Prefund 4 accounts, and endow the account of the pots with the existential deposit.
 	pallet_balances::GenesisConfig::<Test> {
-		balances: vec![(1, 50), (2, 50), (3, 50), (4, 50)],
+		balances: vec![
+			(1, 50),
+			(2, 50),
+			(3, 50),
+			(4, 50),
+			(QuadraticVoting::account_id(), ExistentialDeposit::get()),
+		],
+	}
+	.assimilate_storage(&mut t)
+	.unwrap();
+
+	// Hand the same amounts out in the guild asset.
+	pallet_assets::GenesisConfig::<Test> {
+		assets: vec![(GUILD_ASSET, 1, true, 1)],
+		metadata: vec![],
+		accounts: (1..=4)
+			.map(|who| (GUILD_ASSET, who, 50))
+			.chain([(GUILD_ASSET, Guild::account_id(), 1)])
+			.collect(),
 	}
 	.assimilate_storage(&mut t)
 	.unwrap();
@@ -130,18 +341,8 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	ext.execute_with(|| { 
 		assert_ok!(Identity::add_registrar(Origin::root(), 1));
 
-		// Create default identity info and set it up for 3 predefined accounts.
-		let info = IdentityInfo {
-			additional: BoundedVec::default(),
-			display: Data::Raw(b"name".to_vec().try_into().unwrap()),
-			legal: Data::default(),
-			web: Data::Raw(b"website".to_vec().try_into().unwrap()),
-			riot: Data::default(),
-			email: Data::default(),
-			pgp_fingerprint: None,
-			image: Data::default(),
-			twitter: Data::default(),
-		};
+		// Set up default identity info for 3 predefined accounts.
+		let info = identity_info();
 		assert_ok!(Identity::set_identity(Origin::signed(1), Box::new(info.clone())));
 		assert_ok!(Identity::set_identity(Origin::signed(2), Box::new(info.clone())));
 		assert_ok!(Identity::set_identity(Origin::signed(3), Box::new(info.clone())));
@@ -150,3 +351,17 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	});
 	ext
 }
+
+// Run `test` on the default genesis storage, then check the invariants of both instances.
+pub fn build_and_execute(test: impl FnOnce()) {
+	execute_with(new_test_ext(), test);
+}
+
+// Run `test` on `ext`, then check the invariants of both instances.
+pub fn execute_with(mut ext: sp_io::TestExternalities, test: impl FnOnce()) {
+	ext.execute_with(|| {
+		test();
+		assert_ok!(QuadraticVoting::do_try_state());
+		assert_ok!(Guild::do_try_state());
+	});
+}
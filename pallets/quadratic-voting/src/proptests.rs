@@ -0,0 +1,539 @@
+//! Property-based tests that run random sequences of calls on both instances against a reference
+//! model of the rules in the README, and compare storage and balances after every call. Failing
+//! sequences are shrunk by proptest.
+
+use crate::{mock::*, Config, Pallet, ProposalIndex, Redistribution, VotePermit, VoteSide};
+use frame_support::instances::Instance1;
+use proptest::{collection::vec, prelude::*};
+use sp_runtime::{testing::TestSignature, SaturatedConversion};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Accounts 1 to 3 have an identity with a deposit of 10, account 4 has none.
+const ACCOUNTS: [AccountId; 4] = [1, 2, 3, 4];
+// Account that submits vote permits on behalf of their voters.
+const RELAYER: AccountId = 4;
+// Bounds and periods shared by both instances in the mock.
+const MAX_ACTIVE: usize = 3;
+const MAX_PER_PROPOSER: usize = 4;
+const MAX_VOTED: usize = 3;
+const MAX_DUE: usize = 2;
+const CLAIM_PERIOD: u64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Body {
+	// The default instance, which reserves native balances.
+	Main,
+	// The guild instance, which freezes the guild asset and caps votes at 2.
+	Guild,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+	Propose(Body, AccountId, Option<Redistribution>),
+	Vote(Body, AccountId, ProposalIndex, u128, VoteSide),
+	// The last field is added to the next nonce of the voter.
+	VoteWithPermit(Body, AccountId, ProposalIndex, u128, VoteSide, u32),
+	Unreserve(Body, AccountId, ProposalIndex),
+	Finalize(Body, ProposalIndex),
+	ClaimShare(Body, AccountId, ProposalIndex),
+	Advance(u64),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+	let body = || prop_oneof![Just(Body::Main), Just(Body::Guild)];
+	let side = || prop_oneof![Just(VoteSide::Aye), Just(VoteSide::Nay)];
+	let target = prop_oneof![
+		2 => Just(None),
+		1 => Just(Some(Redistribution::Voters)),
+		1 => Just(Some(Redistribution::Identities)),
+	];
+	prop_oneof![
+		2 => (body(), 1..=4u64, target).prop_map(|(body, who, target)| Op::Propose(body, who, target)),
+		3 => (body(), 1..=4u64, 0..6u32, 0..=7u128, side())
+			.prop_map(|(body, who, index, votes, side)| Op::Vote(body, who, index, votes, side)),
+		2 => (body(), 1..=3u64, 0..6u32, 0..=3u128, side(), prop_oneof![3 => Just(0u32), 1 => Just(1u32)])
+			.prop_map(|(body, who, index, votes, side, skip)| {
+				Op::VoteWithPermit(body, who, index, votes, side, skip)
+			}),
+		2 => (body(), 1..=4u64, 0..6u32).prop_map(|(body, who, index)| Op::Unreserve(body, who, index)),
+		1 => (body(), 0..6u32).prop_map(|(body, index)| Op::Finalize(body, index)),
+		2 => (body(), 1..=4u64, 0..6u32).prop_map(|(body, who, index)| Op::ClaimShare(body, who, index)),
+		2 => (1..=6u64).prop_map(Op::Advance),
+	]
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ModelProposal {
+	proposer: AccountId,
+	end: u64,
+	aye: u128,
+	nay: u128,
+	finalized: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ModelVote {
+	side: VoteSide,
+	votes: u128,
+	cost: u64,
+	released: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct ModelPot {
+	target: Redistribution,
+	remaining: u64,
+	recipients: BTreeSet<AccountId>,
+}
+
+// What an instance is expected to do, written from the rules rather than from the code.
+struct BodyModel {
+	body: Body,
+	pot_account: AccountId,
+	proposals: Vec<ModelProposal>,
+	votes: BTreeMap<(ProposalIndex, AccountId), ModelVote>,
+	pots: BTreeMap<ProposalIndex, ModelPot>,
+	// Finalized proposals whose pots are waiting for their payout.
+	due: BTreeSet<ProposalIndex>,
+	// Proposals each account voted on, until the funds of the vote are released.
+	voted: BTreeMap<AccountId, BTreeSet<ProposalIndex>>,
+	nonces: BTreeMap<AccountId, u32>,
+	// Balance in the currency of the instance: free native balances for the main instance, guild
+	// asset balances for the guild.
+	balance: BTreeMap<AccountId, u64>,
+	// Reserved native balances for the main instance, frozen guild assets for the guild.
+	held: BTreeMap<AccountId, u64>,
+}
+
+impl BodyModel {
+	fn new(body: Body) -> Self {
+		let (pot_account, balance, held) = match body {
+			Body::Main => (
+				QuadraticVoting::account_id(),
+				[(1, 40), (2, 40), (3, 40), (4, 50)],
+				[(1, 10), (2, 10), (3, 10), (4, 0)],
+			),
+			Body::Guild => (
+				Guild::account_id(),
+				[(1, 50), (2, 50), (3, 50), (4, 50)],
+				[(1, 0), (2, 0), (3, 0), (4, 0)],
+			),
+		};
+		BodyModel {
+			body,
+			pot_account,
+			proposals: Vec::new(),
+			votes: BTreeMap::new(),
+			pots: BTreeMap::new(),
+			due: BTreeSet::new(),
+			voted: BTreeMap::new(),
+			nonces: BTreeMap::new(),
+			balance: balance.into_iter().chain([(pot_account, 1)]).collect(),
+			held: held.into_iter().chain([(pot_account, 0)]).collect(),
+		}
+	}
+
+	fn voting_period(&self) -> u64 {
+		match self.body {
+			Body::Main => 10,
+			Body::Guild => 5,
+		}
+	}
+
+	fn vote_cap(&self) -> Option<u128> {
+		match self.body {
+			Body::Main => None,
+			Body::Guild => Some(2),
+		}
+	}
+
+	// Accounts with an identity may propose, vote and enroll for shares.
+	fn eligible(who: AccountId) -> bool {
+		who != 4
+	}
+
+	// Whether `amount` can be put on hold for `who`. Reserved funds leave the free balance, while
+	// frozen assets stay in the balance and keep the minimum balance of 1 once anything is frozen.
+	fn can_hold(&self, who: AccountId, amount: u64) -> bool {
+		let (balance, held) = (self.balance[&who], self.held[&who]);
+		match self.body {
+			Body::Main => balance >= amount,
+			Body::Guild if held > 0 => balance.saturating_sub(held + 1) >= amount,
+			Body::Guild => balance >= amount,
+		}
+	}
+
+	// Whether `amount` can be transferred from `who`, keeping it alive.
+	fn can_transfer(&self, who: AccountId, amount: u64) -> bool {
+		let (balance, held) = (self.balance[&who], self.held[&who]);
+		match self.body {
+			// An account with reserved funds stays alive with an empty free balance.
+			Body::Main => balance >= amount && balance - amount + held >= 1,
+			Body::Guild => balance.saturating_sub(held + 1) >= amount,
+		}
+	}
+
+	fn hold(&mut self, who: AccountId, amount: u64) {
+		if self.body == Body::Main {
+			*self.balance.get_mut(&who).unwrap() -= amount;
+		}
+		*self.held.get_mut(&who).unwrap() += amount;
+	}
+
+	fn release(&mut self, who: AccountId, amount: u64) {
+		if self.body == Body::Main {
+			*self.balance.get_mut(&who).unwrap() += amount;
+		}
+		*self.held.get_mut(&who).unwrap() -= amount;
+	}
+
+	fn transfer(&mut self, from: AccountId, to: AccountId, amount: u64) {
+		*self.balance.get_mut(&from).unwrap() -= amount;
+		*self.balance.get_mut(&to).unwrap() += amount;
+	}
+
+	fn propose(&mut self, now: u64, who: AccountId, target: Option<Redistribution>) -> bool {
+		let active = self.proposals.iter().filter(|p| !p.finalized).count();
+		let created = self.proposals.iter().filter(|p| p.proposer == who).count();
+		if !Self::eligible(who) || active >= MAX_ACTIVE || created >= MAX_PER_PROPOSER {
+			return false
+		}
+		if let Some(target) = target {
+			let pot = ModelPot { target, remaining: 0, recipients: BTreeSet::new() };
+			self.pots.insert(self.proposals.len() as ProposalIndex, pot);
+		}
+		let end = now + self.voting_period();
+		self.proposals.push(ModelProposal { proposer: who, end, aye: 0, nay: 0, finalized: false });
+		true
+	}
+
+	fn vote(
+		&mut self,
+		now: u64,
+		who: AccountId,
+		index: ProposalIndex,
+		votes: u128,
+		side: VoteSide,
+	) -> bool {
+		let open = self.proposals.get(index as usize).map_or(false, |p| now <= p.end);
+		let voted = self.voted.get(&who).map_or(0, |voted| voted.len());
+		if !open ||
+			!Self::eligible(who) ||
+			votes == 0 ||
+			self.vote_cap().map_or(false, |cap| votes > cap) ||
+			self.votes.contains_key(&(index, who)) ||
+			voted >= MAX_VOTED
+		{
+			return false
+		}
+		let cost = (votes * votes) as u64;
+		match self.pots.contains_key(&index) {
+			true if self.can_transfer(who, cost) => {
+				self.transfer(who, self.pot_account, cost);
+				let pot = self.pots.get_mut(&index).unwrap();
+				pot.remaining += cost;
+				if pot.target == Redistribution::Voters {
+					pot.recipients.insert(who);
+				}
+			},
+			false if self.can_hold(who, cost) => self.hold(who, cost),
+			_ => return false,
+		}
+		let p = &mut self.proposals[index as usize];
+		match side {
+			VoteSide::Aye => p.aye += votes,
+			VoteSide::Nay => p.nay += votes,
+		}
+		self.votes.insert((index, who), ModelVote { side, votes, cost, released: false });
+		self.voted.entry(who).or_default().insert(index);
+		true
+	}
+
+	fn vote_with_permit(
+		&mut self,
+		now: u64,
+		who: AccountId,
+		index: ProposalIndex,
+		votes: u128,
+		side: VoteSide,
+		skip: u32,
+	) -> bool {
+		// Permits have to carry the next nonce of the voter.
+		if skip > 0 || !self.vote(now, who, index, votes, side) {
+			return false
+		}
+		*self.nonces.entry(who).or_default() += 1;
+		true
+	}
+
+	fn unreserve(&mut self, now: u64, who: AccountId, index: ProposalIndex) -> bool {
+		let ended = self.proposals.get(index as usize).map_or(false, |p| now > p.end);
+		if !ended || self.pots.contains_key(&index) {
+			return false
+		}
+		let cost = match self.votes.get_mut(&(index, who)) {
+			Some(vote) if !vote.released => {
+				vote.released = true;
+				vote.cost
+			},
+			_ => return false,
+		};
+		self.release(who, cost);
+		self.voted.get_mut(&who).unwrap().remove(&index);
+		true
+	}
+
+	fn finalize(&mut self, now: u64, index: ProposalIndex) -> bool {
+		match self.proposals.get(index as usize) {
+			Some(p) if now > p.end && !p.finalized => (),
+			_ => return false,
+		}
+		// Pots that still have shares to pay out wait for their payout in a bounded queue.
+		if let Some(pot) = self.pots.get(&index) {
+			if pot.target == Redistribution::Identities || !pot.recipients.is_empty() {
+				if self.due.len() >= MAX_DUE {
+					return false
+				}
+				self.due.insert(index);
+			}
+		}
+		self.proposals[index as usize].finalized = true;
+		true
+	}
+
+	fn claim_share(&mut self, now: u64, who: AccountId, index: ProposalIndex) -> bool {
+		let end = match self.proposals.get(index as usize) {
+			Some(p) if now > p.end => p.end,
+			_ => return false,
+		};
+		let pot = match self.pots.get_mut(&index) {
+			Some(pot) => pot,
+			None => return false,
+		};
+		// Identities enroll during the claim period, and are paid once it is over.
+		if pot.target == Redistribution::Identities && now <= end + CLAIM_PERIOD {
+			return Self::eligible(who) && pot.recipients.insert(who)
+		}
+		if !pot.recipients.remove(&who) {
+			return false
+		}
+		// Every recipient gets an equal share of what is left, so the last one empties the pot.
+		let share = pot.remaining / (pot.recipients.len() as u64 + 1);
+		pot.remaining -= share;
+		if pot.recipients.is_empty() {
+			self.due.remove(&index);
+		}
+		self.transfer(self.pot_account, who, share);
+		if let Some(voted) = self.voted.get_mut(&who) {
+			voted.remove(&index);
+		}
+		true
+	}
+}
+
+struct Model {
+	now: u64,
+	main: BodyModel,
+	guild: BodyModel,
+}
+
+impl Model {
+	fn new() -> Self {
+		Model { now: 1, main: BodyModel::new(Body::Main), guild: BodyModel::new(Body::Guild) }
+	}
+
+	fn body(&mut self, body: Body) -> &mut BodyModel {
+		match body {
+			Body::Main => &mut self.main,
+			Body::Guild => &mut self.guild,
+		}
+	}
+
+	// Apply `op` and return whether it is expected to succeed.
+	fn apply(&mut self, op: &Op) -> bool {
+		let now = self.now;
+		match *op {
+			Op::Propose(body, who, target) => self.body(body).propose(now, who, target),
+			Op::Vote(body, who, index, votes, side) =>
+				self.body(body).vote(now, who, index, votes, side),
+			Op::VoteWithPermit(body, who, index, votes, side, skip) =>
+				self.body(body).vote_with_permit(now, who, index, votes, side, skip),
+			Op::Unreserve(body, who, index) => self.body(body).unreserve(now, who, index),
+			Op::Finalize(body, index) => self.body(body).finalize(now, index),
+			Op::ClaimShare(body, who, index) => self.body(body).claim_share(now, who, index),
+			Op::Advance(blocks) => {
+				self.now += blocks;
+				true
+			},
+		}
+	}
+}
+
+fn dispatch(op: &Op) -> bool {
+	match op {
+		Op::Propose(Body::Main, ..) |
+		Op::Vote(Body::Main, ..) |
+		Op::VoteWithPermit(Body::Main, ..) |
+		Op::Unreserve(Body::Main, ..) |
+		Op::Finalize(Body::Main, ..) |
+		Op::ClaimShare(Body::Main, ..) => dispatch_in::<()>(op),
+		Op::Advance(blocks) => {
+			System::set_block_number(System::block_number() + blocks);
+			true
+		},
+		_ => dispatch_in::<Instance1>(op),
+	}
+}
+
+fn dispatch_in<I: 'static>(op: &Op) -> bool
+where
+	Test: Config<I, PermitSignature = TestSignature>,
+{
+	let signed = Origin::signed;
+	match *op {
+		Op::Propose(_, who, None) =>
+			Pallet::<Test, I>::propose(signed(who), Default::default()).is_ok(),
+		Op::Propose(_, who, Some(target)) =>
+			Pallet::<Test, I>::propose_with_pot(signed(who), Default::default(), target).is_ok(),
+		Op::Vote(_, who, index, votes, VoteSide::Aye) =>
+			Pallet::<Test, I>::vote_aye(signed(who), index, votes).is_ok(),
+		Op::Vote(_, who, index, votes, VoteSide::Nay) =>
+			Pallet::<Test, I>::vote_nay(signed(who), index, votes).is_ok(),
+		Op::VoteWithPermit(_, who, index, votes, side, skip) => {
+			let permit = VotePermit {
+				voter: who,
+				proposal_index: index,
+				side,
+				votes,
+				nonce: Pallet::<Test, I>::permit_nonce(who) + skip,
+				deadline: System::block_number(),
+			};
+			let signature = TestSignature(who, Pallet::<Test, I>::permit_payload(&permit));
+			Pallet::<Test, I>::vote_with_permit(signed(RELAYER), permit, signature).is_ok()
+		},
+		Op::Unreserve(_, who, index) => Pallet::<Test, I>::unreserve(signed(who), index).is_ok(),
+		Op::Finalize(_, index) => Pallet::<Test, I>::finalize(signed(1), index).is_ok(),
+		Op::ClaimShare(_, who, index) =>
+			Pallet::<Test, I>::claim_share(signed(who), index).is_ok(),
+		Op::Advance(_) => unreachable!("advancing blocks is not a call of an instance"),
+	}
+}
+
+// Check the invariants that hold whatever the calls were: tallies are the sums of the recorded
+// votes, and every vote costs the square of its number.
+fn check_invariants<I: 'static>() -> Result<(), TestCaseError>
+where
+	Test: Config<I>,
+{
+	for (index, p) in crate::Proposals::<Test, I>::iter() {
+		let (mut aye, mut nay) = (0, 0);
+		for vote in crate::Votes::<Test, I>::iter_prefix_values(index) {
+			match vote.side {
+				Some(VoteSide::Aye) => aye += vote.votes,
+				Some(VoteSide::Nay) => nay += vote.votes,
+				None => return Err(TestCaseError::fail("vote without a side")),
+			}
+			prop_assert_eq!(vote.cost.saturated_into::<u128>(), vote.votes * vote.votes);
+		}
+		prop_assert_eq!((p.aye, p.nay), (aye, nay), "tallies of proposal {}", index);
+	}
+	prop_assert!(Pallet::<Test, I>::do_try_state().is_ok());
+	Ok(())
+}
+
+// Compare the storage of an instance with its model.
+fn check_in<I: 'static>(model: &BodyModel) -> Result<(), TestCaseError>
+where
+	Test: Config<I>,
+{
+	prop_assert_eq!(
+		Pallet::<Test, I>::proposal_count().unwrap_or(0) as usize,
+		model.proposals.len()
+	);
+	let active = Pallet::<Test, I>::active_proposals();
+	for (index, expected) in model.proposals.iter().enumerate() {
+		let index = index as ProposalIndex;
+		let p = Pallet::<Test, I>::proposals(index).unwrap();
+		let actual = ModelProposal {
+			proposer: p.proposer,
+			end: p.end,
+			aye: p.aye,
+			nay: p.nay,
+			finalized: !active.contains(&index),
+		};
+		prop_assert_eq!(&actual, expected);
+	}
+
+	let votes: BTreeMap<_, _> = crate::Votes::<Test, I>::iter()
+		.map(|(index, who, vote)| {
+			let side = vote.side.unwrap();
+			let cost = vote.cost.saturated_into();
+			let vote = ModelVote { side, votes: vote.votes, cost, released: vote.released };
+			((index, who), vote)
+		})
+		.collect();
+	prop_assert_eq!(&votes, &model.votes);
+
+	let pots: BTreeMap<_, _> = crate::Pots::<Test, I>::iter()
+		.map(|(index, pot)| {
+			let recipients: BTreeSet<_> =
+				crate::PotRecipients::<Test, I>::iter_key_prefix(index).collect();
+			assert_eq!(pot.recipients as usize, recipients.len());
+			let remaining = pot.remaining.saturated_into();
+			(index, ModelPot { target: pot.target, remaining, recipients })
+		})
+		.collect();
+	prop_assert_eq!(&pots, &model.pots);
+	let due: BTreeSet<_> =
+		Pallet::<Test, I>::due_payouts().into_iter().map(|(_, index)| index).collect();
+	prop_assert_eq!(&due, &model.due);
+
+	for who in ACCOUNTS {
+		let voted = model.voted.get(&who).cloned().unwrap_or_default();
+		prop_assert_eq!(
+			Pallet::<Test, I>::voted_proposals(who).into_iter().collect::<BTreeSet<_>>(),
+			voted
+		);
+		prop_assert_eq!(
+			Pallet::<Test, I>::permit_nonce(who),
+			model.nonces.get(&who).copied().unwrap_or_default()
+		);
+	}
+	Ok(())
+}
+
+// Compare both instances and the balances of all accounts with the model.
+fn check(model: &Model) -> Result<(), TestCaseError> {
+	prop_assert_eq!(System::block_number(), model.now);
+	check_in::<()>(&model.main)?;
+	check_in::<Instance1>(&model.guild)?;
+	check_invariants::<()>()?;
+	check_invariants::<Instance1>()?;
+
+	for who in ACCOUNTS.into_iter().chain([model.main.pot_account]) {
+		prop_assert_eq!(Balances::free_balance(who), model.main.balance[&who]);
+		prop_assert_eq!(Balances::reserved_balance(who), model.main.held[&who]);
+	}
+	for who in ACCOUNTS.into_iter().chain([model.guild.pot_account]) {
+		prop_assert_eq!(Assets::balance(GUILD_ASSET, who), model.guild.balance[&who]);
+		prop_assert_eq!(Guild::frozen_balance(&who), model.guild.held[&who]);
+	}
+	Ok(())
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(256))]
+
+	#[test]
+	fn random_calls_follow_the_model(ops in vec(op(), 1..80)) {
+		new_test_ext().execute_with(|| {
+			let mut model = Model::new();
+			for op in ops.iter() {
+				let expected = model.apply(op);
+				prop_assert_eq!(dispatch(op), expected, "outcome of {:?}", op);
+				check(&model)?;
+			}
+			Ok(())
+		})?;
+	}
+}
@@ -0,0 +1,59 @@
+//! Runtime API of the quadratic voting pallet.
+//!
+//! Lets UIs, scripts and the node's RPC layer query proposals and votes through the same logic the
+//! runtime uses, instead of decoding raw `Proposals` and `Votes` storage.
+//!
+//! Every method takes the instance of the pallet to query. `Instance` is a type of the runtime that
+//! names its instances, so one implementation of the API serves all of them.
+
+use crate::{AccountHistory, Ineligibility, ProposalIndex, ProposalInfo, VoteInfo};
+use codec::Codec;
+use sp_runtime::DispatchError;
+use sp_std::prelude::*;
+
+sp_api::decl_runtime_apis! {
+	pub trait QuadraticVotingApi<AccountId, Balance, BlockNumber, Instance> where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+		Instance: Codec,
+	{
+		/// Get the proposal at `index` together with its status at the current block.
+		fn proposal(
+			instance: Instance,
+			index: ProposalIndex,
+		) -> Option<ProposalInfo<AccountId, BlockNumber>>;
+
+		/// Get the indexes of all proposals that are still open for voting.
+		fn active_proposals(instance: Instance) -> Vec<ProposalIndex>;
+
+		/// Get the vote `who` cast on the proposal at `index`.
+		fn vote_of(
+			instance: Instance,
+			index: ProposalIndex,
+			who: AccountId,
+		) -> Option<VoteInfo<Balance, BlockNumber>>;
+
+		/// Get the votes of `who` whose funds have not been released yet, and its statistics.
+		fn account_history(instance: Instance, who: AccountId) -> AccountHistory<Balance, BlockNumber>;
+
+		/// Get the cost of casting `votes` votes, or `None` if it overflows the balance type.
+		fn quote_cost(instance: Instance, votes: u128) -> Option<Balance>;
+
+		/// Check whether `who` can vote on the proposal at `index`, and why not if it can't.
+		fn eligibility(
+			instance: Instance,
+			index: ProposalIndex,
+			who: AccountId,
+		) -> Result<(), Ineligibility>;
+
+		/// Execute a vote of `who` without persisting any of its effects.
+		fn dry_run_vote(
+			instance: Instance,
+			index: ProposalIndex,
+			who: AccountId,
+			aye: bool,
+			votes: u128,
+		) -> Result<(), DispatchError>;
+	}
+}
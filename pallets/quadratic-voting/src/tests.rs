@@ -1,9 +1,37 @@
-use crate::{mock::*, Error};
-use frame_support::{assert_noop, assert_ok, BoundedVec};
+use crate::{
+	currency::CheckpointCalls,
+	eligibility::{And, Capped, HasIdentity, IsMember, MinBalance, Or},
+	migrations::v1::MigrateToV1,
+	mock::*,
+	Error, Ineligibility, ProposalStatus, Redistribution, Refusal, VoteInfo, VotePermit, VoteRecord,
+	VoteSide, VoterEligibility, VoterStats,
+};
+use frame_support::{
+	assert_noop, assert_ok,
+	instances::Instance1,
+	traits::{
+		ConstU128, ConstU64, GenesisBuild, GetStorageVersion, Hooks, OnRuntimeUpgrade, SortedMembers,
+		StorageVersion,
+	},
+	unsigned::ValidateUnsigned,
+	weights::{GetDispatchInfo, Pays},
+	BoundedVec,
+};
+use codec::{Decode, Encode};
+use sp_core::offchain::{
+	testing::{TestOffchainExt, TestTransactionPoolExt},
+	OffchainDbExt, OffchainWorkerExt, TransactionPoolExt,
+};
+use sp_runtime::{
+	testing::{TestSignature, UintAuthorityId},
+	traits::{BadOrigin, BlakeTwo256, Dispatchable, Hash, SignedExtension},
+	transaction_validity::{InvalidTransaction, TransactionSource},
+	TokenError,
+};
 
 #[test]
 fn proposal_creation() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 		let proposer_with_identity = Origin::signed(1);
 		let proposer_without_identity = Origin::signed(4);
 		let proposal = BoundedVec::default();
@@ -22,7 +50,7 @@ fn proposal_creation() {
 
 #[test]
 fn vote_aye_for_proposal() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 		let proposer_with_identity = Origin::signed(1);
 		let voter_with_identity = Origin::signed(2);
 		let voter_late = Origin::signed(3);
@@ -44,11 +72,20 @@ fn vote_aye_for_proposal() {
 			QuadraticVoting::vote_aye(voter_with_identity.clone(), proposal_index_missing, number_of_votes), 
 			Error::<Test>::UnexistingProposal
 		);
+		// Fail because the cost of the votes does not fit into the balance type.
+		assert_noop!(
+			QuadraticVoting::vote_aye(voter_with_identity.clone(), proposal_index, u128::MAX),
+			Error::<Test>::Overflow
+		);
+		assert_noop!(
+			QuadraticVoting::vote_aye(voter_with_identity.clone(), proposal_index, 1 << 32),
+			Error::<Test>::Overflow
+		);
 		// Successful voting.
 		assert_ok!(QuadraticVoting::vote_aye(voter_with_identity.clone(), proposal_index, number_of_votes));
 		// Check that last event is voted aye for proposal.
 		System::assert_last_event(
-			crate::Event::VotedAye(0)
+			crate::Event::VotedAye(0, 2, 3)
 			.into(),
 		);
 		// Fail because user has already voted for the proposal.
@@ -56,6 +93,14 @@ fn vote_aye_for_proposal() {
 			QuadraticVoting::vote_aye(voter_with_identity.clone(), proposal_index, number_of_votes), 
 			Error::<Test>::AlreadyVoted
 		);
+		// Fail because the tally of the proposal would overflow.
+		crate::Proposals::<Test>::mutate(proposal_index, |p| {
+			p.as_mut().unwrap().aye = u128::MAX;
+		});
+		assert_noop!(
+			QuadraticVoting::vote_aye(voter_late.clone(), proposal_index, number_of_votes),
+			Error::<Test>::Overflow
+		);
 		// Set block number to be after the voting period deadline.
 		System::set_block_number(20);
 		// Fail because voting period is over and user cannot vote.
@@ -68,7 +113,7 @@ fn vote_aye_for_proposal() {
 
 #[test]
 fn vote_aye_on_multiple_proposals() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 			let first_proposer_with_identity = Origin::signed(1);
 			let second_proposer_with_identity = Origin::signed(2);
 			let voter = Origin::signed(3);
@@ -83,13 +128,13 @@ fn vote_aye_on_multiple_proposals() {
 			// Vote for first proposal and check that expected event is emmited.
 			assert_ok!(QuadraticVoting::vote_aye(voter.clone(), first_proposal_index, number_of_votes));
 			System::assert_last_event(
-				crate::Event::VotedAye(0)
+				crate::Event::VotedAye(0, 3, 3)
 				.into(),
 			);
 			// Vote for second proposal and check that expected event is emmited.
 			assert_ok!(QuadraticVoting::vote_aye(voter.clone(), second_proposal_index, number_of_votes));
 			System::assert_last_event(
-				crate::Event::VotedAye(1)
+				crate::Event::VotedAye(1, 3, 3)
 				.into(),
 			);
 	});
@@ -97,7 +142,7 @@ fn vote_aye_on_multiple_proposals() {
 
 #[test]
 fn vote_nay_for_proposal() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 		let proposer_with_identity = Origin::signed(1);
 		let voter_with_identity = Origin::signed(2);
 		let voter_late = Origin::signed(3);
@@ -123,7 +168,7 @@ fn vote_nay_for_proposal() {
 		assert_ok!(QuadraticVoting::vote_nay(voter_with_identity.clone(), proposal_index, number_of_votes));
 		// Check that last event is voted aye for proposal.
 		System::assert_last_event(
-			crate::Event::VotedNay(0)
+			crate::Event::VotedNay(0, 2, 3)
 			.into(),
 		);
 		// Fail because user has already voted for the proposal.
@@ -143,7 +188,7 @@ fn vote_nay_for_proposal() {
 
 #[test]
 fn vote_nay_on_multiple_proposals() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 		let first_proposer_with_identity = Origin::signed(1);
 		let second_proposer_with_identity = Origin::signed(2);
 		let voter = Origin::signed(3);
@@ -158,13 +203,13 @@ fn vote_nay_on_multiple_proposals() {
 		// Vote for first proposal and check that expected event is emmited.
 		assert_ok!(QuadraticVoting::vote_nay(voter.clone(), first_proposal_index, number_of_votes));
 		System::assert_last_event(
-			crate::Event::VotedNay(0)
+			crate::Event::VotedNay(0, 3, 3)
 			.into(),
 		);
 		// Vote for second proposal and check that expected event is emmited.
 		assert_ok!(QuadraticVoting::vote_nay(voter.clone(), second_proposal_index, number_of_votes));
 		System::assert_last_event(
-			crate::Event::VotedNay(1)
+			crate::Event::VotedNay(1, 3, 3)
 			.into(),
 		);
 	});
@@ -172,7 +217,7 @@ fn vote_nay_on_multiple_proposals() {
 
 #[test]
 fn vote_aye_then_nay_on_same_proposal() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 			let proposer = Origin::signed(1);
 			let voter = Origin::signed(2);
 			let proposal = BoundedVec::default();
@@ -184,7 +229,7 @@ fn vote_aye_then_nay_on_same_proposal() {
 			// Vote for the proposal and check that correct event is emmited.
 			assert_ok!(QuadraticVoting::vote_aye(voter.clone(), proposal_index, number_of_votes));
 			System::assert_last_event(
-				crate::Event::VotedAye(0)
+				crate::Event::VotedAye(0, 2, 3)
 				.into(),
 			);
 			// Fail because user already voted aye on same proposal.
@@ -194,7 +239,7 @@ fn vote_aye_then_nay_on_same_proposal() {
 			);
 			// Check that last event didn't changed.
 			System::assert_last_event(
-				crate::Event::VotedAye(0)
+				crate::Event::VotedAye(0, 2, 3)
 				.into(),
 			);
 	});
@@ -202,7 +247,7 @@ fn vote_aye_then_nay_on_same_proposal() {
 
 #[test]
 fn unreserve_tokens_after_voting() {
-	new_test_ext().execute_with(|| {
+	build_and_execute(|| {
 			let proposer = Origin::signed(1);
 			let voter = Origin::signed(2);
 			let not_voter = Origin::signed(3);
@@ -216,19 +261,839 @@ fn unreserve_tokens_after_voting() {
 			// Vote to support some proposal and check emitted event.
 			assert_ok!(QuadraticVoting::vote_aye(voter.clone(), proposal_index, number_of_votes));
 			System::assert_last_event(
-				crate::Event::VotedAye(0)
+				crate::Event::VotedAye(0, 2, 3)
 				.into(),
 			);
 			// Fail because user tried to unreserve tokens from unexisting proposal.
 			assert_noop!(QuadraticVoting::unreserve(voter.clone(), proposal_index_unexisting), Error::<Test>::UnexistingProposal);
 			// Fail because user tried to unreserve tokens while the voting is ongoing
 			assert_noop!(QuadraticVoting::unreserve(voter.clone(), proposal_index), Error::<Test>::VotingNotEnded);
+			// Fail because votes can still be cast in the last block of the voting period.
+			System::set_block_number(11);
+			assert_noop!(QuadraticVoting::unreserve(voter.clone(), proposal_index), Error::<Test>::VotingNotEnded);
 			// Set block number to be after the voting ended.
 			System::set_block_number(20);
 			// Fail because user tried to unreserve tokens but the user didn't voted at all.
 			assert_noop!(QuadraticVoting::unreserve(not_voter.clone(), proposal_index), Error::<Test>::NotVoted);
 			// Successful unlocking of tokens.
 			assert_ok!(QuadraticVoting::unreserve(voter.clone(), proposal_index));
-			
+			// The vote stays recorded, but its funds can't be released twice.
+			assert!(QuadraticVoting::votes(proposal_index, 2).unwrap().released);
+			assert_eq!(Balances::reserved_balance(2), 10);
+			assert_noop!(QuadraticVoting::unreserve(voter.clone(), proposal_index), Error::<Test>::AlreadyReleased);
+
+	});
+}
+
+#[test]
+fn redistribute_pot_among_voters() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		let first_voter = Origin::signed(2);
+		let second_voter = Origin::signed(3);
+		let proposal = BoundedVec::default();
+		let proposal_index = 0;
+
+		// Create proposal whose vote costs are paid into a pot.
+		assert_ok!(QuadraticVoting::propose_with_pot(proposer.clone(), proposal, Redistribution::Voters));
+		// Vote costs are taken from the voters and collected in the pallet account.
+		assert_ok!(QuadraticVoting::vote_aye(first_voter.clone(), proposal_index, 3));
+		assert_ok!(QuadraticVoting::vote_nay(second_voter.clone(), proposal_index, 2));
+		assert_eq!(Balances::free_balance(QuadraticVoting::account_id()), 14);
+		assert_eq!(Balances::reserved_balance(2), 10);
+		// Fail because shares cannot be claimed while voting is ongoing.
+		assert_noop!(QuadraticVoting::claim_share(first_voter.clone(), proposal_index), Error::<Test>::ClaimNotOpen);
+		// Set block number to be after the voting ended.
+		System::set_block_number(12);
+		// Nothing was reserved for the vote, so there is nothing to unreserve.
+		assert_noop!(QuadraticVoting::unreserve(first_voter.clone(), proposal_index), Error::<Test>::NotReserved);
+		// Fail because the proposer did not vote.
+		assert_noop!(QuadraticVoting::claim_share(proposer, proposal_index), Error::<Test>::NotRecipient);
+		// Voters split the pot, with the rounding remainder going to the last one.
+		assert_ok!(QuadraticVoting::claim_share(first_voter.clone(), proposal_index));
+		System::assert_last_event(crate::Event::SharePaid(0, 2, 6).into());
+		assert_ok!(QuadraticVoting::claim_share(second_voter, proposal_index));
+		System::assert_last_event(crate::Event::SharePaid(0, 3, 7).into());
+		assert_eq!(Balances::free_balance(2), 37);
+		assert_eq!(Balances::free_balance(3), 43);
+		// The account of the pots keeps its existential deposit.
+		assert_eq!(Balances::free_balance(QuadraticVoting::account_id()), 1);
+		// Fail because the share has already been paid.
+		assert_noop!(QuadraticVoting::claim_share(first_voter, proposal_index), Error::<Test>::NotRecipient);
+	});
+}
+
+#[test]
+fn zero_votes_are_rejected() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		assert_ok!(QuadraticVoting::propose_with_pot(proposer.clone(), BoundedVec::default(), Redistribution::Voters));
+		// Fail because a vote of nothing would enroll the voter for a share of the pot for free.
+		assert_noop!(QuadraticVoting::vote_aye(Origin::signed(2), 0, 0), Error::<Test>::ZeroVotes);
+		assert_noop!(QuadraticVoting::vote_nay(Origin::signed(2), 0, 0), Error::<Test>::ZeroVotes);
+		assert!(QuadraticVoting::free_call_validity(&2, &crate::Call::vote_aye { proposal_index: 0, votes: 0 }).is_none());
+		assert_eq!(QuadraticVoting::pots(0).unwrap().recipients, 0);
+		assert_ok!(QuadraticVoting::cancel(proposer, 0));
+	});
+}
+
+#[test]
+fn pots_are_paid_into_and_out_of_with_an_existential_deposit() {
+	ExistentialDeposit::set(10);
+	build_and_execute(|| {
+		let proposal_index = 0;
+		assert_ok!(QuadraticVoting::propose_with_pot(Origin::signed(1), BoundedVec::default(), Redistribution::Voters));
+		// Costs below the existential deposit can be paid into the pot, since its account exists.
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(2), proposal_index, 2));
+		assert_ok!(QuadraticVoting::vote_nay(Origin::signed(3), proposal_index, 2));
+		assert_eq!(Balances::free_balance(QuadraticVoting::account_id()), 18);
+		// One of the voters gives up its identity and empties its account, so it gets reaped.
+		System::set_block_number(12);
+		assert_ok!(Identity::clear_identity(Origin::signed(3)));
+		assert_ok!(Balances::transfer(Origin::signed(3), 4, 46));
+		assert_eq!(Balances::total_balance(&3), 0);
+		// Its share is too small to recreate the account, which does not hold up the batch.
+		assert_ok!(QuadraticVoting::payout_shares(Origin::signed(4), proposal_index));
+		assert!(System::events().iter().any(|record| matches!(
+			record.event,
+			Event::QuadraticVoting(crate::Event::ShareUnpaid(0, 3, 4, _))
+		)));
+		assert_eq!(Balances::free_balance(2), 40);
+		assert_eq!(QuadraticVoting::pots(proposal_index).unwrap().recipients, 0);
+		assert_eq!(QuadraticVoting::unpaid_shares(proposal_index, 3), Some(4));
+		// The account of the pots is kept alive.
+		assert_eq!(Balances::free_balance(QuadraticVoting::account_id()), 14);
+		// Once the account is back, the share can be claimed.
+		assert_ok!(Balances::transfer(Origin::signed(4), 3, 20));
+		assert_ok!(QuadraticVoting::claim_share(Origin::signed(3), proposal_index));
+		System::assert_last_event(crate::Event::SharePaid(0, 3, 4).into());
+		assert_eq!(Balances::free_balance(3), 24);
+		assert_eq!(QuadraticVoting::unpaid_shares(proposal_index, 3), None);
+		assert_eq!(Balances::free_balance(QuadraticVoting::account_id()), 10);
+		assert_noop!(QuadraticVoting::claim_share(Origin::signed(3), proposal_index), Error::<Test>::NotRecipient);
+	});
+}
+
+#[test]
+fn redistribute_pot_among_identities_in_batches() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		let voter = Origin::signed(2);
+		let proposal = BoundedVec::default();
+		let proposal_index = 0;
+
+		// Create proposal whose pot is split among all identities.
+		assert_ok!(QuadraticVoting::propose_with_pot(proposer.clone(), proposal, Redistribution::Identities));
+		assert_ok!(QuadraticVoting::vote_aye(voter.clone(), proposal_index, 3));
+		// Fail because proposal without pot has nothing to redistribute.
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		assert_noop!(QuadraticVoting::claim_share(proposer.clone(), 1), Error::<Test>::NoPot);
+		// Set block number to be within the claim period.
+		System::set_block_number(12);
+		// Identities enroll for a share, including ones that did not vote.
+		assert_ok!(QuadraticVoting::claim_share(proposer.clone(), proposal_index));
+		System::assert_last_event(crate::Event::ShareEnrolled(0, 1).into());
+		assert_ok!(QuadraticVoting::claim_share(voter.clone(), proposal_index));
+		assert_ok!(QuadraticVoting::claim_share(Origin::signed(3), proposal_index));
+		assert_noop!(QuadraticVoting::claim_share(voter.clone(), proposal_index), Error::<Test>::AlreadyEnrolled);
+		assert_noop!(QuadraticVoting::claim_share(Origin::signed(4), proposal_index), Error::<Test>::MissingIdentity);
+		// Fail because shares are not payable until the claim period is over.
+		assert_noop!(QuadraticVoting::payout_shares(voter.clone(), proposal_index), Error::<Test>::ClaimNotOpen);
+		// Set block number to be after the claim period.
+		System::set_block_number(17);
+		// Enrollment is closed.
+		assert_noop!(QuadraticVoting::claim_share(Origin::signed(4), proposal_index), Error::<Test>::NotRecipient);
+		// Each batch pays out at most `MaxPayoutsPerBatch` shares.
+		assert_ok!(QuadraticVoting::payout_shares(Origin::signed(4), proposal_index));
+		assert_eq!(QuadraticVoting::pots(proposal_index).unwrap().recipients, 1);
+		assert_ok!(QuadraticVoting::payout_shares(Origin::signed(4), proposal_index));
+		assert_eq!(QuadraticVoting::pots(proposal_index).unwrap().recipients, 0);
+		assert_eq!(QuadraticVoting::pots(proposal_index).unwrap().remaining, 0);
+		assert_eq!(Balances::free_balance(1), 43);
+		assert_eq!(Balances::free_balance(2), 34);
+		assert_eq!(Balances::free_balance(3), 43);
+	});
+}
+
+#[test]
+fn runtime_api_queries() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		let voter = Origin::signed(2);
+
+		// Create two proposals and vote on the first one.
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		System::set_block_number(5);
+		assert_ok!(QuadraticVoting::propose(proposer, BoundedVec::default()));
+		assert_ok!(QuadraticVoting::vote_aye(voter, 0, 3));
+		assert_eq!(QuadraticVoting::active_proposals(), vec![0, 1]);
+		assert_eq!(QuadraticVoting::proposal_info(0).unwrap().status, ProposalStatus::Voting);
+		assert_eq!(
+			QuadraticVoting::vote_of(0, &2),
+			Some(VoteInfo {
+				vote: VoteRecord {
+					side: Some(VoteSide::Aye),
+					votes: 3,
+					cost: 9,
+					at_block: 5,
+					released: false,
+				},
+				paid_into_pot: false,
+			})
+		);
+		assert_eq!(QuadraticVoting::vote_of(1, &2), None);
+		assert_eq!(QuadraticVoting::quote_cost(4), Some(16));
+		assert_eq!(QuadraticVoting::quote_cost(u128::MAX), None);
+		// Eligibility reports the same reasons as voting does.
+		assert_eq!(QuadraticVoting::eligibility(0, &3), Ok(()));
+		assert_eq!(QuadraticVoting::eligibility(0, &2), Err(Ineligibility::AlreadyVoted));
+		assert_eq!(QuadraticVoting::eligibility(0, &4), Err(Ineligibility::MissingIdentity));
+		assert_eq!(QuadraticVoting::eligibility(2, &3), Err(Ineligibility::UnexistingProposal));
+		// Dry run reports the outcome of the vote without applying it.
+		assert_ok!(QuadraticVoting::dry_run_vote(0, 3, false, 2));
+		assert_eq!(QuadraticVoting::vote_of(0, &3), None);
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().nay, 0);
+		assert_noop!(QuadraticVoting::dry_run_vote(0, 3, false, 10), pallet_balances::Error::<Test>::InsufficientBalance);
+		// Set block number to be after the first proposal ended.
+		System::set_block_number(12);
+		assert_eq!(QuadraticVoting::active_proposals(), vec![1]);
+		assert_eq!(QuadraticVoting::proposal_info(0).unwrap().status, ProposalStatus::Passed);
+		assert_eq!(QuadraticVoting::eligibility(0, &3), Err(Ineligibility::VotingEnded));
+	});
+}
+
+#[test]
+fn proposal_indexes_follow_lifecycle() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		let other_proposer = Origin::signed(2);
+		let voter = Origin::signed(3);
+
+		// Create proposals ending at different blocks.
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		System::set_block_number(3);
+		assert_ok!(QuadraticVoting::propose(other_proposer.clone(), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		assert_eq!(QuadraticVoting::active_proposal_index().into_inner(), vec![(11, 0), (13, 1), (13, 2)]);
+		assert_eq!(QuadraticVoting::proposals_by(&2), vec![1]);
+		// Fail because there are too many proposals awaiting finalization.
+		assert_noop!(
+			QuadraticVoting::propose(other_proposer.clone(), BoundedVec::default()),
+			Error::<Test>::TooManyActiveProposals
+		);
+		// Only the proposer can cancel, and only before anybody voted.
+		assert_ok!(QuadraticVoting::vote_aye(voter.clone(), 0, 1));
+		assert_noop!(QuadraticVoting::cancel(other_proposer.clone(), 0), Error::<Test>::NotProposer);
+		assert_noop!(QuadraticVoting::cancel(proposer.clone(), 0), Error::<Test>::ProposalHasVotes);
+		assert_ok!(QuadraticVoting::cancel(other_proposer.clone(), 1));
+		System::assert_last_event(crate::Event::Cancelled(1).into());
+		assert_eq!(QuadraticVoting::proposals(1), None);
+		assert_eq!(QuadraticVoting::proposals_by(&2), Vec::<u32>::new());
+		assert_eq!(QuadraticVoting::active_proposal_index().into_inner(), vec![(11, 0), (13, 2)]);
+		// Fail because voting is still ongoing.
+		assert_noop!(QuadraticVoting::finalize(voter.clone(), 0), Error::<Test>::VotingNotEnded);
+		// Set block number to be after the first proposal ended.
+		System::set_block_number(12);
+		assert_ok!(QuadraticVoting::finalize(voter.clone(), 0));
+		System::assert_last_event(crate::Event::Finalized(0, ProposalStatus::Passed).into());
+		assert_noop!(QuadraticVoting::finalize(voter.clone(), 0), Error::<Test>::AlreadyFinalized);
+		assert_eq!(QuadraticVoting::active_proposal_index().into_inner(), vec![(13, 2)]);
+		// Finalized proposals stay available, including to their proposer's index.
+		assert_eq!(QuadraticVoting::proposals_by(&1), vec![0, 2]);
+	});
+}
+
+#[test]
+fn proposals_per_proposer_are_bounded() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+
+		for _ in 0..3 {
+			assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		}
+		System::set_block_number(12);
+		for index in 0..3 {
+			assert_ok!(QuadraticVoting::finalize(proposer.clone(), index));
+		}
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		// Fail because the account already created `MaxProposalsPerProposer` proposals.
+		assert_noop!(
+			QuadraticVoting::propose(proposer.clone(), BoundedVec::default()),
+			Error::<Test>::TooManyProposals
+		);
+		// Cancelled proposals do not count.
+		assert_ok!(QuadraticVoting::cancel(proposer.clone(), 3));
+		assert_ok!(QuadraticVoting::propose(proposer, BoundedVec::default()));
+		assert_eq!(QuadraticVoting::proposals_by(&1), vec![0, 1, 2, 4]);
+	});
+}
+
+#[test]
+fn migration_to_v1_indexes_ended_proposals() {
+	build_and_execute(|| {
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		System::set_block_number(3);
+		assert_ok!(QuadraticVoting::propose(Origin::signed(2), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::propose(Origin::signed(3), BoundedVec::default()));
+		// Storage version 0 had no bound on the number of open proposals.
+		crate::ActiveProposals::<Test>::kill();
+		assert_ok!(QuadraticVoting::propose(Origin::signed(3), BoundedVec::default()));
+		// Drop the indexes, as in storage version 0.
+		crate::ActiveProposals::<Test>::kill();
+		for (proposer, index) in [(1, 0), (2, 1), (3, 2), (3, 3)] {
+			crate::ProposalsByProposer::<Test>::remove(proposer, index);
+		}
+		StorageVersion::new(0).put::<QuadraticVoting>();
+
+		// The first proposal has ended, but it was never finalized.
+		System::set_block_number(12);
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		assert_eq!(QuadraticVoting::on_chain_storage_version(), 1);
+		assert_eq!(
+			QuadraticVoting::active_proposal_index().into_inner(),
+			vec![(11, 0), (13, 1), (13, 2)]
+		);
+		assert_eq!(QuadraticVoting::proposals_by(&2), vec![1]);
+		assert_ok!(QuadraticVoting::finalize(Origin::signed(3), 0));
+
+		// The proposal that did not fit can still be finalized, once.
+		assert_eq!(QuadraticVoting::overflow_proposals(3), Some(()));
+		System::set_block_number(14);
+		assert_ok!(QuadraticVoting::finalize(Origin::signed(3), 3));
+		assert_eq!(QuadraticVoting::overflow_proposals(3), None);
+		assert_noop!(QuadraticVoting::finalize(Origin::signed(3), 3), Error::<Test>::AlreadyFinalized);
 	});
-}
\ No newline at end of file
+}
+
+#[test]
+fn participation_history_is_pruned_on_release() {
+	build_and_execute(|| {
+		let proposer = Origin::signed(1);
+		let voter = Origin::signed(2);
+
+		// Vote on two proposals that reserve the cost and one that pays it into a pot.
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::propose_with_pot(proposer.clone(), BoundedVec::default(), Redistribution::Voters));
+		assert_ok!(QuadraticVoting::vote_aye(voter.clone(), 0, 3));
+		assert_ok!(QuadraticVoting::vote_nay(voter.clone(), 1, 2));
+		assert_ok!(QuadraticVoting::vote_aye(voter.clone(), 2, 1));
+		assert_eq!(QuadraticVoting::voted_proposals(2).into_inner(), vec![0, 1, 2]);
+		assert_eq!(
+			QuadraticVoting::voter_stats(2),
+			VoterStats { votes_cast: 6, credits_spent: 14, outstanding_reserves: 13 }
+		);
+		// Fail because the account already has `MaxVotedProposals` unreleased votes.
+		System::set_block_number(12);
+		for index in 0..3 {
+			assert_ok!(QuadraticVoting::finalize(proposer.clone(), index));
+		}
+		assert_ok!(QuadraticVoting::propose(proposer.clone(), BoundedVec::default()));
+		assert_noop!(QuadraticVoting::vote_aye(voter.clone(), 3, 1), Error::<Test>::TooManyVotedProposals);
+		// Unreserving and being paid out of the pot release the funds of a vote.
+		assert_ok!(QuadraticVoting::unreserve(voter.clone(), 0));
+		assert_ok!(QuadraticVoting::claim_share(voter.clone(), 2));
+		let history = QuadraticVoting::account_history(&2);
+		assert_eq!(history.stats, VoterStats { votes_cast: 6, credits_spent: 14, outstanding_reserves: 4 });
+		assert_eq!(
+			history.votes,
+			vec![(
+				1,
+				VoteInfo {
+					vote: VoteRecord {
+						side: Some(VoteSide::Nay),
+						votes: 2,
+						cost: 4,
+						at_block: 1,
+						released: false,
+					},
+					paid_into_pot: false,
+				},
+			)]
+		);
+		assert_ok!(QuadraticVoting::unreserve(voter, 1));
+		assert!(QuadraticVoting::account_history(&2).votes.is_empty());
+		assert_eq!(QuadraticVoting::voter_stats(2).outstanding_reserves, 0);
+	});
+}
+
+#[test]
+fn genesis_seeds_open_proposals() {
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	let genesis = crate::GenesisConfig::<Test> {
+		proposals: vec![
+			(1, b"first".to_vec().try_into().unwrap(), None),
+			(4, BoundedVec::default(), Some(Redistribution::Voters)),
+		],
+		voting_period: Some(20),
+		phantom: Default::default(),
+	};
+	GenesisBuild::<Test>::assimilate_storage(&genesis, &mut t).unwrap();
+
+	execute_with(sp_io::TestExternalities::new(t), || {
+		// Genesis proposals are open from the first block, even without an identity.
+		assert_eq!(QuadraticVoting::proposal_count(), Some(2));
+		assert_eq!(QuadraticVoting::active_proposals(), vec![0, 1]);
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().end, 20);
+		assert_eq!(QuadraticVoting::proposals_by(&4), vec![1]);
+		assert!(QuadraticVoting::pots(0).is_none());
+		assert_eq!(QuadraticVoting::pots(1).unwrap().target, Redistribution::Voters);
+	});
+}
+
+#[test]
+fn instances_keep_separate_state() {
+	build_and_execute(|| {
+		// Proposals of one instance are not visible to the other.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		assert_ok!(Guild::propose(Origin::signed(2), BoundedVec::default()));
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().proposer, 1);
+		assert_eq!(Guild::proposals(0).unwrap().proposer, 2);
+		// Each instance has its own voting period.
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().end, 11);
+		assert_eq!(Guild::proposals(0).unwrap().end, 6);
+		// Votes and events are recorded per instance.
+		assert_ok!(Guild::vote_aye(Origin::signed(3), 0, 2));
+		System::assert_last_event(crate::Event::<Test, Instance1>::VotedAye(0, 3, 2).into());
+		assert!(QuadraticVoting::votes(0, 3).is_none());
+		assert_ok!(QuadraticVoting::vote_nay(Origin::signed(3), 0, 2));
+		System::assert_last_event(crate::Event::<Test>::VotedNay(0, 3, 2).into());
+		assert_eq!(Guild::proposals(0).unwrap().aye, 2);
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().nay, 2);
+		// Errors are reported by the instance that raised them.
+		assert_noop!(Guild::vote_aye(Origin::signed(3), 0, 1), Error::<Test, Instance1>::AlreadyVoted);
+		System::set_block_number(7);
+		assert_noop!(Guild::vote_aye(Origin::signed(1), 0, 1), Error::<Test, Instance1>::VotingEnded);
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(1), 0, 1));
+	});
+}
+
+#[test]
+fn eligibility_adapters_combine() {
+	build_and_execute(|| {
+		// Accounts 1 and 4 are members, only accounts 1 to 3 have identities.
+		struct Members;
+		impl SortedMembers<AccountId> for Members {
+			fn sorted_members() -> Vec<AccountId> {
+				vec![1, 4]
+			}
+		}
+		type Member = IsMember<Members>;
+		// Accounts with an identity have 40 free after the deposit, account 4 has 50.
+		type Rich = MinBalance<Balances, ConstU64<45>>;
+
+		assert_eq!(HasIdentity::<Test>::eligibility(&1), Ok(None));
+		assert_eq!(HasIdentity::<Test>::eligibility(&4), Err(Refusal::MissingIdentity));
+		assert_eq!(Member::eligibility(&2), Err(Refusal::NotMember));
+		assert_eq!(Rich::eligibility(&1), Err(Refusal::InsufficientBalance));
+		assert_eq!(Rich::eligibility(&4), Ok(None));
+		// Both must agree, and the lower cap applies.
+		type Both = And<Capped<HasIdentity<Test>, ConstU128<3>>, Capped<Member, ConstU128<5>>>;
+		assert_eq!(Both::eligibility(&1), Ok(Some(3)));
+		assert_eq!(Both::eligibility(&2), Err(Refusal::NotMember));
+		assert_eq!(Both::eligibility(&4), Err(Refusal::MissingIdentity));
+		// Either is enough, and the higher cap applies.
+		type Either = Or<Capped<HasIdentity<Test>, ConstU128<3>>, Capped<Rich, ConstU128<5>>>;
+		assert_eq!(Either::eligibility(&2), Ok(Some(3)));
+		assert_eq!(Either::eligibility(&4), Ok(Some(5)));
+		assert_eq!(Or::<Member, Rich>::eligibility(&3), Err(Refusal::NotMember));
+		assert_eq!(Or::<Member, HasIdentity<Test>>::eligibility(&4), Ok(None));
+
+		// The guild caps votes at 2 per proposal.
+		assert_ok!(Guild::propose(Origin::signed(1), BoundedVec::default()));
+		assert_noop!(Guild::vote_aye(Origin::signed(2), 0, 3), Error::<Test, Instance1>::VoteCapExceeded);
+		assert_noop!(Guild::vote_aye(Origin::signed(4), 0, 1), Error::<Test, Instance1>::MissingIdentity);
+		assert_ok!(Guild::vote_aye(Origin::signed(2), 0, 2));
+	});
+}
+
+#[test]
+fn snapshot_proposals_use_checkpoints() {
+	build_and_execute(|| {
+		// Balance changes are only recorded while a snapshot proposal is open.
+		System::set_block_number(2);
+		assert_ok!(Balances::transfer(Origin::signed(3), 2, 5));
+		assert!(QuadraticVoting::checkpoints(2).is_empty());
+		// The first one starts the recording, so it takes its snapshot at the end of its block.
+		assert_ok!(QuadraticVoting::propose_with_snapshot(Origin::signed(1), BoundedVec::default()));
+		assert_eq!(QuadraticVoting::snapshots(0), Some(2));
+		assert_eq!(QuadraticVoting::recording_period(), Some((2, 12)));
+		assert_ok!(Balances::transfer(Origin::signed(3), 2, 10));
+		let blocks: Vec<_> = QuadraticVoting::checkpoints(2).iter().map(|(block, _)| *block).collect();
+		assert_eq!(blocks, vec![0, 2]);
+		assert_eq!(QuadraticVoting::checkpoints(2)[1].1.balance, 55);
+
+		// Funds and identities change after the snapshot block.
+		System::set_block_number(3);
+		assert_ok!(Identity::set_identity(Origin::signed(4), Box::new(identity_info())));
+		assert_ok!(QuadraticVoting::checkpoint(Origin::signed(4), 4));
+		System::assert_last_event(crate::Event::<Test>::Checkpointed(4).into());
+		assert_ok!(QuadraticVoting::propose_with_snapshot(Origin::signed(1), BoundedVec::default()));
+		assert_eq!(QuadraticVoting::snapshots(1), Some(2));
+		assert_eq!(QuadraticVoting::recording_period(), Some((2, 13)));
+		assert_ok!(Balances::transfer(Origin::signed(3), 2, 10));
+
+		// Account 2 holds 65 now, but only the 55 it held at the snapshot count.
+		assert_noop!(QuadraticVoting::vote_aye(Origin::signed(2), 1, 8), Error::<Test>::VoteCapExceeded);
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(2), 1, 7));
+		assert_ok!(QuadraticVoting::vote_nay(Origin::signed(3), 1, 2));
+		// Account 4 only became eligible after the snapshot.
+		assert_noop!(
+			QuadraticVoting::vote_aye(Origin::signed(4), 1, 1),
+			Error::<Test>::NotEligibleAtSnapshot
+		);
+		// Account 1 never changed, so its current state was its state at the snapshot.
+		assert!(QuadraticVoting::checkpoints(1).iter().all(|(block, _)| *block == 0));
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(1), 1, 1));
+
+		// Proposals without a snapshot only look at the current state.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(4), 2, 1));
+
+		// Checkpoints still covering the snapshot of an open proposal are kept.
+		for block in 4..=6 {
+			System::set_block_number(block);
+			assert_ok!(Balances::transfer(Origin::signed(2), 1, 1));
+		}
+		let blocks: Vec<_> = QuadraticVoting::checkpoints(2).iter().map(|(block, _)| *block).collect();
+		assert_eq!(blocks, vec![2, 5, 6]);
+		assert_eq!(QuadraticVoting::checkpoints(2)[0].1.balance, 55);
+
+		// Once the last snapshot proposal has ended, a change drops the checkpoints.
+		System::set_block_number(14);
+		assert_ok!(Balances::transfer(Origin::signed(2), 1, 1));
+		assert!(QuadraticVoting::checkpoints(2).is_empty());
+	});
+}
+
+#[test]
+fn guild_snapshots_follow_asset_transfers() {
+	build_and_execute(|| {
+		// Account 2 parks most of its guild assets with account 4 while nothing is recorded.
+		assert_ok!(Assets::transfer(Origin::signed(2), GUILD_ASSET, 4, 47));
+		System::set_block_number(2);
+		assert_ok!(Guild::propose_with_snapshot(Origin::signed(1), BoundedVec::default()));
+
+		// It gets them back after the snapshot, through a signed transaction.
+		System::set_block_number(3);
+		let call = Call::Assets(pallet_assets::Call::transfer { id: GUILD_ASSET, target: 2, amount: 47 });
+		let info = call.get_dispatch_info();
+		let pre = CheckpointCalls::<Test, Guild, GuildTransfers>::new()
+			.pre_dispatch(&4, &call, &info, 0)
+			.unwrap();
+		assert_eq!(pre, vec![4, 2]);
+		let post_info = call.dispatch(Origin::signed(4)).unwrap();
+		assert_ok!(CheckpointCalls::<Test, Guild, GuildTransfers>::post_dispatch(
+			Some(pre),
+			&info,
+			&post_info,
+			0,
+			&Ok(())
+		));
+		let blocks: Vec<_> = Guild::checkpoints(2).iter().map(|(block, _)| *block).collect();
+		assert_eq!(blocks, vec![0, 3]);
+
+		// Only the 3 assets it held at the snapshot count, which pay for a single vote.
+		assert_noop!(Guild::vote_aye(Origin::signed(2), 0, 2), Error::<Test, Instance1>::VoteCapExceeded);
+		assert_ok!(Guild::vote_aye(Origin::signed(2), 0, 1));
+	});
+}
+
+#[test]
+fn guild_votes_are_charged_in_its_asset() {
+	build_and_execute(|| {
+		assert_ok!(Guild::propose(Origin::signed(1), BoundedVec::default()));
+		assert_ok!(Guild::vote_aye(Origin::signed(3), 0, 2));
+		// The cost is frozen in the guild asset, the native balance is left alone.
+		assert_eq!(Guild::frozen_balance(&3), 4);
+		assert_eq!(Assets::balance(GUILD_ASSET, 3), 50);
+		assert_eq!(Balances::free_balance(3), 40);
+		assert_eq!(Balances::reserved_balance(3), 10);
+		// Only what is not frozen can be moved, keeping the minimum balance of 1.
+		assert_noop!(
+			Assets::transfer(Origin::signed(3), GUILD_ASSET, 4, 46),
+			pallet_assets::Error::<Test>::BalanceLow
+		);
+		assert_ok!(Assets::transfer(Origin::signed(3), GUILD_ASSET, 4, 45));
+		// Votes that are not backed by unfrozen assets are refused.
+		assert_ok!(Guild::propose(Origin::signed(1), BoundedVec::default()));
+		assert_noop!(Guild::vote_aye(Origin::signed(3), 1, 2), TokenError::NoFunds);
+		// Unreserving after voting is over unfreezes the cost.
+		System::set_block_number(7);
+		assert_ok!(Guild::unreserve(Origin::signed(3), 0));
+		assert_eq!(Guild::frozen_balance(&3), 0);
+		assert_ok!(Assets::transfer(Origin::signed(3), GUILD_ASSET, 4, 5));
+	});
+}
+
+#[test]
+fn eligible_voters_vote_without_fees() {
+	build_and_execute(|| {
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		// Calls that are expected to succeed for free get the fee-less pool priority.
+		let vote = crate::Call::<Test>::vote_aye { proposal_index: 0, votes: 2 };
+		assert_eq!(QuadraticVoting::free_call_validity(&2, &vote).unwrap().priority, 1);
+		assert!(QuadraticVoting::free_call_validity(&4, &vote).is_none());
+		// The fee of a successful vote of an eligible account is waived.
+		assert_eq!(QuadraticVoting::vote_aye(Origin::signed(2), 0, 2).unwrap().pays_fee, Pays::No);
+		assert_eq!(QuadraticVoting::free_calls(0, &2), 1);
+		// Other proposals come with their own free calls.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		assert_eq!(QuadraticVoting::vote_aye(Origin::signed(2), 1, 1).unwrap().pays_fee, Pays::No);
+		// Once the free calls on a proposal are used up, calls on it pay again.
+		System::set_block_number(12);
+		let unreserve = crate::Call::<Test>::unreserve { proposal_index: 0 };
+		assert!(QuadraticVoting::free_call_validity(&2, &unreserve).is_none());
+		assert_eq!(QuadraticVoting::unreserve(Origin::signed(2), 0).unwrap().pays_fee, Pays::Yes);
+		// Settling the vote forgets the count.
+		assert_eq!(QuadraticVoting::free_calls(0, &2), 0);
+		assert_eq!(QuadraticVoting::free_calls(1, &2), 1);
+
+		// The guild allows two free calls per proposal, enough to also unreserve for free.
+		assert_ok!(Guild::propose(Origin::signed(1), BoundedVec::default()));
+		assert_eq!(Guild::vote_nay(Origin::signed(3), 0, 1).unwrap().pays_fee, Pays::No);
+		System::set_block_number(18);
+		let unreserve = crate::Call::<Test, Instance1>::unreserve { proposal_index: 0 };
+		assert!(Guild::free_call_validity(&3, &unreserve).is_some());
+		assert_eq!(Guild::unreserve(Origin::signed(3), 0).unwrap().pays_fee, Pays::No);
+	});
+}
+
+#[test]
+fn relayers_submit_permitted_votes() {
+	build_and_execute(|| {
+		let validate = |call: &crate::Call<Test>| {
+			QuadraticVoting::validate_unsigned(TransactionSource::External, call)
+		};
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		let permit = VotePermit {
+			voter: 2,
+			proposal_index: 0,
+			side: VoteSide::Nay,
+			votes: 3,
+			nonce: 0,
+			deadline: 5,
+		};
+		let signature = TestSignature(2, QuadraticVoting::permit_payload(&permit));
+
+		// Permits signed by anyone but the voter are refused.
+		let forged = TestSignature(4, QuadraticVoting::permit_payload(&permit));
+		assert_noop!(
+			QuadraticVoting::vote_with_permit(Origin::none(), permit.clone(), forged),
+			Error::<Test>::BadPermitSignature
+		);
+		// Valid permits are accepted by the pool as unsigned transactions.
+		let call = crate::Call::<Test>::vote_with_permit {
+			permit: permit.clone(),
+			signature: signature.clone(),
+		};
+		assert_eq!(validate(&call).unwrap().priority, 1);
+
+		// A relayer submits the permit, and the cost is reserved from the voter.
+		let relayer = Origin::signed(4);
+		assert_ok!(QuadraticVoting::vote_with_permit(relayer, permit.clone(), signature.clone()));
+		assert_eq!(QuadraticVoting::proposals(0).unwrap().nay, 3);
+		assert_eq!(QuadraticVoting::votes(0, 2).unwrap().cost, 9);
+		assert_eq!(Balances::reserved_balance(4), 0);
+
+		// Permits can only be used once.
+		assert_eq!(QuadraticVoting::permit_nonce(2), 1);
+		assert_eq!(validate(&call), Err(InvalidTransaction::Stale.into()));
+		assert_noop!(
+			QuadraticVoting::vote_with_permit(Origin::none(), permit.clone(), signature),
+			Error::<Test>::BadPermitNonce
+		);
+
+		// Permits with later nonces wait in the pool for the earlier ones.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		let validate_nonce = |nonce| {
+			let permit = VotePermit { proposal_index: 1, nonce, ..permit.clone() };
+			let signature = TestSignature(2, QuadraticVoting::permit_payload(&permit));
+			validate(&crate::Call::<Test>::vote_with_permit { permit, signature }).unwrap()
+		};
+		let (next, later) = (validate_nonce(1), validate_nonce(2));
+		assert!(next.requires.is_empty());
+		assert_eq!(later.requires, next.provides);
+
+		// Expired permits are refused.
+		let late = VotePermit { voter: 3, ..permit };
+		let signature = TestSignature(3, QuadraticVoting::permit_payload(&late));
+		System::set_block_number(6);
+		assert_noop!(
+			QuadraticVoting::vote_with_permit(Origin::none(), late, signature),
+			Error::<Test>::PermitExpired
+		);
+	});
+}
+
+#[test]
+fn offchain_worker_settles_ended_proposals() {
+	let mut ext = new_test_ext();
+	let (offchain, _) = TestOffchainExt::new();
+	let (pool, pool_state) = TestTransactionPoolExt::new();
+	ext.register_extension(OffchainDbExt::new(offchain.clone()));
+	ext.register_extension(OffchainWorkerExt::new(offchain));
+	ext.register_extension(TransactionPoolExt::new(pool));
+
+	execute_with(ext, || {
+		let submitted = || {
+			let tx = pool_state.write().transactions.pop()?;
+			Some(Extrinsic::decode(&mut &tx[..]).unwrap())
+		};
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), BoundedVec::default()));
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(2), 0, 2));
+
+		// Nodes without a key stay idle.
+		System::set_block_number(11);
+		QuadraticVoting::offchain_worker(11);
+		assert!(submitted().is_none());
+
+		// Ended proposals are finalized, signed by the key of the node.
+		UintAuthorityId::set_all_keys(vec![5]);
+		QuadraticVoting::offchain_worker(11);
+		let tx = submitted().unwrap();
+		assert_eq!(tx.signature, Some((5, ())));
+		assert_eq!(tx.call, Call::QuadraticVoting(crate::Call::finalize { proposal_index: 0 }));
+
+		// Reserves of finalized proposals are refunded.
+		System::set_block_number(12);
+		assert_ok!(QuadraticVoting::finalize(Origin::signed(5), 0));
+		QuadraticVoting::offchain_worker(12);
+		let tx = submitted().unwrap();
+		assert_eq!(tx.call, Call::QuadraticVoting(crate::Call::refund_votes { proposal_index: 0 }));
+		assert_ok!(QuadraticVoting::refund_votes(Origin::signed(5), 0));
+		System::assert_last_event(crate::Event::<Test>::Refunded(0, 2, 4).into());
+		assert_eq!(Balances::reserved_balance(2), 10);
+		assert_eq!(QuadraticVoting::voted_proposals(2).len(), 0);
+
+		// Nothing is left to settle.
+		QuadraticVoting::offchain_worker(13);
+		assert!(submitted().is_none());
+		assert_noop!(QuadraticVoting::refund_votes(Origin::signed(5), 0), Error::<Test>::NotFinalized);
+
+		// Both instances have a proposal to finalize, but sign with the same key.
+		System::set_block_number(13);
+		let pot = Redistribution::Voters;
+		assert_ok!(QuadraticVoting::propose_with_pot(Origin::signed(1), BoundedVec::default(), pot));
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(3), 1, 1));
+		assert_ok!(Guild::propose(Origin::signed(1), BoundedVec::default()));
+		assert_ok!(Guild::vote_nay(Origin::signed(3), 0, 1));
+		System::set_block_number(23);
+		QuadraticVoting::offchain_worker(23);
+		Guild::offchain_worker(23);
+		assert_eq!(pool_state.read().transactions.len(), 1);
+		let tx = submitted().unwrap();
+		assert_eq!(tx.call, Call::QuadraticVoting(crate::Call::finalize { proposal_index: 1 }));
+
+		// Finalized pots are queued for their payout, which comes before the other instance.
+		System::set_block_number(24);
+		assert_ok!(QuadraticVoting::finalize(Origin::signed(5), 1));
+		assert_eq!(QuadraticVoting::due_payouts().into_inner(), vec![(23, 1)]);
+		QuadraticVoting::offchain_worker(24);
+		Guild::offchain_worker(24);
+		assert_eq!(pool_state.read().transactions.len(), 1);
+		let tx = submitted().unwrap();
+		assert_eq!(tx.call, Call::QuadraticVoting(crate::Call::payout_shares { proposal_index: 1 }));
+		assert_ok!(QuadraticVoting::payout_shares(Origin::signed(5), 1));
+		assert!(QuadraticVoting::due_payouts().is_empty());
+
+		// The other instance gets its turn once the first one is settled.
+		System::set_block_number(25);
+		QuadraticVoting::offchain_worker(25);
+		Guild::offchain_worker(25);
+		let tx = submitted().unwrap();
+		assert_eq!(tx.call, Call::Guild(crate::Call::finalize { proposal_index: 0 }));
+	});
+}
+
+#[test]
+fn passed_proposals_are_enacted_after_a_delay() {
+	build_and_execute(|| {
+		let call = Call::Balances(pallet_balances::Call::set_balance {
+			who: 4,
+			new_free: 100,
+			new_reserved: 0,
+		});
+		let hash = BlakeTwo256::hash(&call.encode());
+		assert_ok!(Preimage::note_preimage(Origin::root(), call.encode()));
+		let noted: BoundedVec<u8, _> = hash.as_ref().to_vec().try_into().unwrap();
+		let unknown: BoundedVec<u8, _> = vec![1; 32].try_into().unwrap();
+
+		// Pass a proposal of the noted call, one of an unknown call and one to be cancelled.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), noted.clone()));
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), unknown));
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), noted));
+		for index in 0..3 {
+			assert_ok!(QuadraticVoting::vote_aye(Origin::signed(2), index, 2));
+		}
+		System::set_block_number(12);
+		for index in 0..3 {
+			assert_ok!(QuadraticVoting::finalize(Origin::signed(3), index));
+			System::assert_last_event(crate::Event::Scheduled(index, 14).into());
+		}
+		assert_eq!(QuadraticVoting::enactments(0), Some(14));
+
+		// Only the cancel origin can drop an enactment.
+		assert_noop!(QuadraticVoting::cancel_enactment(Origin::signed(1), 2), BadOrigin);
+		assert_ok!(QuadraticVoting::cancel_enactment(Origin::root(), 2));
+		System::assert_last_event(crate::Event::EnactmentCancelled(2).into());
+		assert_noop!(
+			QuadraticVoting::cancel_enactment(Origin::root(), 2),
+			Error::<Test>::NotScheduled
+		);
+		// Enactments can only be dispatched by the scheduler.
+		assert_noop!(QuadraticVoting::enact(Origin::signed(1), 0), BadOrigin);
+
+		// Nothing happens before the delay is over.
+		System::set_block_number(13);
+		Scheduler::on_initialize(13);
+		assert_eq!(Balances::free_balance(4), 50);
+		System::set_block_number(14);
+		Scheduler::on_initialize(14);
+		assert_eq!(Balances::free_balance(4), 100);
+		System::assert_has_event(crate::Event::Enacted(0, Ok(())).into());
+		System::assert_has_event(
+			crate::Event::EnactmentFailed(1, Error::<Test>::PreimageMissing.into()).into(),
+		);
+		assert!(!System::events().iter().any(|record| matches!(
+			record.event,
+			Event::QuadraticVoting(crate::Event::Enacted(2, _))
+		)));
+		assert_eq!(QuadraticVoting::enactments(0), None);
+
+		// Proposals that pass with too few votes are not enacted.
+		assert_ok!(QuadraticVoting::propose(Origin::signed(1), hash.as_ref().to_vec().try_into().unwrap()));
+		assert_ok!(QuadraticVoting::vote_aye(Origin::signed(3), 3, 1));
+		System::set_block_number(25);
+		assert_ok!(QuadraticVoting::finalize(Origin::signed(3), 3));
+		System::assert_last_event(
+			crate::Event::EnactmentFailed(3, Error::<Test>::TurnoutTooLow.into()).into(),
+		);
+		assert_eq!(QuadraticVoting::enactments(3), None);
+	});
+}
+
+#[test]
+fn guild_proposals_are_enacted_from_its_account() {
+	build_and_execute(|| {
+		let call = Call::System(frame_system::Call::remark_with_event { remark: vec![7] });
+		let hash = BlakeTwo256::hash(&call.encode());
+		assert_ok!(Preimage::note_preimage(Origin::root(), call.encode()));
+		assert_ok!(Guild::propose(Origin::signed(1), hash.as_ref().to_vec().try_into().unwrap()));
+		assert_ok!(Guild::vote_aye(Origin::signed(2), 0, 1));
+		System::set_block_number(7);
+		assert_ok!(Guild::finalize(Origin::signed(3), 0));
+		System::assert_last_event(crate::Event::<Test, Instance1>::Scheduled(0, 9).into());
+
+		// Only the scheduler, from the account of the guild, can enact.
+		assert_noop!(Guild::enact(Origin::root(), 0), BadOrigin);
+		System::set_block_number(9);
+		Scheduler::on_initialize(9);
+		System::assert_has_event(crate::Event::<Test, Instance1>::Enacted(0, Ok(())).into());
+		let remarked = frame_system::Event::Remarked {
+			sender: Guild::account_id(),
+			hash: BlakeTwo256::hash(&[7]),
+		};
+		System::assert_has_event(remarked.into());
+	});
+}
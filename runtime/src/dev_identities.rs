@@ -0,0 +1,84 @@
+//! Genesis helper that registers identities for development chains.
+//!
+//! `pallet_identity` has no genesis config, so fresh chains start without identities and nobody
+//! can propose or vote until identities are set up by hand. This pallet sets identities, and
+//! optionally a registrar with `KnownGood` judgements, at genesis by dispatching the identity
+//! calls on behalf of the configured accounts. Identity deposits are reserved as usual, so the
+//! accounts must be endowed at genesis.
+
+pub use pallet::*;
+
+#[frame_support::pallet]
+pub mod pallet {
+	use frame_support::{pallet_prelude::*, traits::GenesisBuild};
+	use frame_system::RawOrigin;
+	use pallet_identity::{Data, IdentityInfo, Judgement};
+	use sp_runtime::traits::StaticLookup;
+	use sp_std::prelude::*;
+
+	#[pallet::config]
+	pub trait Config: frame_system::Config + pallet_identity::Config {}
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig<T: Config> {
+		/// Account that is added as registrar and judges every identity as `KnownGood`.
+		pub registrar: Option<T::AccountId>,
+		/// Accounts to set identities for, with their display names.
+		pub identities: Vec<(T::AccountId, Vec<u8>)>,
+	}
+
+	#[cfg(feature = "std")]
+	impl<T: Config> Default for GenesisConfig<T> {
+		fn default() -> Self {
+			Self { registrar: None, identities: Vec::new() }
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
+		fn build(&self) {
+			let registrar_index = self.registrar.as_ref().map(|registrar| {
+				pallet_identity::Pallet::<T>::add_registrar(
+					RawOrigin::Root.into(),
+					registrar.clone(),
+				)
+				.expect("registrar origin is root; qed");
+				pallet_identity::Pallet::<T>::registrars().len() as u32 - 1
+			});
+
+			for (who, display) in self.identities.iter() {
+				let info = IdentityInfo {
+					additional: Default::default(),
+					display: Data::Raw(
+						display.clone().try_into().expect("display names are at most 32 bytes"),
+					),
+					legal: Data::None,
+					web: Data::None,
+					riot: Data::None,
+					email: Data::None,
+					pgp_fingerprint: None,
+					image: Data::None,
+					twitter: Data::None,
+				};
+				pallet_identity::Pallet::<T>::set_identity(
+					RawOrigin::Signed(who.clone()).into(),
+					Box::new(info),
+				)
+				.expect("identity accounts must be endowed with the identity deposit");
+
+				if let (Some(registrar), Some(index)) = (&self.registrar, registrar_index) {
+					pallet_identity::Pallet::<T>::provide_judgement(
+						RawOrigin::Signed(registrar.clone()).into(),
+						index,
+						T::Lookup::unlookup(who.clone()),
+						Judgement::KnownGood,
+					)
+					.expect("registrar was added above and the identity was just set; qed");
+				}
+			}
+		}
+	}
+}
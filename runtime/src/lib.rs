@@ -6,17 +6,24 @@
 #[cfg(feature = "std")]
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
+pub mod dev_identities;
+
 use pallet_grandpa::{
 	fg_primitives, AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList,
 };
+use codec::{Decode, Encode};
+use scale_info::TypeInfo;
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::{
 	create_runtime_str, generic, impl_opaque_keys,
-	traits::{AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, Verify},
-	transaction_validity::{TransactionSource, TransactionValidity},
-	ApplyExtrinsicResult, MultiSignature,
+	traits::{
+		self, AccountIdConversion, AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount,
+		NumberFor, SaturatedConversion, StaticLookup, Verify,
+	},
+	transaction_validity::{TransactionSource, TransactionValidity, ValidTransaction},
+	ApplyExtrinsicResult, MultiSignature, RuntimeDebug,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -25,15 +32,18 @@ use sp_version::RuntimeVersion;
 
 // A few exports that help ease life for downstream crates.
 pub use frame_support::{
-	construct_runtime, parameter_types,
+	construct_runtime,
+	instances::Instance1,
+	parameter_types,
 	traits::{
-		ConstU128, ConstU32, ConstU64, ConstU8, KeyOwnerProofSystem, Randomness, StorageInfo,
+		ConstU128, ConstU32, ConstU64, ConstU8, EqualPrivilegeOnly, KeyOwnerProofSystem,
+		Randomness, StorageInfo,
 	},
 	weights::{
 		constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
 		IdentityFee, Weight,
 	},
-	StorageValue,
+	PalletId, StorageValue,
 };
 pub use frame_system::Call as SystemCall;
 pub use pallet_balances::Call as BalancesCall;
@@ -42,10 +52,13 @@ use pallet_transaction_payment::CurrencyAdapter;
 #[cfg(any(feature = "std", test))]
 pub use sp_runtime::BuildStorage;
 pub use sp_runtime::{Perbill, Permill};
-pub use frame_system::EnsureRoot;
+pub use frame_system::{EnsureRoot, RawOrigin};
 
 /// Import the quadratic voting pallet.
-pub use pallet_quadratic_voting::IdentityVerifier;
+use pallet_quadratic_voting::{
+	currency::{ChangedAccounts, CheckpointCalls, CheckpointOnChange, FrozenAsset, NativeReserve},
+	eligibility::{And, HasIdentity, MinBalance},
+};
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -129,6 +142,8 @@ pub const DAYS: BlockNumber = HOURS * 24;
 // OTP = the base number of indivisible units for balances
 pub const DOLLARS: Balance = 1_000_000_000_000;
 pub const CENTS: Balance = 1_000_000_000;
+/// Minimum balance that keeps an account alive.
+pub const EXISTENTIAL_DEPOSIT: Balance = 500;
 
 /// The version information used to identify this runtime when compiled natively.
 #[cfg(feature = "std")]
@@ -248,8 +263,8 @@ impl pallet_balances::Config for Runtime {
 	/// The ubiquitous event type.
 	type Event = Event;
 	type DustRemoval = ();
-	type ExistentialDeposit = ConstU128<500>;
-	type AccountStore = System;
+	type ExistentialDeposit = ConstU128<EXISTENTIAL_DEPOSIT>;
+	type AccountStore = CheckpointOnChange<System, QuadraticVoting>;
 	type WeightInfo = pallet_balances::weights::SubstrateWeight<Runtime>;
 }
 
@@ -291,24 +306,319 @@ impl pallet_identity::Config for Runtime {
 	type WeightInfo = pallet_identity::weights::SubstrateWeight<Runtime>;
 }
 
-pub struct VotingIdentityVerifier;
-impl IdentityVerifier<AccountId> for VotingIdentityVerifier {
-	fn has_identity(who: &AccountId, fields: u64) -> bool {
-		Identity::has_identity(who, fields)
+impl dev_identities::Config for Runtime {}
+
+/// Identifier of an asset in pallet-assets.
+pub type AssetId = u32;
+
+parameter_types! {
+	pub const AssetDeposit: Balance = 100 * DOLLARS;
+	pub const AssetAccountDeposit: Balance = 1 * DOLLARS;
+	pub const MetadataDepositBase: Balance = 10 * DOLLARS;
+	pub const MetadataDepositPerByte: Balance = 1 * DOLLARS;
+	pub const ApprovalDeposit: Balance = 1 * DOLLARS;
+	pub const AssetStringLimit: u32 = 50;
+	/// Governance token that the engineering guild votes with, created in the chain spec.
+	pub const GovernanceAssetId: AssetId = 1;
+}
+
+/// Reports guild votes that are not unreserved yet as frozen governance tokens.
+pub struct GovernanceFreezer;
+
+impl pallet_assets::FrozenBalance<AssetId, AccountId, Balance> for GovernanceFreezer {
+	fn frozen_balance(asset: AssetId, who: &AccountId) -> Option<Balance> {
+		let frozen = EngineeringGuild::frozen_balance(who);
+		if asset == GovernanceAssetId::get() && frozen > 0 {
+			Some(frozen)
+		} else {
+			None
+		}
+	}
+
+	fn died(_asset: AssetId, _who: &AccountId) {}
+}
+
+/// Accounts whose governance tokens a call may change, so the engineering guild can record
+/// checkpoints around it. Calls dispatched through `Sudo` are looked into.
+pub struct GovernanceTransfers;
+
+impl ChangedAccounts<AccountId, Call> for GovernanceTransfers {
+	fn changed_accounts(who: &AccountId, call: &Call) -> Vec<AccountId> {
+		let lookup =
+			|address: &Address| AccountIdLookup::<AccountId, ()>::lookup(address.clone()).ok();
+		match call {
+			Call::Assets(call) => {
+				use pallet_assets::Call as AssetsCall;
+				let (id, accounts) = match call {
+					AssetsCall::transfer { id, target, .. } |
+					AssetsCall::transfer_keep_alive { id, target, .. } =>
+						(id, vec![Some(who.clone()), lookup(target)]),
+					AssetsCall::force_transfer { id, source, dest, .. } =>
+						(id, vec![lookup(source), lookup(dest)]),
+					AssetsCall::transfer_approved { id, owner, destination, .. } =>
+						(id, vec![lookup(owner), lookup(destination)]),
+					AssetsCall::mint { id, beneficiary, .. } => (id, vec![lookup(beneficiary)]),
+					AssetsCall::burn { id, who: target, .. } |
+					AssetsCall::freeze { id, who: target } |
+					AssetsCall::thaw { id, who: target } => (id, vec![lookup(target)]),
+					AssetsCall::refund { id, .. } => (id, vec![Some(who.clone())]),
+					_ => return Vec::new(),
+				};
+				if *id == GovernanceAssetId::get() {
+					accounts.into_iter().flatten().collect()
+				} else {
+					Vec::new()
+				}
+			},
+			Call::Sudo(
+				pallet_sudo::Call::sudo { call } |
+				pallet_sudo::Call::sudo_unchecked_weight { call, .. },
+			) => Self::changed_accounts(who, call),
+			Call::Sudo(pallet_sudo::Call::sudo_as { who, call }) =>
+				lookup(who).map(|who| Self::changed_accounts(&who, call)).unwrap_or_default(),
+			_ => Vec::new(),
+		}
 	}
 }
 
+impl pallet_assets::Config for Runtime {
+	type Event = Event;
+	type Balance = Balance;
+	type AssetId = AssetId;
+	type Currency = Balances;
+	type ForceOrigin = EnsureRoot<AccountId>;
+	type AssetDeposit = AssetDeposit;
+	type AssetAccountDeposit = AssetAccountDeposit;
+	type MetadataDepositBase = MetadataDepositBase;
+	type MetadataDepositPerByte = MetadataDepositPerByte;
+	type ApprovalDeposit = ApprovalDeposit;
+	type StringLimit = AssetStringLimit;
+	type Freezer = GovernanceFreezer;
+	type Extra = ();
+	type WeightInfo = pallet_assets::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+	pub const PreimageMaxSize: u32 = 4096 * 1024;
+	pub const PreimageBaseDeposit: Balance = 1 * DOLLARS;
+	pub const PreimageByteDeposit: Balance = 1 * CENTS;
+}
+
+impl pallet_preimage::Config for Runtime {
+	type WeightInfo = pallet_preimage::weights::SubstrateWeight<Runtime>;
+	type Event = Event;
+	type Currency = Balances;
+	type ManagerOrigin = EnsureRoot<AccountId>;
+	type MaxSize = PreimageMaxSize;
+	type BaseDeposit = PreimageBaseDeposit;
+	type ByteDeposit = PreimageByteDeposit;
+}
+
+parameter_types! {
+	pub MaximumSchedulerWeight: Weight = Perbill::from_percent(80) *
+		BlockWeights::get().max_block;
+	pub const NoPreimagePostponement: Option<BlockNumber> = Some(10);
+}
+
+impl pallet_scheduler::Config for Runtime {
+	type Event = Event;
+	type Origin = Origin;
+	type PalletsOrigin = OriginCaller;
+	type Call = Call;
+	type MaximumWeight = MaximumSchedulerWeight;
+	type ScheduleOrigin = EnsureRoot<AccountId>;
+	type MaxScheduledPerBlock = ConstU32<50>;
+	type WeightInfo = pallet_scheduler::weights::SubstrateWeight<Runtime>;
+	type OriginPrivilegeCmp = EqualPrivilegeOnly;
+	type PreimageProvider = Preimage;
+	type NoPreimagePostponement = NoPreimagePostponement;
+}
+
 parameter_types! {
 	pub const VotingPeriod: BlockNumber = 1 * MINUTES;       // 258 bytes on-chain
+	pub const QuadraticVotingPalletId: PalletId = PalletId(*b"py/qvote");
+	pub const ClaimPeriod: BlockNumber = 1 * MINUTES;
+	pub const EnactmentDelay: BlockNumber = 1 * MINUTES;
+	pub MaxEnactmentWeight: Weight = Perbill::from_percent(50) * BlockWeights::get().max_block;
+	pub RootEnactment: RawOrigin<AccountId> = RawOrigin::Root;
 }
 
 /// Configure the pallet-quadratic-voting in pallets/quadratic-voting.
 impl pallet_quadratic_voting::Config for Runtime {
 	type Event = Event;
-	type Currency = Balances;
-	type IdentityVerifier = VotingIdentityVerifier;
+	/// Vote costs are reserved from native balances
+	type Currency = NativeReserve<Balances>;
+	/// Accounts with an identity can propose and vote
+	type VoterEligibility = HasIdentity<Runtime>;
 	/// Voting period for proposal
 	type VotingPeriod = VotingPeriod;
+	/// Account that holds the pots of budget-balanced proposals
+	type PalletId = QuadraticVotingPalletId;
+	/// Period after voting during which identities can enroll for a share of a pot
+	type ClaimPeriod = ClaimPeriod;
+	type MaxPayoutsPerBatch = ConstU32<64>;
+	type MaxDuePayouts = ConstU32<256>;
+	/// Maximum number of proposals awaiting finalization
+	type MaxActiveProposals = ConstU32<256>;
+	/// Maximum number of proposals an account can create
+	type MaxProposalsPerProposer = ConstU32<1024>;
+	type MaxVotedProposals = ConstU32<128>;
+	/// Checkpoints kept per account for proposals with a snapshot
+	type MaxCheckpoints = ConstU32<16>;
+	/// Two votes or unreserves per proposal and account are fee-less
+	type MaxFreeCalls = ConstU32<2>;
+	/// Fee-less calls queue behind every transaction that pays a fee
+	type FreeCallPriority = ConstU64<0>;
+	/// Vote permits are signed with account keys
+	type PermitSignature = Signature;
+	type PermitSigner = <Signature as Verify>::Signer;
+	/// Offchain worker transactions are signed with `qvot` keys
+	type AuthorityId = pallet_quadratic_voting::crypto::OffchainAuthId;
+	/// Passed proposals enact the call noted under their hash
+	type Proposal = Call;
+	type PalletsOrigin = OriginCaller;
+	type Scheduler = Scheduler;
+	type Preimages = Preimage;
+	/// Blocks between finalization and enactment
+	type EnactmentDelay = EnactmentDelay;
+	type MaxEnactmentWeight = MaxEnactmentWeight;
+	/// Passed proposals are enacted as root
+	type EnactmentOrigin = RootEnactment;
+	type EnactmentPriority = ConstU8<63>;
+	/// At least ten votes have to be cast for a passed proposal to be enacted
+	type MinEnactmentTurnout = ConstU128<10>;
+	/// Root can cancel a scheduled enactment
+	type CancelOrigin = EnsureRoot<AccountId>;
+}
+
+parameter_types! {
+	pub const GuildVotingPeriod: BlockNumber = 10 * MINUTES;
+	pub const GuildPalletId: PalletId = PalletId(*b"py/qvgld");
+	pub const GuildMinBalance: Balance = 100 * DOLLARS;
+	pub const GuildEnactmentDelay: BlockNumber = 10 * MINUTES;
+	pub GuildEnactment: RawOrigin<AccountId> =
+		RawOrigin::Signed(GuildPalletId::get().into_account_truncating());
+}
+
+/// Configure a second, independent instance of pallet-quadratic-voting for the engineering guild.
+impl pallet_quadratic_voting::Config<Instance1> for Runtime {
+	type Event = Event;
+	/// Vote costs are frozen in the governance token
+	type Currency = FrozenAsset<Assets, GovernanceAssetId>;
+	/// Accounts with an identity and a minimum balance can propose and vote
+	type VoterEligibility = And<HasIdentity<Runtime>, MinBalance<Balances, GuildMinBalance>>;
+	/// Voting period for proposal
+	type VotingPeriod = GuildVotingPeriod;
+	/// Account that holds the pots of budget-balanced proposals
+	type PalletId = GuildPalletId;
+	/// Period after voting during which identities can enroll for a share of a pot
+	type ClaimPeriod = ClaimPeriod;
+	type MaxPayoutsPerBatch = ConstU32<64>;
+	type MaxDuePayouts = ConstU32<256>;
+	/// Maximum number of proposals awaiting finalization
+	type MaxActiveProposals = ConstU32<64>;
+	/// Maximum number of proposals an account can create
+	type MaxProposalsPerProposer = ConstU32<256>;
+	type MaxVotedProposals = ConstU32<64>;
+	/// Checkpoints kept per account for proposals with a snapshot
+	type MaxCheckpoints = ConstU32<16>;
+	/// Two votes or unreserves per proposal and account are fee-less
+	type MaxFreeCalls = ConstU32<2>;
+	/// Fee-less calls queue behind every transaction that pays a fee
+	type FreeCallPriority = ConstU64<0>;
+	/// Vote permits are signed with account keys
+	type PermitSignature = Signature;
+	type PermitSigner = <Signature as Verify>::Signer;
+	/// Offchain worker transactions are signed with `qvot` keys
+	type AuthorityId = pallet_quadratic_voting::crypto::OffchainAuthId;
+	/// Passed proposals enact the call noted under their hash
+	type Proposal = Call;
+	type PalletsOrigin = OriginCaller;
+	type Scheduler = Scheduler;
+	type Preimages = Preimage;
+	/// Blocks between finalization and enactment
+	type EnactmentDelay = GuildEnactmentDelay;
+	type MaxEnactmentWeight = MaxEnactmentWeight;
+	/// Passed proposals are enacted from the guild account, not as root
+	type EnactmentOrigin = GuildEnactment;
+	type EnactmentPriority = ConstU8<63>;
+	type MinEnactmentTurnout = ConstU128<5>;
+	/// Root can cancel a scheduled enactment
+	type CancelOrigin = EnsureRoot<AccountId>;
+}
+
+/// Instances of pallet-quadratic-voting, as the `QuadraticVotingApi` runtime API selects them.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo)]
+pub enum QuadraticVotingInstance {
+	/// The main voting body, `QuadraticVoting`.
+	QuadraticVoting,
+	/// The engineering guild, `EngineeringGuild`.
+	EngineeringGuild,
+}
+
+/// Evaluates `$body` with `$pallet` standing for the pallet instance `$instance` selects.
+macro_rules! with_instance {
+	($instance:expr, $pallet:ident => $body:expr) => {
+		match $instance {
+			QuadraticVotingInstance::QuadraticVoting => {
+				type $pallet = QuadraticVoting;
+				$body
+			},
+			QuadraticVotingInstance::EngineeringGuild => {
+				type $pallet = EngineeringGuild;
+				$body
+			},
+		}
+	};
+}
+
+impl frame_system::offchain::SigningTypes for Runtime {
+	type Public = <Signature as Verify>::Signer;
+	type Signature = Signature;
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Runtime
+where
+	Call: From<C>,
+{
+	type OverarchingCall = Call;
+	type Extrinsic = UncheckedExtrinsic;
+}
+
+/// Lets offchain workers, like the one of pallet-quadratic-voting, submit signed transactions.
+impl<LocalCall> frame_system::offchain::CreateSignedTransaction<LocalCall> for Runtime
+where
+	Call: From<LocalCall>,
+{
+	fn create_transaction<C: frame_system::offchain::AppCrypto<Self::Public, Self::Signature>>(
+		call: Call,
+		public: <Signature as Verify>::Signer,
+		account: AccountId,
+		nonce: Index,
+	) -> Option<(Call, <UncheckedExtrinsic as traits::Extrinsic>::SignaturePayload)> {
+		let period = BlockHashCount::get().checked_next_power_of_two().map(|c| c / 2).unwrap_or(2);
+		// The transaction is included in the next block at the earliest.
+		let current_block = System::block_number().saturated_into::<u64>().saturating_sub(1);
+		let extra: SignedExtra = (
+			frame_system::CheckNonZeroSender::<Runtime>::new(),
+			frame_system::CheckSpecVersion::<Runtime>::new(),
+			frame_system::CheckTxVersion::<Runtime>::new(),
+			frame_system::CheckGenesis::<Runtime>::new(),
+			frame_system::CheckEra::<Runtime>::from(generic::Era::mortal(
+				period as u64,
+				current_block,
+			)),
+			frame_system::CheckNonce::<Runtime>::from(nonce),
+			frame_system::CheckWeight::<Runtime>::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+			CheckpointCalls::<Runtime, EngineeringGuild, GovernanceTransfers>::new(),
+		);
+		let raw_payload = SignedPayload::new(call, extra).ok()?;
+		let signature = raw_payload.using_encoded(|payload| C::sign(payload, public))?;
+		let address = AccountIdLookup::unlookup(account);
+		let (call, extra, _) = raw_payload.deconstruct();
+		Some((call, (address, signature, extra)))
+	}
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -329,7 +639,11 @@ construct_runtime!(
 		// Pallets added to substrate node template
 		Identity: pallet_identity,
 		QuadraticVoting: pallet_quadratic_voting,
-
+		DevIdentities: dev_identities::{Pallet, Config<T>},
+		EngineeringGuild: pallet_quadratic_voting::<Instance1>,
+		Assets: pallet_assets,
+		Scheduler: pallet_scheduler,
+		Preimage: pallet_preimage,
 	}
 );
 
@@ -349,11 +663,21 @@ pub type SignedExtra = (
 	frame_system::CheckNonce<Runtime>,
 	frame_system::CheckWeight<Runtime>,
 	pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+	CheckpointCalls<Runtime, EngineeringGuild, GovernanceTransfers>,
 );
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
 /// The payload being signed in transactions.
 pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
+/// Storage migrations applied on runtime upgrade.
+pub type Migrations = (
+	pallet_quadratic_voting::migrations::v1::MigrateToV1<Runtime>,
+	pallet_quadratic_voting::migrations::v2::MigrateToV2<Runtime>,
+	pallet_quadratic_voting::migrations::v3::MigrateToV3<Runtime>,
+	pallet_quadratic_voting::migrations::v1::MigrateToV1<Runtime, Instance1>,
+	pallet_quadratic_voting::migrations::v2::MigrateToV2<Runtime, Instance1>,
+	pallet_quadratic_voting::migrations::v3::MigrateToV3<Runtime, Instance1>,
+);
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
 	Runtime,
@@ -361,6 +685,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	Migrations,
 >;
 
 #[cfg(feature = "runtime-benchmarks")]
@@ -376,9 +701,23 @@ mod benches {
 		[pallet_timestamp, Timestamp]
 		[pallet_quadratic_voting, QuadraticVoting]
 		[pallet_identity, Identity]
+		[pallet_assets, Assets]
+		[pallet_scheduler, Scheduler]
+		[pallet_preimage, Preimage]
 	);
 }
 
+/// Pool validity of `uxt` if it is a quadratic voting call that is expected to be fee-less.
+fn free_call_validity(uxt: &UncheckedExtrinsic) -> Option<ValidTransaction> {
+	let (address, _, _) = uxt.signature.as_ref()?;
+	let who = <Runtime as frame_system::Config>::Lookup::lookup(address.clone()).ok()?;
+	match &uxt.function {
+		Call::QuadraticVoting(call) => QuadraticVoting::free_call_validity(&who, call),
+		Call::EngineeringGuild(call) => EngineeringGuild::free_call_validity(&who, call),
+		_ => None,
+	}
+}
+
 impl_runtime_apis! {
 	impl sp_api::Core<Block> for Runtime {
 		fn version() -> RuntimeVersion {
@@ -427,7 +766,13 @@ impl_runtime_apis! {
 			tx: <Block as BlockT>::Extrinsic,
 			block_hash: <Block as BlockT>::Hash,
 		) -> TransactionValidity {
-			Executive::validate_transaction(source, tx, block_hash)
+			let mut validity = Executive::validate_transaction(source, tx.clone(), block_hash)?;
+			// Checked after the executive has set up the block the transaction is validated for.
+			if let Some(free_call) = free_call_validity(&tx) {
+				validity.priority = free_call.priority;
+				validity.provides.extend(free_call.provides);
+			}
+			Ok(validity)
 		}
 	}
 
@@ -500,13 +845,83 @@ impl_runtime_apis! {
 			uxt: <Block as BlockT>::Extrinsic,
 			len: u32,
 		) -> pallet_transaction_payment_rpc_runtime_api::RuntimeDispatchInfo<Balance> {
-			TransactionPayment::query_info(uxt, len)
+			// Calls the pool takes as fee-less are charged upfront too, but refunded once they
+			// succeed.
+			let free = free_call_validity(&uxt).is_some();
+			let mut info = TransactionPayment::query_info(uxt, len);
+			if free {
+				info.partial_fee = 0;
+			}
+			info
 		}
 		fn query_fee_details(
 			uxt: <Block as BlockT>::Extrinsic,
 			len: u32,
 		) -> pallet_transaction_payment::FeeDetails<Balance> {
-			TransactionPayment::query_fee_details(uxt, len)
+			let free = free_call_validity(&uxt).is_some();
+			let mut details = TransactionPayment::query_fee_details(uxt, len);
+			if free {
+				details.inclusion_fee = None;
+			}
+			details
+		}
+	}
+
+	impl pallet_quadratic_voting::runtime_api::QuadraticVotingApi<
+		Block,
+		AccountId,
+		Balance,
+		BlockNumber,
+		QuadraticVotingInstance,
+	> for Runtime {
+		fn proposal(
+			instance: QuadraticVotingInstance,
+			index: pallet_quadratic_voting::ProposalIndex,
+		) -> Option<pallet_quadratic_voting::ProposalInfo<AccountId, BlockNumber>> {
+			with_instance!(instance, Pallet => Pallet::proposal_info(index))
+		}
+
+		fn active_proposals(
+			instance: QuadraticVotingInstance,
+		) -> Vec<pallet_quadratic_voting::ProposalIndex> {
+			with_instance!(instance, Pallet => Pallet::active_proposals())
+		}
+
+		fn vote_of(
+			instance: QuadraticVotingInstance,
+			index: pallet_quadratic_voting::ProposalIndex,
+			who: AccountId,
+		) -> Option<pallet_quadratic_voting::VoteInfo<Balance, BlockNumber>> {
+			with_instance!(instance, Pallet => Pallet::vote_of(index, &who))
+		}
+
+		fn account_history(
+			instance: QuadraticVotingInstance,
+			who: AccountId,
+		) -> pallet_quadratic_voting::AccountHistory<Balance, BlockNumber> {
+			with_instance!(instance, Pallet => Pallet::account_history(&who))
+		}
+
+		fn quote_cost(instance: QuadraticVotingInstance, votes: u128) -> Option<Balance> {
+			with_instance!(instance, Pallet => Pallet::quote_cost(votes))
+		}
+
+		fn eligibility(
+			instance: QuadraticVotingInstance,
+			index: pallet_quadratic_voting::ProposalIndex,
+			who: AccountId,
+		) -> Result<(), pallet_quadratic_voting::Ineligibility> {
+			with_instance!(instance, Pallet => Pallet::eligibility(index, &who))
+		}
+
+		fn dry_run_vote(
+			instance: QuadraticVotingInstance,
+			index: pallet_quadratic_voting::ProposalIndex,
+			who: AccountId,
+			aye: bool,
+			votes: u128,
+		) -> Result<(), sp_runtime::DispatchError> {
+			with_instance!(instance, Pallet => Pallet::dry_run_vote(index, who, aye, votes))
 		}
 	}
 
@@ -572,7 +987,11 @@ impl_runtime_apis! {
 		}
 
 		fn execute_block_no_check(block: Block) -> Weight {
-			Executive::execute_block_no_check(block)
+			let weight = Executive::execute_block_no_check(block);
+			// Pallet hooks can't check the state after a block in this version of FRAME.
+			QuadraticVoting::do_try_state().unwrap();
+			EngineeringGuild::do_try_state().unwrap();
+			weight
 		}
 	}
 }